@@ -10,29 +10,43 @@ use margined_common::validate::{
 use margined_perp::margined_engine::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 use crate::error::ContractError;
-use crate::handle::{trigger_tp_sl, update_tp_sl};
-use crate::query::{query_last_position_id, query_positions};
+use crate::handle::{
+    trigger_tp_sl, trigger_tp_sl_batch_position, trigger_tp_sl_many, update_tp_sl,
+};
+use crate::query::{
+    query_last_position_id, query_limit_order, query_limit_orders, query_liquidatable_positions,
+    query_positions, query_triggerable_positions,
+};
 use crate::state::init_last_position_id;
-use crate::tick::{query_tick, query_ticks};
+use crate::tick::{query_limit_order_tick, query_limit_order_ticks, query_tick, query_ticks};
 use crate::utils::get_margin_ratio_calc_option;
 use crate::{
     handle::{
-        close_position, deposit_margin, liquidate, open_position, pay_funding, update_config,
-        withdraw_margin,
+        adjust_vamm_k, cancel_limit_order, close_position, deposit_collateral, deposit_margin,
+        deregister_collateral, execute_limit_orders, fill_batch_limit_order, liquidate,
+        liquidate_batch_position, liquidate_many, open_position, pay_funding, place_limit_order,
+        register_collateral, repeg_vamm, settle_position, update_config, update_fee_routing,
+        update_fee_tiers, withdraw_collateral, withdraw_margin,
     },
     query::{
-        query_all_positions, query_config, query_cumulative_premium_fraction,
-        query_free_collateral, query_margin_ratio, query_pauser, query_position,
-        query_position_notional_unrealized_pnl, query_state,
-        query_trader_balance_with_funding_payment, query_trader_position_with_funding_payment,
+        query_account_free_collateral, query_account_margin_ratio, query_all_collateral,
+        query_all_positions, query_collateral, query_collateral_value, query_config,
+        query_cumulative_premium_fraction, query_fee_routing, query_fee_tiers,
+        query_free_collateral, query_is_reduce_only, query_margin_ratio, query_pauser,
+        query_position, query_position_funding_payments, query_position_notional_unrealized_pnl,
+        query_state, query_trader_balance_with_funding_payment, query_trader_fee_tier,
+        query_trader_position_with_funding_payment,
     },
     reply::{
-        close_position_reply, liquidate_reply, partial_close_position_reply,
-        partial_liquidation_reply, pay_funding_reply, update_position_reply,
+        adjust_vamm_reply, batch_limit_order_reply, batch_position_reply, close_position_reply,
+        insurance_payout_reply, insurance_withdrawal_reply, limit_order_fill_failure_reply,
+        liquidate_reply, partial_close_position_reply, partial_liquidation_reply,
+        pay_funding_reply, update_position_reply,
     },
     state::{store_config, store_state, Config, State},
     utils::{
-        add_whitelist, parse_pay_funding, parse_swap, remove_whitelist, set_pause, update_pauser,
+        add_whitelist, parse_adjust_vamm, parse_batch_position, parse_insurance_withdrawal,
+        parse_pay_funding, parse_swap, remove_whitelist, set_pause, set_reduce_only, update_pauser,
     },
 };
 
@@ -51,8 +65,16 @@ pub const PARTIAL_CLOSE_POSITION_REPLY_ID: u64 = 3;
 pub const LIQUIDATION_REPLY_ID: u64 = 4;
 pub const PARTIAL_LIQUIDATION_REPLY_ID: u64 = 5;
 pub const PAY_FUNDING_REPLY_ID: u64 = 6;
+pub const ADJUST_VAMM_REPLY_ID: u64 = 7;
+pub const LIMIT_ORDER_FILL_REPLY_ID: u64 = 8;
 
 pub const TRANSFER_FAILURE_REPLY_ID: u64 = 9;
+pub const BATCH_POSITION_REPLY_ID: u64 = 10;
+pub const INSURANCE_WITHDRAWAL_REPLY_ID: u64 = 11;
+pub const INSURANCE_PAYOUT_REPLY_ID: u64 = 12;
+pub const BATCH_LIMIT_ORDER_REPLY_ID: u64 = 13;
+
+pub const FEE_ROUTING_TOTAL_WEIGHT: u64 = 10_000; // basis points
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -102,6 +124,8 @@ pub fn instantiate(
         partial_liquidation_ratio: Uint128::zero(), // set as zero by default
         tp_sl_spread: msg.tp_sl_spread,
         liquidation_fee: msg.liquidation_fee,
+        pricefeed: None,
+        execution_fee: Uint128::zero(), // set as zero by default
     };
 
     // Initialize last position id
@@ -115,6 +139,7 @@ pub fn instantiate(
         &State {
             open_interest_notional: Uint128::zero(),
             prepaid_bad_debt: Uint128::zero(),
+            uncovered_bad_debt: Uint128::zero(),
             pause: false,
         },
     )?;
@@ -136,6 +161,8 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             partial_liquidation_ratio,
             tp_sl_spread,
             liquidation_fee,
+            pricefeed,
+            execution_fee,
         } => update_config(
             deps,
             info,
@@ -147,10 +174,20 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             partial_liquidation_ratio,
             tp_sl_spread,
             liquidation_fee,
+            pricefeed,
+            execution_fee,
         ),
         ExecuteMsg::UpdatePauser { pauser } => update_pauser(deps, info, pauser),
         ExecuteMsg::AddWhitelist { address } => add_whitelist(deps, info, address),
         ExecuteMsg::RemoveWhitelist { address } => remove_whitelist(deps, info, address),
+        ExecuteMsg::RegisterCollateral {
+            asset,
+            risk_factor,
+            price_key,
+        } => register_collateral(deps, info, asset, risk_factor, price_key),
+        ExecuteMsg::DeregisterCollateral { asset } => deregister_collateral(deps, info, asset),
+        ExecuteMsg::UpdateFeeRouting { fee_routing } => update_fee_routing(deps, info, fee_routing),
+        ExecuteMsg::UpdateFeeTiers { fee_tiers } => update_fee_tiers(deps, info, fee_tiers),
         ExecuteMsg::OpenPosition {
             vamm,
             side,
@@ -159,6 +196,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             take_profit,
             stop_loss,
             base_asset_limit,
+            margin_mode,
         } => open_position(
             deps,
             env,
@@ -170,7 +208,38 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             take_profit,
             stop_loss,
             base_asset_limit,
+            margin_mode,
         ),
+        ExecuteMsg::PlaceLimitOrder {
+            vamm,
+            side,
+            margin_amount,
+            leverage,
+            limit_price,
+            take_profit,
+            stop_loss,
+            expiry,
+            margin_mode,
+        } => place_limit_order(
+            deps,
+            env,
+            info,
+            vamm,
+            side,
+            margin_amount,
+            leverage,
+            limit_price,
+            take_profit,
+            stop_loss,
+            expiry,
+            margin_mode,
+        ),
+        ExecuteMsg::CancelLimitOrder { vamm, order_id } => {
+            cancel_limit_order(deps, env, info, vamm, order_id)
+        }
+        ExecuteMsg::ExecuteLimitOrders { vamm, limit } => {
+            execute_limit_orders(deps, env, vamm, limit)
+        }
         ExecuteMsg::UpdateTpSl {
             vamm,
             position_id,
@@ -182,17 +251,63 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             position_id,
             quote_asset_limit,
         } => close_position(deps, env, info, vamm, position_id, quote_asset_limit),
+        ExecuteMsg::SettlePosition { vamm, position_id } => {
+            settle_position(deps, env, info, vamm, position_id)
+        }
         ExecuteMsg::Liquidate {
             vamm,
             position_id,
             quote_asset_limit,
         } => liquidate(deps, env, info, vamm, position_id, quote_asset_limit),
+        ExecuteMsg::LiquidateMany {
+            vamm,
+            position_ids,
+            quote_asset_limits,
+        } => liquidate_many(deps, env, info, vamm, position_ids, quote_asset_limits),
         ExecuteMsg::TriggerTpSl {
             vamm,
             position_id,
             quote_asset_limit,
         } => trigger_tp_sl(deps, env, info, vamm, position_id, quote_asset_limit),
+        ExecuteMsg::TriggerTpSlMany {
+            vamm,
+            position_ids,
+            quote_asset_limits,
+        } => trigger_tp_sl_many(deps, env, info, vamm, position_ids, quote_asset_limits),
+        ExecuteMsg::TriggerTpSlBatchPosition {
+            vamm,
+            position_id,
+            quote_asset_limit,
+            keeper,
+        } => trigger_tp_sl_batch_position(
+            deps,
+            env,
+            info,
+            vamm,
+            position_id,
+            quote_asset_limit,
+            keeper,
+        ),
+        ExecuteMsg::LiquidateBatchPosition {
+            vamm,
+            position_id,
+            quote_asset_limit,
+            liquidator,
+        } => liquidate_batch_position(
+            deps,
+            env,
+            info,
+            vamm,
+            position_id,
+            quote_asset_limit,
+            liquidator,
+        ),
+        ExecuteMsg::FillBatchLimitOrder { vamm, order_id } => {
+            fill_batch_limit_order(deps, env, info, vamm, order_id)
+        }
         ExecuteMsg::PayFunding { vamm } => pay_funding(deps, env, info, vamm),
+        ExecuteMsg::RepegVamm { vamm, target_price } => repeg_vamm(deps, info, vamm, target_price),
+        ExecuteMsg::AdjustVammK { vamm, scale } => adjust_vamm_k(deps, info, vamm, scale),
         ExecuteMsg::DepositMargin {
             vamm,
             position_id,
@@ -203,18 +318,36 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             position_id,
             amount,
         } => withdraw_margin(deps, env, info, vamm, position_id, amount),
+        ExecuteMsg::DepositCollateral {
+            vamm,
+            position_id,
+            asset,
+            amount,
+        } => deposit_collateral(deps, env, info, vamm, position_id, asset, amount),
+        ExecuteMsg::WithdrawCollateral {
+            vamm,
+            position_id,
+            amount,
+        } => withdraw_collateral(deps, info, vamm, position_id, amount),
         ExecuteMsg::SetPause { pause } => set_pause(deps, env, info, pause),
+        ExecuteMsg::SetReduceOnly { vamm, reduce_only } => {
+            set_reduce_only(deps, info, vamm, reduce_only)
+        }
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::FeeRouting {} => to_binary(&query_fee_routing(deps)?),
+        QueryMsg::FeeTiers {} => to_binary(&query_fee_tiers(deps)?),
+        QueryMsg::TraderFeeTier { trader } => to_binary(&query_trader_fee_tier(deps, env, trader)?),
         QueryMsg::State {} => to_binary(&query_state(deps)?),
         QueryMsg::GetPauser {} => to_binary(&query_pauser(deps)?),
         QueryMsg::IsWhitelisted { address } => to_binary(&WHITELIST.query_hook(deps, address)?),
         QueryMsg::GetWhitelist {} => to_binary(&WHITELIST.query_hooks(deps)?),
+        QueryMsg::IsReduceOnly { vamm } => to_binary(&query_is_reduce_only(deps, vamm)?),
         QueryMsg::AllPositions {
             trader,
             start_after,
@@ -243,6 +376,21 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             limit,
             order_by,
         )?),
+        QueryMsg::LiquidatablePositions {
+            vamm,
+            calc_option,
+            start_after,
+            limit,
+        } => to_binary(&query_liquidatable_positions(
+            deps,
+            vamm,
+            calc_option,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::TriggerablePositions { vamm, side, limit } => {
+            to_binary(&query_triggerable_positions(deps, vamm, side, limit)?)
+        }
         QueryMsg::Position { vamm, position_id } => {
             to_binary(&query_position(deps, vamm, position_id)?)
         }
@@ -265,6 +413,42 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             side,
             entry_price,
         } => to_binary(&query_tick(deps, vamm, side, entry_price)?),
+        QueryMsg::LimitOrder { vamm, order_id } => {
+            to_binary(&query_limit_order(deps, vamm, order_id)?)
+        }
+        QueryMsg::LimitOrders {
+            vamm,
+            trader,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&query_limit_orders(
+            deps,
+            vamm,
+            trader,
+            start_after,
+            limit,
+            order_by,
+        )?),
+        QueryMsg::LimitOrderTicks {
+            vamm,
+            side,
+            start_after,
+            limit,
+            order_by,
+        } => to_binary(&query_limit_order_ticks(
+            deps,
+            vamm,
+            side,
+            start_after,
+            limit,
+            order_by,
+        )?),
+        QueryMsg::LimitOrderTick {
+            vamm,
+            side,
+            limit_price,
+        } => to_binary(&query_limit_order_tick(deps, vamm, side, limit_price)?),
         QueryMsg::MarginRatio { vamm, position_id } => {
             to_binary(&query_margin_ratio(deps, vamm, position_id)?)
         }
@@ -294,13 +478,36 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::FreeCollateral { vamm, position_id } => {
             to_binary(&query_free_collateral(deps, vamm, position_id)?)
         }
+        QueryMsg::AccountMarginRatio { trader } => {
+            to_binary(&query_account_margin_ratio(deps, trader)?)
+        }
+        QueryMsg::AccountFreeCollateral { trader } => {
+            to_binary(&query_account_free_collateral(deps, trader)?)
+        }
         QueryMsg::BalanceWithFundingPayment { position_id } => to_binary(
             &query_trader_balance_with_funding_payment(deps, position_id)?,
         ),
         QueryMsg::PositionWithFundingPayment { vamm, position_id } => to_binary(
             &query_trader_position_with_funding_payment(deps, vamm, position_id)?,
         ),
+        QueryMsg::PositionFundingPayments {
+            vamm,
+            position_id,
+            start_after,
+            limit,
+        } => to_binary(&query_position_funding_payments(
+            deps,
+            vamm,
+            position_id,
+            start_after,
+            limit,
+        )?),
         QueryMsg::LastPositionId {} => to_binary(&query_last_position_id(deps)?),
+        QueryMsg::Collateral { asset } => to_binary(&query_collateral(deps, asset)?),
+        QueryMsg::AllCollateral {} => to_binary(&query_all_collateral(deps)?),
+        QueryMsg::CollateralValue { vamm, position_id } => {
+            to_binary(&query_collateral_value(deps, vamm, position_id)?)
+        }
     }
 }
 
@@ -308,7 +515,7 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
 pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
     match &msg.result {
         SubMsgResult::Ok(response) => match msg.id {
-            INCREASE_POSITION_REPLY_ID => {
+            INCREASE_POSITION_REPLY_ID | LIMIT_ORDER_FILL_REPLY_ID => {
                 let (input, output, position_id) = parse_swap(response)?;
                 let response = update_position_reply(deps, env, input, output, position_id)?;
                 Ok(response)
@@ -334,8 +541,33 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
                 Ok(response)
             }
             PAY_FUNDING_REPLY_ID => {
-                let (premium_fraction, sender) = parse_pay_funding(response)?;
-                let response = pay_funding_reply(deps, env, premium_fraction, sender)?;
+                let (premium_fraction, funding_id, sender) = parse_pay_funding(response)?;
+                let response = pay_funding_reply(deps, env, premium_fraction, funding_id, sender)?;
+                Ok(response)
+            }
+            ADJUST_VAMM_REPLY_ID => {
+                let (adjustment_cost, sender) = parse_adjust_vamm(response)?;
+                let response = adjust_vamm_reply(deps, env, adjustment_cost, sender)?;
+                Ok(response)
+            }
+            BATCH_POSITION_REPLY_ID => {
+                let action = parse_batch_position(response)?;
+                let response = batch_position_reply(deps, action)?;
+                Ok(response)
+            }
+            BATCH_LIMIT_ORDER_REPLY_ID => {
+                let action = parse_batch_position(response)?;
+                let response = batch_limit_order_reply(deps, action)?;
+                Ok(response)
+            }
+            INSURANCE_WITHDRAWAL_REPLY_ID => {
+                let shortfall = parse_insurance_withdrawal(response)?;
+                let response = insurance_withdrawal_reply(deps, shortfall)?;
+                Ok(response)
+            }
+            INSURANCE_PAYOUT_REPLY_ID => {
+                let shortfall = parse_insurance_withdrawal(response)?;
+                let response = insurance_payout_reply(deps, shortfall)?;
                 Ok(response)
             }
             _ => Err(StdError::generic_err(format!(
@@ -352,6 +584,9 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
                 "open position failure - reply (id {:?})",
                 msg.id
             ))),
+            LIMIT_ORDER_FILL_REPLY_ID => limit_order_fill_failure_reply(deps, e),
+            BATCH_POSITION_REPLY_ID => batch_position_reply(deps, &format!("failed: {}", e)),
+            BATCH_LIMIT_ORDER_REPLY_ID => batch_limit_order_reply(deps, &format!("failed: {}", e)),
             CLOSE_POSITION_REPLY_ID => Err(StdError::generic_err(format!(
                 "close position failure - reply (id {:?})",
                 msg.id
@@ -372,6 +607,16 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
                 "funding payment failure - reply (id {:?})",
                 msg.id
             ))),
+            ADJUST_VAMM_REPLY_ID => Err(StdError::generic_err(format!(
+                "vamm adjustment failure - reply (id {:?})",
+                msg.id
+            ))),
+            INSURANCE_WITHDRAWAL_REPLY_ID | INSURANCE_PAYOUT_REPLY_ID => {
+                Err(StdError::generic_err(format!(
+                    "insurance fund withdrawal failure - reply (id {:?})",
+                    msg.id
+                )))
+            }
             _ => Err(StdError::generic_err(format!(
                 "reply (id {:?}) error {:?}",
                 msg.id, e
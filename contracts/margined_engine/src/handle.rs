@@ -1,41 +1,57 @@
 use cosmwasm_std::{
-    Addr, Attribute, DepsMut, Env, MessageInfo, QuerierWrapper, Response, StdError, StdResult,
-    SubMsg, Uint128,
+    Addr, Attribute, Deps, DepsMut, Env, MessageInfo, QuerierWrapper, Response, StdError,
+    StdResult, SubMsg, Uint128,
 };
 use margined_utils::contracts::helpers::VammController;
 
 use crate::{
     contract::{
-        CLOSE_POSITION_REPLY_ID, INCREASE_POSITION_REPLY_ID, LIQUIDATION_REPLY_ID,
-        PARTIAL_CLOSE_POSITION_REPLY_ID, PARTIAL_LIQUIDATION_REPLY_ID, PAY_FUNDING_REPLY_ID,
+        ADJUST_VAMM_REPLY_ID, BATCH_LIMIT_ORDER_REPLY_ID, BATCH_POSITION_REPLY_ID,
+        CLOSE_POSITION_REPLY_ID, FEE_ROUTING_TOTAL_WEIGHT, INCREASE_POSITION_REPLY_ID,
+        LIMIT_ORDER_FILL_REPLY_ID, LIQUIDATION_REPLY_ID, PARTIAL_CLOSE_POSITION_REPLY_ID,
+        PARTIAL_LIQUIDATION_REPLY_ID, PAY_FUNDING_REPLY_ID,
+    },
+    messages::{execute_transfer, execute_transfer_collateral, execute_transfer_from, withdraw},
+    query::{
+        calc_liquidation_margin_ratio, query_account_free_collateral, query_account_margin_ratio,
+        query_free_collateral, query_margin_ratio,
     },
-    messages::{execute_transfer_from, withdraw},
-    query::{query_free_collateral, query_margin_ratio},
     state::{
-        increase_last_position_id, read_config, read_position, read_state, store_config,
-        store_position, store_sent_funds, store_state, store_tmp_liquidator, store_tmp_swap,
-        SentFunds, TmpSwapInfo,
+        increase_last_order_id, increase_last_position_id, read_collateral, read_config,
+        read_crossed_limit_orders, read_limit_order, read_position, read_state, remove_collateral,
+        remove_limit_order, remove_position, store_collateral, store_config, store_fee_routing,
+        store_fee_tiers, store_limit_order, store_position, store_sent_funds, store_state,
+        store_tmp_batch, store_tmp_keeper, store_tmp_limit_order, store_tmp_liquidator,
+        store_tmp_swap, Config, SentFunds, TmpSwapInfo, DEFAULT_LIMIT, MAX_LIMIT,
     },
     utils::{
-        calc_remain_margin_with_funding_payment, direction_to_side, get_asset,
-        get_margin_ratio_calc_option, get_position_notional_unrealized_pnl, keccak_256,
-        position_to_side, require_additional_margin, require_bad_debt, require_insufficient_margin,
-        require_non_zero_input, require_not_paused, require_not_restriction_mode,
-        require_position_not_zero, require_vamm, side_to_direction,
+        apply_fee_discount, calc_remain_margin_with_funding_payment, convert_collateral,
+        cover_cross_margin_loss, direction_to_side, get_asset,
+        get_position_notional_unrealized_pnl, keccak_256, position_to_side, realize_bad_debt,
+        require_additional_margin, require_bad_debt, require_insufficient_margin,
+        require_non_zero_input, require_not_paused, require_not_reduce_only,
+        require_not_restriction_mode, require_position_not_zero, require_vamm, side_to_direction,
+        stop_loss_reached, take_profit_reached, update_open_interest_notional,
     },
 };
 use margined_common::{
     asset::{Asset, AssetInfo},
     integer::Integer,
     messages::wasm_execute,
-    validate::{validate_margin_ratios, validate_ratio},
+    validate::{validate_eligible_collateral, validate_margin_ratios, validate_ratio},
 };
-use margined_perp::margined_vamm::{Direction, ExecuteMsg, QueryMsg};
+use margined_perp::margined_vamm::{CalcFeeResponse, Direction, ExecuteMsg, QueryMsg};
 use margined_perp::{
-    margined_engine::{PnlCalcOption, Position, PositionUnrealizedPnlResponse, Side},
+    margined_engine::{
+        Collateral, ExecuteMsg as EngineExecuteMessage, FeeRecipient, FeeRouting, FeeTier,
+        LimitOrder, MarginMode, PnlCalcOption, Position, PositionUnrealizedPnlResponse,
+        RemainMarginResponse, Side,
+    },
     margined_vamm::ConfigResponse,
 };
 
+const PARTIAL_LIQUIDATION: &str = "partial_liquidation";
+
 #[allow(clippy::too_many_arguments)]
 pub fn update_config(
     deps: DepsMut,
@@ -48,6 +64,8 @@ pub fn update_config(
     partial_liquidation_ratio: Option<Uint128>,
     tp_sl_spread: Option<Uint128>,
     liquidation_fee: Option<Uint128>,
+    pricefeed: Option<String>,
+    execution_fee: Option<Uint128>,
 ) -> StdResult<Response> {
     let mut config = read_config(deps.storage)?;
 
@@ -103,11 +121,191 @@ pub fn update_config(
         config.liquidation_fee = liquidation_fee;
     }
 
+    // update pricefeed used to value collateral
+    if let Some(pricefeed) = pricefeed {
+        config.pricefeed = Some(deps.api.addr_validate(pricefeed.as_str())?);
+    }
+
+    // update TP/SL keeper execution fee
+    if let Some(execution_fee) = execution_fee {
+        validate_ratio(execution_fee, config.decimals)?;
+        config.execution_fee = execution_fee;
+    }
+
     store_config(deps.storage, &config)?;
 
     Ok(Response::default().add_attribute("action", "update_config"))
 }
 
+/// Registers an asset as eligible collateral, registering it again updates it
+pub fn register_collateral(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: String,
+    risk_factor: Uint128,
+    price_key: String,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let asset = validate_eligible_collateral(deps.as_ref(), asset)?;
+
+    // the quote asset is always eligible and is never haircut
+    if asset.equal(&config.eligible_collateral) {
+        return Err(StdError::generic_err(
+            "Quote asset cannot be registered as collateral",
+        ));
+    }
+
+    // risk factor must be 0 < risk_factor <= 1
+    require_non_zero_input(risk_factor)?;
+    validate_ratio(risk_factor, config.decimals)?;
+
+    let decimals = asset.get_decimals(&deps.querier)?;
+
+    store_collateral(
+        deps.storage,
+        &Collateral {
+            asset: asset.clone(),
+            decimals: Uint128::from(10u128.pow(decimals as u32)),
+            risk_factor,
+            price_key: price_key.clone(),
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "register_collateral"),
+        ("asset", &asset.to_string()),
+        ("risk_factor", &risk_factor.to_string()),
+        ("price_key", &price_key),
+    ]))
+}
+
+/// Deregisters an eligible collateral, positions holding it no longer get its value
+pub fn deregister_collateral(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: String,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let asset = validate_eligible_collateral(deps.as_ref(), asset)?;
+    read_collateral(deps.storage, &asset)?;
+
+    remove_collateral(deps.storage, &asset);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "deregister_collateral"),
+        ("asset", &asset.to_string()),
+    ]))
+}
+
+/// Updates the recipients of the spread fee, toll fee and liquidation penalty
+pub fn update_fee_routing(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_routing: FeeRouting,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let fee_routing = FeeRouting {
+        spread_fee: validate_fee_recipients(deps.as_ref(), fee_routing.spread_fee)?,
+        toll_fee: validate_fee_recipients(deps.as_ref(), fee_routing.toll_fee)?,
+        liquidation_fee: validate_fee_recipients(deps.as_ref(), fee_routing.liquidation_fee)?,
+    };
+
+    store_fee_routing(deps.storage, &fee_routing)?;
+
+    Ok(Response::default().add_attribute("action", "update_fee_routing"))
+}
+
+// each fee must be routed in full to a non-empty list of recipients
+fn validate_fee_recipients(
+    deps: Deps,
+    recipients: Vec<FeeRecipient>,
+) -> StdResult<Vec<FeeRecipient>> {
+    if recipients.is_empty() {
+        return Err(StdError::generic_err("fee recipients cannot be empty"));
+    }
+
+    let mut total_weight = 0u64;
+    let mut validated = vec![];
+    for fee_recipient in recipients {
+        if fee_recipient.weight == 0 {
+            return Err(StdError::generic_err("fee weight cannot be zero"));
+        }
+        total_weight = total_weight.saturating_add(fee_recipient.weight);
+
+        validated.push(FeeRecipient {
+            recipient: deps
+                .api
+                .addr_validate(&fee_recipient.recipient)?
+                .to_string(),
+            weight: fee_recipient.weight,
+        });
+    }
+
+    if total_weight != FEE_ROUTING_TOTAL_WEIGHT {
+        return Err(StdError::generic_err(format!(
+            "fee weights must sum to {}",
+            FEE_ROUTING_TOTAL_WEIGHT
+        )));
+    }
+
+    Ok(validated)
+}
+
+/// Updates the fee schedule, traders reaching the volume of a tier get its discount on fees
+pub fn update_fee_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_tiers: Vec<FeeTier>,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    for (index, fee_tier) in fee_tiers.iter().enumerate() {
+        // discount must be 0 <= discount <= 1
+        validate_ratio(fee_tier.discount, config.decimals)?;
+
+        if index > 0 {
+            let previous = &fee_tiers[index - 1];
+            if fee_tier.min_volume <= previous.min_volume {
+                return Err(StdError::generic_err(
+                    "fee tiers must be ordered by ascending volume",
+                ));
+            }
+            if fee_tier.discount < previous.discount {
+                return Err(StdError::generic_err(
+                    "fee tier discounts cannot decrease with volume",
+                ));
+            }
+        }
+    }
+
+    store_fee_tiers(deps.storage, &fee_tiers)?;
+
+    Ok(Response::default().add_attribute("action", "update_fee_tiers"))
+}
+
 // Opens a position
 #[allow(clippy::too_many_arguments)]
 pub fn open_position(
@@ -121,15 +319,18 @@ pub fn open_position(
     take_profit: Uint128,
     stop_loss: Option<Uint128>,
     base_asset_limit: Uint128,
+    margin_mode: Option<MarginMode>,
 ) -> StdResult<Response> {
     // validate address inputs
     let vamm = deps.api.addr_validate(&vamm)?;
     let trader = info.sender.clone();
+    let margin_mode = margin_mode.unwrap_or_default();
 
     let state = read_state(deps.storage)?;
     require_not_paused(state.pause)?;
     let config = read_config(deps.storage)?;
     require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+    require_not_reduce_only(deps.storage, &vamm)?;
     let position_id = increase_last_position_id(deps.storage)?;
 
     require_not_restriction_mode(deps.storage, &vamm, env.block.height)?;
@@ -198,7 +399,10 @@ pub fn open_position(
         take_profit: Uint128::zero(),
         stop_loss: Some(Uint128::zero()),
         last_updated_premium_fraction: Integer::zero(),
+        last_updated_funding_id: 0u64,
         block_time: 0u64,
+        margin_mode,
+        collateral: None,
     };
 
     // calculate the position notional
@@ -206,6 +410,16 @@ pub fn open_position(
         .checked_mul(leverage)?
         .checked_div(config.decimals)?;
 
+    // fees are quoted before the swap moves the vamm
+    let CalcFeeResponse {
+        spread_fee,
+        toll_fee,
+    } = VammController(vamm.clone()).calc_fee(
+        &deps.querier,
+        open_notional,
+        side_to_direction(&side),
+    )?;
+
     let msg = internal_increase_position(
         vamm.clone(),
         side.clone(),
@@ -231,6 +445,10 @@ pub fn open_position(
             fees_paid: false,
             take_profit,
             stop_loss,
+            margin_mode,
+            escrowed: false,
+            spread_fee,
+            toll_fee,
         },
     )?;
 
@@ -253,144 +471,527 @@ pub fn open_position(
         ("leverage", &leverage.to_string()),
         ("take_profit", &take_profit.to_string()),
         ("stop_loss", &format!("{:?}", stop_loss)),
+        ("margin_mode", &format!("{:?}", margin_mode)),
     ]))
 }
 
-pub fn update_tp_sl(
+/// Places a resting limit order, the margin and the fees quoted at placement are escrowed
+#[allow(clippy::too_many_arguments)]
+pub fn place_limit_order(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     vamm: String,
-    position_id: u64,
-    take_profit: Option<Uint128>,
+    side: Side,
+    margin_amount: Uint128,
+    leverage: Uint128,
+    limit_price: Uint128,
+    take_profit: Uint128,
     stop_loss: Option<Uint128>,
+    expiry: Option<u64>,
+    margin_mode: Option<MarginMode>,
 ) -> StdResult<Response> {
     let vamm = deps.api.addr_validate(&vamm)?;
-    let trader = info.sender;
-
-    // read the position for the trader from vamm
-    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
-    let mut position = read_position(deps.storage, &vamm_key, position_id)?;
+    let trader = info.sender.clone();
+    let margin_mode = margin_mode.unwrap_or_default();
 
     let state = read_state(deps.storage)?;
     require_not_paused(state.pause)?;
-    require_position_not_zero(position.size.value)?;
+    let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+    require_not_reduce_only(deps.storage, &vamm)?;
 
-    if position.trader != trader {
-        return Err(StdError::generic_err("Unauthorized"));
+    require_non_zero_input(margin_amount)?;
+    require_non_zero_input(leverage)?;
+    require_non_zero_input(limit_price)?;
+    require_non_zero_input(take_profit)?;
+
+    if leverage < config.decimals {
+        return Err(StdError::generic_err("Leverage must be greater than 1"));
     }
 
-    if Some(take_profit).is_none() && Some(stop_loss).is_none() {
-        return Err(StdError::generic_err(
-            "Both take profit and stop loss are not set",
-        ));
+    if let Some(expiry) = expiry {
+        if expiry <= env.block.time.seconds() {
+            return Err(StdError::generic_err("Expiry must be in the future"));
+        }
     }
 
-    match position.side {
+    match side {
         Side::Buy => {
-            if let Some(take_profit) = take_profit {
-                if take_profit <= position.entry_price {
-                    return Err(StdError::generic_err("TP price is too low"));
-                }
-                position.take_profit = take_profit;
+            if take_profit <= limit_price {
+                return Err(StdError::generic_err("TP price is too low"));
             }
-
-            if let Some(sl) = stop_loss {
-                if sl > position.entry_price {
+            if let Some(stop_loss) = stop_loss {
+                if stop_loss > limit_price {
                     return Err(StdError::generic_err("SL price is too high"));
                 }
-                position.stop_loss = stop_loss;
             }
         }
         Side::Sell => {
-            if let Some(take_profit) = take_profit {
-                if take_profit >= position.entry_price {
-                    return Err(StdError::generic_err("TP price is too high"));
-                }
+            if take_profit >= limit_price {
+                return Err(StdError::generic_err("TP price is too high"));
             }
-            if let Some(sl) = stop_loss {
-                if sl < position.entry_price {
+            if let Some(stop_loss) = stop_loss {
+                if stop_loss < limit_price {
                     return Err(StdError::generic_err("SL price is too low"));
                 }
-                position.stop_loss = stop_loss;
             }
         }
     }
 
-    store_position(deps.storage, &vamm_key, &position, false)?;
+    // calculate the margin ratio of new position wrt to leverage
+    let margin_ratio = config
+        .decimals
+        .checked_mul(config.decimals)?
+        .checked_div(leverage)?;
 
-    Ok(Response::new().add_attributes(vec![
-        ("action", "update_tp_sl"),
+    require_additional_margin(Integer::from(margin_ratio), config.initial_margin_ratio)?;
+
+    let open_notional = margin_amount
+        .checked_mul(leverage)?
+        .checked_div(config.decimals)?;
+
+    let CalcFeeResponse {
+        spread_fee,
+        toll_fee,
+    } = VammController(vamm.clone()).calc_fee(
+        &deps.querier,
+        open_notional,
+        side_to_direction(&side),
+    )?;
+    let fee_amount = spread_fee.checked_add(toll_fee)?;
+    let escrow_amount = margin_amount.checked_add(fee_amount)?;
+
+    let mut response = Response::new();
+
+    match config.eligible_collateral {
+        AssetInfo::NativeToken { .. } => {
+            Asset {
+                info: config.eligible_collateral,
+                amount: escrow_amount,
+            }
+            .assert_sent_native_token_balance(&info)?;
+        }
+        AssetInfo::Token { .. } => {
+            response = response.add_submessage(execute_transfer_from(
+                deps.storage,
+                &trader,
+                &env.contract.address,
+                escrow_amount,
+            )?);
+        }
+    };
+
+    let order = LimitOrder {
+        order_id: increase_last_order_id(deps.storage)?,
+        vamm: vamm.clone(),
+        trader: trader.clone(),
+        side: side.clone(),
+        margin_amount,
+        leverage,
+        limit_price,
+        take_profit,
+        stop_loss,
+        margin_mode,
+        fee_amount,
+        expiry,
+    };
+
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    store_limit_order(deps.storage, &vamm_key, &order)?;
+
+    Ok(response.add_attributes(vec![
+        ("action", "place_limit_order"),
+        ("order_id", &order.order_id.to_string()),
+        ("order_side", &format!("{:?}", side)),
         ("vamm", vamm.as_ref()),
-        ("pair", &position.pair),
         ("trader", trader.as_ref()),
-        ("position_id", &position_id.to_string()),
+        ("margin_amount", &margin_amount.to_string()),
+        ("leverage", &leverage.to_string()),
+        ("limit_price", &limit_price.to_string()),
+        ("fee_amount", &fee_amount.to_string()),
     ]))
 }
 
-pub fn close_position(
+/// Cancels a limit order and refunds the escrow, expired orders can be cancelled by anyone
+pub fn cancel_limit_order(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     vamm: String,
-    position_id: u64,
-    quote_amount_limit: Uint128,
+    order_id: u64,
 ) -> StdResult<Response> {
-    // validate address inputs
     let vamm = deps.api.addr_validate(&vamm)?;
-    let trader = info.sender;
 
-    // read the position for the trader from vamm
     let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
-    let position = read_position(deps.storage, &vamm_key, position_id)?;
+    let order = read_limit_order(deps.storage, &vamm_key, order_id)?;
 
-    let state = read_state(deps.storage)?;
-
-    if position.trader != trader {
+    let is_expired = order
+        .expiry
+        .map_or(false, |expiry| expiry <= env.block.time.seconds());
+    if order.trader != info.sender && !is_expired {
         return Err(StdError::generic_err("Unauthorized"));
     }
 
-    // check the position isn't zero
-    require_not_paused(state.pause)?;
-    require_position_not_zero(position.size.value)?;
-
-    require_not_restriction_mode(deps.storage, &vamm, env.block.height)?;
+    let msg = refund_limit_order(deps, &vamm_key, &order)?;
 
-    // if it is long position, close a position means short it (which means base dir is AddToAmm) and vice versa
-    let base_direction = if position.size > Integer::zero() {
-        Direction::AddToAmm
-    } else {
-        Direction::RemoveFromAmm
-    };
+    Ok(Response::new().add_submessage(msg).add_attributes(vec![
+        ("action", "cancel_limit_order"),
+        ("order_id", &order_id.to_string()),
+        ("vamm", vamm.as_ref()),
+        ("trader", order.trader.as_ref()),
+    ]))
+}
 
-    let vamm_controller = VammController(vamm.clone());
-    let is_over_fluctuation_limit = vamm_controller.is_over_fluctuation_limit(
-        &deps.querier,
-        Direction::RemoveFromAmm,
-        position.size.value,
-    )?;
+/// Fills every limit order whose limit price has been crossed by the spot price
+pub fn execute_limit_orders(
+    deps: DepsMut,
+    env: Env,
+    vamm: String,
+    limit: Option<u32>,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
 
+    let state = read_state(deps.storage)?;
+    require_not_paused(state.pause)?;
     let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+    require_not_reduce_only(deps.storage, &vamm)?;
+    require_not_restriction_mode(deps.storage, &vamm, env.block.height)?;
 
-    // check if this position exceed fluctuation limit
-    // if over fluctuation limit, then close partial position. Otherwise close all.
-    // if partialLiquidationRatio is 1, then close whole position
-    let msg = if is_over_fluctuation_limit && config.partial_liquidation_ratio < config.decimals {
-        let side = position_to_side(position.size);
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let spot_price = get_spot_price(&deps.querier, &vamm)?;
 
-        let partial_close_amount = position
-            .size
-            .value
-            .checked_mul(config.partial_liquidation_ratio)?
-            .checked_div(config.decimals)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let mut orders =
+        read_crossed_limit_orders(deps.storage, &vamm_key, &Side::Buy, spot_price, limit)?;
+    orders.append(&mut read_crossed_limit_orders(
+        deps.storage,
+        &vamm_key,
+        &Side::Sell,
+        spot_price,
+        limit - orders.len(),
+    )?);
+
+    if orders.is_empty() {
+        return Err(StdError::generic_err("No limit orders have been crossed"));
+    }
 
-        let partial_close_notional =
-            vamm_controller.output_amount(&deps.querier, base_direction, partial_close_amount)?;
+    // each order is filled in its own call, so an order whose fill fails, e.g. because the
+    // position would breach the margin ratio or the open interest cap, is reverted on its own
+    let mut msgs: Vec<SubMsg> = vec![];
+    let mut batch: Vec<u64> = vec![];
+    for order in orders {
+        msgs.push(SubMsg::reply_always(
+            wasm_execute(
+                env.contract.address.to_string(),
+                &EngineExecuteMessage::FillBatchLimitOrder {
+                    vamm: vamm.to_string(),
+                    order_id: order.order_id,
+                },
+                vec![],
+            )?,
+            BATCH_LIMIT_ORDER_REPLY_ID,
+        ));
+        batch.push(order.order_id);
+    }
 
-        let PositionUnrealizedPnlResponse {
-            position_notional,
-            unrealized_pnl,
-        } = get_position_notional_unrealized_pnl(
+    store_tmp_batch(deps.storage, &batch)?;
+
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "execute_limit_orders")
+        .add_attribute("vamm", vamm.as_ref()))
+}
+
+// Fills a single limit order of `execute_limit_orders`, expired orders and orders whose escrow
+// no longer covers the fees are refunded instead
+pub fn fill_batch_limit_order(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    order_id: u64,
+) -> StdResult<Response> {
+    if info.sender != env.contract.address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let order = read_limit_order(deps.storage, &vamm_key, order_id)?;
+
+    let vamm_config = get_vamm_config(&deps.querier, &vamm)?;
+    let pair = format!("{}/{}", vamm_config.base_asset, vamm_config.quote_asset);
+
+    let (msg, position_id) =
+        internal_fill_limit_order(deps, &env, &config, &vamm, &vamm_key, &pair, &order)?;
+
+    let response = Response::new().add_submessage(msg);
+    Ok(match position_id {
+        Some(position_id) => response.add_attributes(vec![
+            ("action", "fill_limit_order"),
+            ("order_id", &order_id.to_string()),
+            ("position_id", &position_id.to_string()),
+            ("trader", order.trader.as_ref()),
+        ]),
+        None => response.add_attributes(vec![
+            ("action", "refund_limit_order"),
+            ("order_id", &order_id.to_string()),
+            ("trader", order.trader.as_ref()),
+        ]),
+    })
+}
+
+// Fills a crossed limit order, expired orders and orders whose escrow no longer covers the fees
+// are refunded instead. Returns the position id of the fill
+fn internal_fill_limit_order(
+    deps: DepsMut,
+    env: &Env,
+    config: &Config,
+    vamm: &Addr,
+    vamm_key: &[u8],
+    pair: &str,
+    order: &LimitOrder,
+) -> StdResult<(SubMsg, Option<u64>)> {
+    let open_notional = order
+        .margin_amount
+        .checked_mul(order.leverage)?
+        .checked_div(config.decimals)?;
+
+    let CalcFeeResponse {
+        spread_fee,
+        toll_fee,
+    } = VammController(vamm.clone()).calc_fee(
+        &deps.querier,
+        open_notional,
+        side_to_direction(&order.side),
+    )?;
+    let fee_amount = spread_fee.checked_add(toll_fee)?;
+
+    let is_expired = order
+        .expiry
+        .map_or(false, |expiry| expiry <= env.block.time.seconds());
+    if is_expired || fee_amount > order.fee_amount {
+        return Ok((refund_limit_order(deps, vamm_key, order)?, None));
+    }
+
+    // the fee tier discount of the trader is refunded with the rest of the escrow
+    let (spread_fee, toll_fee) = apply_fee_discount(
+        deps.storage,
+        &order.trader,
+        env.block.time.seconds(),
+        spread_fee,
+        toll_fee,
+    )?;
+
+    // the swap must not fill the order at a worse average price than its limit price
+    let base_asset_limit = open_notional
+        .checked_mul(config.decimals)?
+        .checked_div(order.limit_price)?;
+
+    remove_limit_order(deps.storage, vamm_key, order)?;
+
+    let position_id = increase_last_position_id(deps.storage)?;
+
+    // kept until the reply, which pays the fees from the escrow or restores the order on failure
+    store_tmp_limit_order(deps.storage, position_id, order)?;
+
+    store_tmp_swap(
+        deps.storage,
+        &TmpSwapInfo {
+            position_id,
+            vamm: vamm.clone(),
+            pair: pair.to_string(),
+            trader: order.trader.clone(),
+            side: order.side.clone(),
+            margin_amount: order.margin_amount,
+            leverage: order.leverage,
+            open_notional,
+            position_notional: Uint128::zero(),
+            unrealized_pnl: Integer::zero(),
+            margin_to_vault: Integer::zero(),
+            fees_paid: true,
+            take_profit: order.take_profit,
+            stop_loss: order.stop_loss,
+            margin_mode: order.margin_mode,
+            escrowed: true,
+            spread_fee,
+            toll_fee,
+        },
+    )?;
+
+    let msg = swap_input(
+        vamm,
+        &order.side,
+        position_id,
+        open_notional,
+        base_asset_limit,
+        false,
+        false,
+        LIMIT_ORDER_FILL_REPLY_ID,
+    )?;
+
+    Ok((msg, Some(position_id)))
+}
+
+// Removes a limit order and refunds the escrowed margin and fees to the trader
+fn refund_limit_order(deps: DepsMut, vamm_key: &[u8], order: &LimitOrder) -> StdResult<SubMsg> {
+    remove_limit_order(deps.storage, vamm_key, order)?;
+
+    execute_transfer(
+        deps.storage,
+        &order.trader,
+        order.margin_amount.checked_add(order.fee_amount)?,
+    )
+}
+
+pub fn update_tp_sl(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    take_profit: Option<Uint128>,
+    stop_loss: Option<Uint128>,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let trader = info.sender;
+
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let mut position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    let state = read_state(deps.storage)?;
+    require_not_paused(state.pause)?;
+    require_position_not_zero(position.size.value)?;
+
+    if position.trader != trader {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    if Some(take_profit).is_none() && Some(stop_loss).is_none() {
+        return Err(StdError::generic_err(
+            "Both take profit and stop loss are not set",
+        ));
+    }
+
+    match position.side {
+        Side::Buy => {
+            if let Some(take_profit) = take_profit {
+                if take_profit <= position.entry_price {
+                    return Err(StdError::generic_err("TP price is too low"));
+                }
+                position.take_profit = take_profit;
+            }
+
+            if let Some(sl) = stop_loss {
+                if sl > position.entry_price {
+                    return Err(StdError::generic_err("SL price is too high"));
+                }
+                position.stop_loss = stop_loss;
+            }
+        }
+        Side::Sell => {
+            if let Some(take_profit) = take_profit {
+                if take_profit >= position.entry_price {
+                    return Err(StdError::generic_err("TP price is too high"));
+                }
+            }
+            if let Some(sl) = stop_loss {
+                if sl < position.entry_price {
+                    return Err(StdError::generic_err("SL price is too low"));
+                }
+                position.stop_loss = stop_loss;
+            }
+        }
+    }
+
+    store_position(deps.storage, &vamm_key, &position, false)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_tp_sl"),
+        ("vamm", vamm.as_ref()),
+        ("pair", &position.pair),
+        ("trader", trader.as_ref()),
+        ("position_id", &position_id.to_string()),
+    ]))
+}
+
+pub fn close_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    quote_amount_limit: Uint128,
+) -> StdResult<Response> {
+    // validate address inputs
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let trader = info.sender;
+
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    let state = read_state(deps.storage)?;
+
+    if position.trader != trader {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    // check the position isn't zero
+    require_not_paused(state.pause)?;
+    require_position_not_zero(position.size.value)?;
+
+    require_not_restriction_mode(deps.storage, &vamm, env.block.height)?;
+
+    // if it is long position, close a position means short it (which means base dir is AddToAmm) and vice versa
+    let base_direction = if position.size > Integer::zero() {
+        Direction::AddToAmm
+    } else {
+        Direction::RemoveFromAmm
+    };
+
+    let vamm_controller = VammController(vamm.clone());
+    let is_over_fluctuation_limit = vamm_controller.is_over_fluctuation_limit(
+        &deps.querier,
+        Direction::RemoveFromAmm,
+        position.size.value,
+    )?;
+
+    let config = read_config(deps.storage)?;
+
+    // check if this position exceed fluctuation limit
+    // if over fluctuation limit, then close partial position. Otherwise close all.
+    // if partialLiquidationRatio is 1, then close whole position
+    let msg = if is_over_fluctuation_limit && config.partial_liquidation_ratio < config.decimals {
+        let side = position_to_side(position.size);
+
+        let partial_close_amount = position
+            .size
+            .value
+            .checked_mul(config.partial_liquidation_ratio)?
+            .checked_div(config.decimals)?;
+
+        let partial_close_notional =
+            vamm_controller.output_amount(&deps.querier, base_direction, partial_close_amount)?;
+
+        let CalcFeeResponse {
+            spread_fee,
+            toll_fee,
+        } = vamm_controller.calc_fee(
+            &deps.querier,
+            partial_close_notional,
+            side_to_direction(&side),
+        )?;
+
+        let PositionUnrealizedPnlResponse {
+            position_notional,
+            unrealized_pnl,
+        } = get_position_notional_unrealized_pnl(
             deps.as_ref(),
             &position,
             PnlCalcOption::SpotPrice,
@@ -413,6 +1014,10 @@ pub fn close_position(
                 fees_paid: false,
                 take_profit: position.take_profit,
                 stop_loss: position.stop_loss,
+                margin_mode: position.margin_mode,
+                escrowed: false,
+                spread_fee,
+                toll_fee,
             },
         )?;
 
@@ -423,6 +1028,7 @@ pub fn close_position(
             partial_close_notional,
             Uint128::zero(),
             true,
+            true,
             PARTIAL_CLOSE_POSITION_REPLY_ID,
         )?
     } else {
@@ -449,10 +1055,130 @@ pub fn close_position(
     ]))
 }
 
+/// Settles a position of a vamm that has been shut down at the settlement price, margin and
+/// pnl are paid out without touching the reserves and bad debt is covered by the insurance fund
+pub fn settle_position(
+    mut deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+) -> StdResult<Response> {
+    // validate address inputs
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    // anyone can settle a position, the payout always goes to the trader
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+    let trader = position.trader.clone();
+
+    let config = read_config(deps.storage)?;
+    let mut state = read_state(deps.storage)?;
+
+    require_not_paused(state.pause)?;
+    require_position_not_zero(position.size.value)?;
+
+    let settlement_price = match VammController(vamm.clone()).settlement_price(&deps.querier)? {
+        Some(settlement_price) => settlement_price,
+        None => return Err(StdError::generic_err("vAMM has not been shut down")),
+    };
+
+    let settled_notional = settlement_price
+        .checked_mul(position.size.value)?
+        .checked_div(config.decimals)?;
+
+    let margin_delta = match &position.direction {
+        Direction::AddToAmm => {
+            Integer::new_positive(settled_notional) - Integer::new_positive(position.notional)
+        }
+        Direction::RemoveFromAmm => {
+            Integer::new_positive(position.notional) - Integer::new_positive(settled_notional)
+        }
+    };
+
+    let RemainMarginResponse {
+        funding_payment,
+        margin,
+        bad_debt,
+        latest_premium_fraction: _,
+        latest_funding_id: _,
+    } = calc_remain_margin_with_funding_payment(deps.as_ref(), position.clone(), margin_delta)?;
+
+    let mut msgs: Vec<SubMsg> = vec![];
+
+    // collateral backing an underwater position is converted to cover the bad debt
+    let (converted, remaining_collateral) =
+        convert_collateral(deps.as_ref(), &position, bad_debt, &mut msgs)?;
+    if let Some(collateral) = &remaining_collateral {
+        msgs.push(execute_transfer_collateral(collateral, &trader, None)?);
+    }
+
+    let mut uncovered_bad_debt = bad_debt.checked_sub(converted)?;
+
+    // cross margin positions settle their loss against the rest of the account
+    if !uncovered_bad_debt.is_zero() && position.margin_mode == MarginMode::Cross {
+        uncovered_bad_debt = cover_cross_margin_loss(deps.branch(), &position, uncovered_bad_debt)?;
+    }
+
+    if !uncovered_bad_debt.is_zero() {
+        realize_bad_debt(
+            deps.as_ref(),
+            &vamm,
+            uncovered_bad_debt,
+            &mut msgs,
+            &mut state,
+        )?;
+    }
+
+    // any shortfall of the vault is withdrawn from the insurance fund
+    if !margin.is_zero() {
+        msgs.append(&mut withdraw(
+            deps.branch(),
+            env,
+            &mut state,
+            &vamm,
+            &trader,
+            config.eligible_collateral,
+            margin,
+            Uint128::zero(),
+        )?);
+    }
+
+    let value =
+        margin_delta + Integer::new_positive(bad_debt) + Integer::new_positive(position.notional);
+
+    update_open_interest_notional(
+        &deps.as_ref(),
+        &mut state,
+        vamm.clone(),
+        value.invert_sign(),
+        trader.clone(),
+    )?;
+
+    let total_position = remove_position(deps.storage, &vamm_key, &position)?;
+
+    store_state(deps.storage, &state)?;
+
+    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
+        ("action", "settle_position"),
+        ("vamm", vamm.as_ref()),
+        ("pair", &position.pair),
+        ("trader", trader.as_ref()),
+        ("position_id", &position_id.to_string()),
+        ("settlement_price", &settlement_price.to_string()),
+        ("total_position", &total_position.to_string()),
+        ("pnl", &margin_delta.to_string()),
+        ("funding_payment", &funding_payment.to_string()),
+        ("bad_debt", &uncovered_bad_debt.to_string()),
+        ("converted_collateral", &converted.to_string()),
+        ("withdraw_amount", &margin.to_string()),
+    ]))
+}
+
 pub fn trigger_tp_sl(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
+    info: MessageInfo,
     vamm: String,
     position_id: u64,
     quote_asset_limit: Uint128,
@@ -462,115 +1188,110 @@ pub fn trigger_tp_sl(
     let config = read_config(deps.storage)?;
     require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
 
-    // read the position for the trader from vamm
-    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
-    let position = read_position(deps.storage, &vamm_key, position_id)?;
+    let (msg, position, action) = internal_trigger_tp_sl(
+        deps,
+        &config,
+        &info.sender,
+        &vamm,
+        position_id,
+        quote_asset_limit,
+    )?;
 
-    // check the position isn't zero
-    require_position_not_zero(position.size.value)?;
+    Ok(Response::new().add_submessage(msg).add_attributes(vec![
+        ("action", action),
+        ("vamm", vamm.as_ref()),
+        ("pair", &position.pair),
+        ("position_id", &position.position_id.to_string()),
+        ("position_side", &format!("{:?}", position.side)),
+        ("trader", position.trader.as_ref()),
+    ]))
+}
 
-    let spot_price = get_spot_price(&deps.querier, &vamm)?;
-    let stop_loss = position.stop_loss.unwrap_or_default();
-    let tp_spread = position
-        .take_profit
-        .checked_mul(config.tp_sl_spread)?
-        .checked_div(config.decimals)?;
-    let sl_spread = stop_loss
-        .checked_mul(config.tp_sl_spread)?
-        .checked_div(config.decimals)?;
+pub fn trigger_tp_sl_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_ids: Vec<u64>,
+    quote_asset_limits: Option<Vec<Uint128>>,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
 
-    let mut msgs: Vec<SubMsg> = vec![];
-    let mut attribute_msgs: Vec<Attribute> = vec![];
+    let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
 
-    // if spot_price is ~ take_profit or stop_loss, close position
-    if position.side == Side::Buy {
-        if spot_price > position.take_profit
-            || position.take_profit.abs_diff(spot_price) <= tp_spread
-        {
-            msgs.push(internal_close_position(
-                deps,
-                &position,
-                quote_asset_limit,
-                CLOSE_POSITION_REPLY_ID,
-            )?);
-            attribute_msgs.push(Attribute {
-                key: "action".to_string(),
-                value: "trigger_take_profit".to_string(),
-            });
-        } else if stop_loss > spot_price
-            || stop_loss > Uint128::zero() && spot_price.abs_diff(stop_loss) <= sl_spread
-        {
-            msgs.push(internal_close_position(
-                deps,
-                &position,
-                quote_asset_limit,
-                CLOSE_POSITION_REPLY_ID,
-            )?);
-            attribute_msgs.push(Attribute {
-                key: "action".to_string(),
-                value: "trigger_stop_loss".to_string(),
-            });
-        } else {
-            return Err(StdError::generic_err("TP/SL price has not been reached"));
+    let quote_asset_limits = batch_quote_asset_limits(&position_ids, quote_asset_limits)?;
+
+    let mut msgs: Vec<SubMsg> = vec![];
+    let mut batch: Vec<u64> = vec![];
+    let mut attribute_msgs: Vec<Attribute> = vec![
+        Attribute::new("action", "trigger_tp_sl_many"),
+        Attribute::new("vamm", vamm.as_str()),
+    ];
+
+    for (index, position_id) in position_ids.iter().enumerate() {
+        // duplicates are only processed once
+        if position_ids[..index].contains(position_id) {
+            continue;
         }
-    } else if position.side == Side::Sell {
-        if position.take_profit > spot_price
-            || spot_price.abs_diff(position.take_profit) <= tp_spread
-        {
-            msgs.push(internal_close_position(
-                deps,
-                &position,
-                quote_asset_limit,
-                CLOSE_POSITION_REPLY_ID,
-            )?);
-            attribute_msgs.push(Attribute {
-                key: "action".to_string(),
-                value: "trigger_take_profit".to_string(),
-            });
-        } else if stop_loss > Uint128::zero() && spot_price > stop_loss
-            || stop_loss.abs_diff(spot_price) <= sl_spread
-        {
-            msgs.push(internal_close_position(
-                deps,
-                &position,
-                quote_asset_limit,
-                CLOSE_POSITION_REPLY_ID,
-            )?);
-            attribute_msgs.push(Attribute {
-                key: "action".to_string(),
-                value: "trigger_stop_loss".to_string(),
-            });
-        } else {
-            return Err(StdError::generic_err("TP/SL price has not been reached"));
+
+        // positions that can no longer be triggered are skipped instead of reverting the batch
+        match check_tp_sl_reached(deps.as_ref(), &config, &vamm, *position_id) {
+            Ok(_) => {
+                msgs.push(batch_position_msg(
+                    &env,
+                    &EngineExecuteMessage::TriggerTpSlBatchPosition {
+                        vamm: vamm.to_string(),
+                        position_id: *position_id,
+                        quote_asset_limit: quote_asset_limits[index],
+                        keeper: info.sender.to_string(),
+                    },
+                )?);
+                batch.push(*position_id);
+            }
+            Err(err) => attribute_msgs.push(Attribute::new(
+                format!("position_{}", position_id),
+                skipped_outcome(err),
+            )),
         }
     }
 
-    attribute_msgs.push(Attribute {
-        key: "vamm".to_string(),
-        value: vamm.to_string(),
-    });
-    attribute_msgs.push(Attribute {
-        key: "pair".to_string(),
-        value: position.pair,
-    });
-    attribute_msgs.push(Attribute {
-        key: "position_id".to_string(),
-        value: position.position_id.to_string(),
-    });
-    attribute_msgs.push(Attribute {
-        key: "position_side".to_string(),
-        value: format!("{:?}", position.side),
-    });
-    attribute_msgs.push(Attribute {
-        key: "trader".to_string(),
-        value: position.trader.to_string(),
-    });
+    store_tmp_batch(deps.storage, &batch)?;
 
     Ok(Response::new()
         .add_submessages(msgs)
         .add_attributes(attribute_msgs))
 }
 
+// Triggers the TP/SL of a single position of `trigger_tp_sl_many` on behalf of the keeper
+pub fn trigger_tp_sl_batch_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    quote_asset_limit: Uint128,
+    keeper: String,
+) -> StdResult<Response> {
+    if info.sender != env.contract.address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let keeper = deps.api.addr_validate(&keeper)?;
+
+    trigger_tp_sl(
+        deps,
+        env,
+        MessageInfo {
+            sender: keeper,
+            funds: vec![],
+        },
+        vamm,
+        position_id,
+        quote_asset_limit,
+    )
+}
+
 pub fn liquidate(
     deps: DepsMut,
     env: Env,
@@ -579,56 +1300,123 @@ pub fn liquidate(
     position_id: u64,
     quote_asset_limit: Uint128,
 ) -> StdResult<Response> {
-    // validate address inputs
-    let vamm = deps.api.addr_validate(&vamm)?;
-
-    // store the liquidator
-    store_tmp_liquidator(deps.storage, &info.sender)?;
-
-    // retrieve the existing margin ratio of the position
-    let mut margin_ratio = query_margin_ratio(deps.as_ref(), vamm.to_string(), position_id)?;
-
-    let vamm_controller = VammController(vamm.clone());
-
-    if vamm_controller.is_over_spread_limit(&deps.querier)? {
-        let oracle_margin_ratio = get_margin_ratio_calc_option(
-            deps.as_ref(),
-            vamm.to_string(),
-            position_id,
-            PnlCalcOption::Oracle,
-        )?;
-
-        if oracle_margin_ratio.checked_sub(margin_ratio)? > Integer::zero() {
-            margin_ratio = oracle_margin_ratio
-        }
+    // validate address inputs
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+
+    let (msg, position, _) = internal_liquidate(
+        deps,
+        env,
+        &config,
+        &info.sender,
+        &vamm,
+        position_id,
+        quote_asset_limit,
+    )?;
+
+    Ok(Response::new().add_submessage(msg).add_attributes(vec![
+        ("action", "liquidate"),
+        ("vamm", vamm.as_ref()),
+        ("pair", &position.pair),
+        ("position_id", &position_id.to_string()),
+        ("trader", position.trader.as_ref()),
+    ]))
+}
+
+pub fn liquidate_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_ids: Vec<u64>,
+    quote_asset_limits: Option<Vec<Uint128>>,
+) -> StdResult<Response> {
+    // validate address inputs
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+
+    let quote_asset_limits = batch_quote_asset_limits(&position_ids, quote_asset_limits)?;
+
+    let mut msgs: Vec<SubMsg> = vec![];
+    let mut batch: Vec<u64> = vec![];
+    let mut attribute_msgs: Vec<Attribute> = vec![
+        Attribute::new("action", "liquidate_many"),
+        Attribute::new("vamm", vamm.as_str()),
+    ];
+
+    for (index, position_id) in position_ids.iter().enumerate() {
+        // duplicates are only processed once
+        if position_ids[..index].contains(position_id) {
+            continue;
+        }
+
+        // positions that are no longer liquidatable are skipped instead of reverting the batch
+        match check_liquidatable(deps.as_ref(), &config, &vamm, *position_id) {
+            Ok(_) => {
+                msgs.push(batch_position_msg(
+                    &env,
+                    &EngineExecuteMessage::LiquidateBatchPosition {
+                        vamm: vamm.to_string(),
+                        position_id: *position_id,
+                        quote_asset_limit: quote_asset_limits[index],
+                        liquidator: info.sender.to_string(),
+                    },
+                )?);
+                batch.push(*position_id);
+            }
+            Err(err) => attribute_msgs.push(Attribute::new(
+                format!("position_{}", position_id),
+                skipped_outcome(err),
+            )),
+        }
+    }
+
+    store_tmp_batch(deps.storage, &batch)?;
+
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_attributes(attribute_msgs))
+}
+
+// Liquidates a single position of `liquidate_many` on behalf of the liquidator
+pub fn liquidate_batch_position(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    quote_asset_limit: Uint128,
+    liquidator: String,
+) -> StdResult<Response> {
+    if info.sender != env.contract.address {
+        return Err(StdError::generic_err("unauthorized"));
     }
 
-    let config = read_config(deps.storage)?;
-    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
-    require_insufficient_margin(margin_ratio, config.maintenance_margin_ratio)?;
-
-    // read the position for the trader from vamm
-    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
-    let position = read_position(deps.storage, &vamm_key, position_id)?;
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let liquidator = deps.api.addr_validate(&liquidator)?;
 
-    // check the position isn't zero
-    require_position_not_zero(position.size.value)?;
+    let config = read_config(deps.storage)?;
 
-    // first see if this is a partial liquidation, else get rekt
-    let msg = if margin_ratio.value > config.liquidation_fee
-        && !config.partial_liquidation_ratio.is_zero()
-    {
-        partial_liquidation(deps, env, vamm.clone(), position_id, quote_asset_limit)?
-    } else {
-        internal_close_position(deps, &position, quote_asset_limit, LIQUIDATION_REPLY_ID)?
-    };
+    let (msg, position, action) = internal_liquidate(
+        deps,
+        env,
+        &config,
+        &liquidator,
+        &vamm,
+        position_id,
+        quote_asset_limit,
+    )?;
 
     Ok(Response::new().add_submessage(msg).add_attributes(vec![
-        ("action", "liquidate"),
+        ("action", action),
         ("vamm", vamm.as_ref()),
         ("pair", &position.pair),
         ("position_id", &position_id.to_string()),
-        ("trader", &position.trader.as_ref()),
+        ("trader", position.trader.as_ref()),
     ]))
 }
 
@@ -656,6 +1444,66 @@ pub fn pay_funding(
         .add_attribute("action", "pay_funding"))
 }
 
+/// Repegs the vamm to the target price, the cost for the open interest is settled with the
+/// insurance fund in the reply
+pub fn repeg_vamm(
+    deps: DepsMut,
+    info: MessageInfo,
+    vamm: String,
+    target_price: Uint128,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    // check its a valid vamm
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+
+    let repeg_msg = SubMsg::reply_always(
+        wasm_execute(vamm, &ExecuteMsg::Repeg { target_price }, vec![])?,
+        ADJUST_VAMM_REPLY_ID,
+    );
+
+    Ok(Response::new()
+        .add_submessage(repeg_msg)
+        .add_attribute("action", "repeg_vamm"))
+}
+
+/// Scales the reserves of the vamm, the cost for the open interest is settled with the
+/// insurance fund in the reply
+pub fn adjust_vamm_k(
+    deps: DepsMut,
+    info: MessageInfo,
+    vamm: String,
+    scale: Uint128,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    // check its a valid vamm
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+
+    let adjust_msg = SubMsg::reply_always(
+        wasm_execute(vamm, &ExecuteMsg::AdjustK { scale }, vec![])?,
+        ADJUST_VAMM_REPLY_ID,
+    );
+
+    Ok(Response::new()
+        .add_submessage(adjust_msg)
+        .add_attribute("action", "adjust_vamm_k"))
+}
+
 /// Enables a user to directly deposit margin into their position
 pub fn deposit_margin(
     deps: DepsMut,
@@ -714,7 +1562,7 @@ pub fn deposit_margin(
 
 /// Enables a user to directly withdraw excess margin from their position
 pub fn withdraw_margin(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     vamm: String,
@@ -748,9 +1596,15 @@ pub fn withdraw_margin(
 
     position.margin = remain_margin.margin;
     position.last_updated_premium_fraction = remain_margin.latest_premium_fraction;
+    position.last_updated_funding_id = remain_margin.latest_funding_id;
 
-    // check if margin is sufficient
-    let free_collateral = query_free_collateral(deps.as_ref(), vamm.to_string(), position_id)?;
+    // check if margin is sufficient, cross margin positions can use the whole account
+    let free_collateral = match position.margin_mode {
+        MarginMode::Isolated => {
+            query_free_collateral(deps.as_ref(), vamm.to_string(), position_id)?
+        }
+        MarginMode::Cross => query_account_free_collateral(deps.as_ref(), trader.to_string())?,
+    };
     if free_collateral
         .checked_sub(Integer::new_positive(amount))?
         .is_negative()
@@ -760,9 +1614,10 @@ pub fn withdraw_margin(
 
     // withdraw margin
     let msgs = withdraw(
-        deps.as_ref(),
+        deps.branch(),
         env,
         &mut state,
+        &vamm,
         &trader,
         config.eligible_collateral,
         amount,
@@ -780,6 +1635,142 @@ pub fn withdraw_margin(
     ]))
 }
 
+/// Enables a user to back their position with an eligible non quote asset collateral
+pub fn deposit_collateral(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    asset: String,
+    amount: Uint128,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let trader = info.sender.clone();
+
+    let state = read_state(deps.storage)?;
+    require_not_paused(state.pause)?;
+    require_non_zero_input(amount)?;
+
+    let asset = validate_eligible_collateral(deps.as_ref(), asset)?;
+    read_collateral(deps.storage, &asset)?;
+
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let mut position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    if position.trader != trader {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    // a position is only backed by a single collateral type
+    let collateral_amount = match &position.collateral {
+        Some(collateral) if !collateral.info.equal(&asset) => {
+            return Err(StdError::generic_err(
+                "Position is backed by a different collateral",
+            ))
+        }
+        Some(collateral) => collateral.amount.checked_add(amount)?,
+        None => amount,
+    };
+
+    let deposit = Asset {
+        info: asset.clone(),
+        amount,
+    };
+
+    let mut response = Response::new();
+
+    match asset {
+        AssetInfo::NativeToken { .. } => {
+            deposit.assert_sent_native_token_balance(&info)?;
+        }
+        AssetInfo::Token { .. } => {
+            let msg = execute_transfer_collateral(&deposit, &env.contract.address, Some(&trader))?;
+            response = response.add_submessage(msg);
+        }
+    };
+
+    position.collateral = Some(Asset {
+        info: asset,
+        amount: collateral_amount,
+    });
+
+    store_position(deps.storage, &vamm_key, &position, false)?;
+
+    Ok(response.add_attributes(vec![
+        ("action", "deposit_collateral"),
+        ("position_id", &position_id.to_string()),
+        ("trader", trader.as_ref()),
+        ("deposit", &deposit.to_string()),
+    ]))
+}
+
+/// Enables a user to withdraw non quote asset collateral from their position
+pub fn withdraw_collateral(
+    deps: DepsMut,
+    info: MessageInfo,
+    vamm: String,
+    position_id: u64,
+    amount: Uint128,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let trader = info.sender;
+
+    let config = read_config(deps.storage)?;
+    require_vamm(deps.as_ref(), &config.insurance_fund, &vamm)?;
+    let state = read_state(deps.storage)?;
+    require_not_paused(state.pause)?;
+    require_non_zero_input(amount)?;
+
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let mut position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    if position.trader != trader {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let mut collateral = match position.collateral.clone() {
+        Some(collateral) => collateral,
+        None => return Err(StdError::generic_err("Position has no collateral")),
+    };
+
+    collateral.amount = collateral
+        .amount
+        .checked_sub(amount)
+        .map_err(|_| StdError::generic_err("Insufficient collateral"))?;
+
+    position.collateral = if collateral.amount.is_zero() {
+        None
+    } else {
+        Some(collateral.clone())
+    };
+
+    store_position(deps.storage, &vamm_key, &position, false)?;
+
+    // the position must remain above the initial margin ratio once withdrawn
+    let margin_ratio = match position.margin_mode {
+        MarginMode::Isolated => query_margin_ratio(deps.as_ref(), vamm.to_string(), position_id)?,
+        MarginMode::Cross => query_account_margin_ratio(deps.as_ref(), trader.to_string())?,
+    };
+    require_additional_margin(margin_ratio, config.initial_margin_ratio)?;
+
+    let withdrawal = Asset {
+        info: collateral.info,
+        amount,
+    };
+
+    let msg = execute_transfer_collateral(&withdrawal, &trader, None)?;
+
+    Ok(Response::new().add_submessage(msg).add_attributes(vec![
+        ("action", "withdraw_collateral"),
+        ("position_id", &position_id.to_string()),
+        ("trader", trader.as_ref()),
+        ("withdrawal", &withdrawal.to_string()),
+    ]))
+}
+
 // Increase the position through a swap
 pub fn internal_increase_position(
     vamm: Addr,
@@ -795,6 +1786,7 @@ pub fn internal_increase_position(
         open_notional,
         base_asset_limit,
         false,
+        false,
         INCREASE_POSITION_REPLY_ID,
     )
 }
@@ -806,6 +1798,22 @@ pub fn internal_close_position(
     id: u64,
 ) -> StdResult<SubMsg> {
     let side = direction_to_side(&position.direction);
+
+    // liquidations are not charged fees, closing trades against the direction of the position
+    let (spread_fee, toll_fee) = if id == LIQUIDATION_REPLY_ID {
+        (Uint128::zero(), Uint128::zero())
+    } else {
+        let CalcFeeResponse {
+            spread_fee,
+            toll_fee,
+        } = VammController(position.vamm.clone()).calc_fee(
+            &deps.querier,
+            position.notional,
+            side_to_direction(&position_to_side(position.size)),
+        )?;
+        (spread_fee, toll_fee)
+    };
+
     store_tmp_swap(
         deps.storage,
         &TmpSwapInfo {
@@ -823,6 +1831,10 @@ pub fn internal_close_position(
             fees_paid: false,
             take_profit: position.take_profit,
             stop_loss: position.stop_loss,
+            margin_mode: position.margin_mode,
+            escrowed: false,
+            spread_fee,
+            toll_fee,
         },
     )?;
 
@@ -836,6 +1848,168 @@ pub fn internal_close_position(
     )
 }
 
+// Closes the position if its take profit or stop loss has been reached, the keeper
+// is paid the execution fee once the position is closed
+fn internal_trigger_tp_sl(
+    mut deps: DepsMut,
+    config: &Config,
+    keeper: &Addr,
+    vamm: &Addr,
+    position_id: u64,
+    quote_asset_limit: Uint128,
+) -> StdResult<(SubMsg, Position, &'static str)> {
+    let (position, action) = check_tp_sl_reached(deps.as_ref(), config, vamm, position_id)?;
+
+    let msg = internal_close_position(
+        deps.branch(),
+        &position,
+        quote_asset_limit,
+        CLOSE_POSITION_REPLY_ID,
+    )?;
+
+    // only stored once the close can be submitted, so a skipped position leaves no keeper behind
+    store_tmp_keeper(deps.storage, position_id, keeper)?;
+
+    Ok((msg, position, action))
+}
+
+// Returns the position if its take profit or stop loss has been reached at the spot price
+fn check_tp_sl_reached(
+    deps: Deps,
+    config: &Config,
+    vamm: &Addr,
+    position_id: u64,
+) -> StdResult<(Position, &'static str)> {
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    // check the position isn't zero
+    require_position_not_zero(position.size.value)?;
+
+    // if spot_price is ~ take_profit or stop_loss, close position
+    let spot_price = get_spot_price(&deps.querier, vamm)?;
+    let action = if take_profit_reached(&position, spot_price, config)? {
+        "trigger_take_profit"
+    } else if stop_loss_reached(&position, spot_price, config)? {
+        "trigger_stop_loss"
+    } else {
+        return Err(StdError::generic_err("TP/SL price has not been reached"));
+    };
+
+    Ok((position, action))
+}
+
+// Liquidates the position if it is undercollateralized, partially if the margin
+// ratio allows it
+fn internal_liquidate(
+    mut deps: DepsMut,
+    env: Env,
+    config: &Config,
+    liquidator: &Addr,
+    vamm: &Addr,
+    position_id: u64,
+    quote_asset_limit: Uint128,
+) -> StdResult<(SubMsg, Position, &'static str)> {
+    let (position, action) = check_liquidatable(deps.as_ref(), config, vamm, position_id)?;
+
+    // first see if this is a partial liquidation, else get rekt
+    let msg = if action == PARTIAL_LIQUIDATION {
+        partial_liquidation(
+            deps.branch(),
+            env,
+            vamm.clone(),
+            position_id,
+            quote_asset_limit,
+        )?
+    } else {
+        internal_close_position(
+            deps.branch(),
+            &position,
+            quote_asset_limit,
+            LIQUIDATION_REPLY_ID,
+        )?
+    };
+
+    // store the liquidator
+    store_tmp_liquidator(deps.storage, position_id, liquidator)?;
+
+    Ok((msg, position, action))
+}
+
+// Returns the position if it is undercollateralized and whether it is liquidated in full
+// or partially
+fn check_liquidatable(
+    deps: Deps,
+    config: &Config,
+    vamm: &Addr,
+    position_id: u64,
+) -> StdResult<(Position, &'static str)> {
+    // read the position for the trader from vamm
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    // retrieve the existing margin ratio of the position, falling back to the oracle
+    // if the vamm is over the spread limit
+    let over_spread_limit = VammController(vamm.clone()).is_over_spread_limit(&deps.querier)?;
+    let margin_ratio =
+        calc_liquidation_margin_ratio(deps, vamm, &position, None, over_spread_limit)?;
+
+    require_insufficient_margin(margin_ratio, config.maintenance_margin_ratio)?;
+
+    // check the position isn't zero
+    require_position_not_zero(position.size.value)?;
+
+    let action = if margin_ratio.value > config.liquidation_fee
+        && !config.partial_liquidation_ratio.is_zero()
+    {
+        PARTIAL_LIQUIDATION
+    } else {
+        "liquidation"
+    };
+
+    Ok((position, action))
+}
+
+// Returns the message processing a single position of a batch in its own call, so it is checked
+// against the price left by the positions before it and a failure only reverts that position
+fn batch_position_msg(env: &Env, msg: &EngineExecuteMessage) -> StdResult<SubMsg> {
+    Ok(SubMsg::reply_always(
+        wasm_execute(env.contract.address.to_string(), msg, vec![])?,
+        BATCH_POSITION_REPLY_ID,
+    ))
+}
+
+// Returns the quote asset limit of each position in a batch, defaulting to zero
+fn batch_quote_asset_limits(
+    position_ids: &[u64],
+    quote_asset_limits: Option<Vec<Uint128>>,
+) -> StdResult<Vec<Uint128>> {
+    if position_ids.is_empty() {
+        return Err(StdError::generic_err("No positions provided"));
+    }
+
+    if position_ids.len() > MAX_LIMIT as usize {
+        return Err(StdError::generic_err("Too many positions provided"));
+    }
+
+    match quote_asset_limits {
+        Some(limits) if limits.len() != position_ids.len() => Err(StdError::generic_err(
+            "Quote asset limits do not match the positions",
+        )),
+        Some(limits) => Ok(limits),
+        None => Ok(vec![Uint128::zero(); position_ids.len()]),
+    }
+}
+
+// Describes why a position in a batch was skipped
+fn skipped_outcome(err: StdError) -> String {
+    match err {
+        StdError::GenericErr { msg, .. } => format!("skipped: {}", msg),
+        err => format!("skipped: {}", err),
+    }
+}
+
 fn partial_liquidation(
     deps: DepsMut,
     _env: Env,
@@ -888,6 +2062,10 @@ fn partial_liquidation(
             fees_paid: false,
             take_profit: position.take_profit,
             stop_loss: position.stop_loss,
+            margin_mode: position.margin_mode,
+            escrowed: false,
+            spread_fee: Uint128::zero(),
+            toll_fee: Uint128::zero(),
         },
     )?;
 
@@ -899,6 +2077,7 @@ fn partial_liquidation(
             position.notional,
             Uint128::zero(),
             true,
+            true,
             PARTIAL_LIQUIDATION_REPLY_ID,
         )?
     } else {
@@ -922,6 +2101,7 @@ fn swap_input(
     open_notional: Uint128,
     base_asset_limit: Uint128,
     can_go_over_fluctuation: bool,
+    reduces_position: bool,
     id: u64,
 ) -> StdResult<SubMsg> {
     let msg = wasm_execute(
@@ -932,6 +2112,7 @@ fn swap_input(
             quote_asset_amount: open_notional,
             base_asset_limit,
             can_go_over_fluctuation,
+            reduces_position,
         },
         vec![],
     )?;
@@ -1,16 +1,20 @@
-use cosmwasm_std::{Addr, Deps, Env, StdError, StdResult, Storage, SubMsg, Uint128};
-
-use margined_utils::contracts::helpers::VammController;
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, StdError, StdResult, Storage, SubMsg, Uint128};
 
 use crate::{
-    contract::TRANSFER_FAILURE_REPLY_ID,
-    state::{read_config, State},
+    contract::{
+        FEE_ROUTING_TOTAL_WEIGHT, INSURANCE_PAYOUT_REPLY_ID, INSURANCE_WITHDRAWAL_REPLY_ID,
+        TRANSFER_FAILURE_REPLY_ID,
+    },
+    state::{push_tmp_payouts, read_config, State, TmpPayouts},
+    utils::{apply_fee_discount, get_fee_routing},
 };
 
-use margined_common::{asset::AssetInfo, messages::wasm_execute};
-use margined_perp::margined_engine::TransferResponse;
+use margined_common::{
+    asset::{Asset, AssetInfo},
+    messages::wasm_execute,
+};
+use margined_perp::margined_engine::{FeeRecipient, TransferResponse};
 use margined_perp::margined_insurance_fund::ExecuteMsg as InsuranceFundExecuteMessage;
-use margined_perp::margined_vamm::CalcFeeResponse;
 
 pub fn execute_transfer_from(
     storage: &dyn Storage,
@@ -43,11 +47,27 @@ pub fn execute_transfer(
     Ok(SubMsg::reply_on_error(msg, TRANSFER_FAILURE_REPLY_ID))
 }
 
+// Transfers non quote asset collateral, the owner is set when pulling it from the trader
+pub fn execute_transfer_collateral(
+    asset: &Asset,
+    receiver: &Addr,
+    owner: Option<&Addr>,
+) -> StdResult<SubMsg> {
+    let msg = asset.info.into_msg(
+        receiver.to_string(),
+        asset.amount,
+        owner.map(|owner| owner.to_string()),
+    )?;
+
+    Ok(SubMsg::reply_on_error(msg, TRANSFER_FAILURE_REPLY_ID))
+}
+
 pub fn execute_transfer_to_insurance_fund(
     deps: Deps,
     env: Env,
+    vamm: &Addr,
     amount: Uint128,
-) -> StdResult<SubMsg> {
+) -> StdResult<Vec<SubMsg>> {
     let config = read_config(deps.storage)?;
 
     let token_balance = config
@@ -59,13 +79,38 @@ pub fn execute_transfer_to_insurance_fund(
         amount,
     );
 
-    match config.insurance_fund {
-        Some(insurance_fund) => execute_transfer(deps.storage, &insurance_fund, amount_to_send),
+    let insurance_fund = match config.insurance_fund {
+        Some(insurance_fund) => insurance_fund,
         None => return Err(StdError::generic_err("insurance fund is not registered")),
+    };
+
+    let msg = execute_transfer(deps.storage, &insurance_fund, amount_to_send)?;
+
+    let mut messages = vec![msg];
+    if !amount_to_send.is_zero() {
+        let msg = execute_insurance_fund_credit(deps.storage, vamm, amount_to_send)?;
+        messages.push(msg);
     }
+
+    Ok(messages)
+}
+
+// Withdraws from the insurance fund, the sub-fund of the vamm is debited first and any shortfall
+// the insurance fund can't cover is recorded in the reply
+pub fn execute_insurance_fund_withdrawal(
+    deps: Deps,
+    vamm: &Addr,
+    amount: Uint128,
+) -> StdResult<SubMsg> {
+    insurance_fund_withdrawal(deps, vamm, amount, INSURANCE_WITHDRAWAL_REPLY_ID)
 }
 
-pub fn execute_insurance_fund_withdrawal(deps: Deps, amount: Uint128) -> StdResult<SubMsg> {
+fn insurance_fund_withdrawal(
+    deps: Deps,
+    vamm: &Addr,
+    amount: Uint128,
+    reply_id: u64,
+) -> StdResult<SubMsg> {
     let config = read_config(deps.storage)?;
 
     let insurance_fund = match config.insurance_fund {
@@ -78,76 +123,213 @@ pub fn execute_insurance_fund_withdrawal(deps: Deps, amount: Uint128) -> StdResu
         &InsuranceFundExecuteMessage::Withdraw {
             token: config.eligible_collateral,
             amount,
+            vamm: Some(vamm.to_string()),
         },
         vec![],
     )?;
 
-    Ok(SubMsg::reply_on_error(msg, TRANSFER_FAILURE_REPLY_ID))
+    Ok(SubMsg::reply_always(msg, reply_id))
+}
+
+// Credits funds already sent to the insurance fund to the sub-fund of the vamm
+pub fn execute_insurance_fund_credit(
+    storage: &dyn Storage,
+    vamm: &Addr,
+    amount: Uint128,
+) -> StdResult<SubMsg> {
+    let config = read_config(storage)?;
+
+    let insurance_fund = match config.insurance_fund {
+        Some(insurance_fund) => insurance_fund,
+        None => return Err(StdError::generic_err("insurance fund is not registered")),
+    };
+
+    let msg = wasm_execute(
+        insurance_fund,
+        &InsuranceFundExecuteMessage::FundVamm {
+            vamm: vamm.to_string(),
+            amount,
+        },
+        vec![],
+    )?;
+
+    Ok(SubMsg::new(msg))
 }
 
-// Transfers the toll and spread fees to the the insurance fund and fee pool
+// Transfers the toll and spread fees, quoted before the swap and discounted by the fee tier of the
+// trader, to their recipients in the fee routing
 pub fn transfer_fees(
     deps: Deps,
+    env: &Env,
     from: Addr,
-    vamm: Addr,
-    notional: Uint128,
+    vamm: &Addr,
+    spread_fee: Uint128,
+    toll_fee: Uint128,
 ) -> StdResult<TransferResponse> {
-    let vamm_controller = VammController(vamm);
+    let (spread_fee, toll_fee) = apply_fee_discount(
+        deps.storage,
+        &from,
+        env.block.time.seconds(),
+        spread_fee,
+        toll_fee,
+    )?;
+
+    let fee_routing = get_fee_routing(deps.storage)?;
+
+    let mut messages: Vec<SubMsg> = vec![];
+    messages.append(&mut route_fee(
+        deps.storage,
+        &fee_routing.spread_fee,
+        vamm,
+        Some(&from),
+        spread_fee,
+    )?);
+    messages.append(&mut route_fee(
+        deps.storage,
+        &fee_routing.toll_fee,
+        vamm,
+        Some(&from),
+        toll_fee,
+    )?);
 
-    let CalcFeeResponse {
+    Ok(TransferResponse {
+        messages,
         spread_fee,
         toll_fee,
-    } = vamm_controller.calc_fee(&deps.querier, notional)?;
+    })
+}
 
+// Splits a fee between its recipients by weight, the last recipient receives the rounding
+// remainder. The fee is pulled from the trader if given, otherwise it is sent by the engine.
+pub fn route_fee(
+    storage: &dyn Storage,
+    recipients: &[FeeRecipient],
+    vamm: &Addr,
+    from: Option<&Addr>,
+    amount: Uint128,
+) -> StdResult<Vec<SubMsg>> {
     let mut messages: Vec<SubMsg> = vec![];
 
-    let config = read_config(deps.storage)?;
-    if !spread_fee.is_zero() {
-        let msg = match config.insurance_fund {
-            Some(insurance_fund) => {
-                execute_transfer_from(deps.storage, &from, &insurance_fund, spread_fee)?
-            }
-            None => return Err(StdError::generic_err("insurance fund is not registered")),
+    if amount.is_zero() {
+        return Ok(messages);
+    }
+
+    // only the default routing of a fee to the insurance fund can be empty
+    if recipients.is_empty() {
+        return Err(StdError::generic_err("insurance fund is not registered"));
+    }
+
+    let config = read_config(storage)?;
+
+    let mut remaining = amount;
+    for (index, fee_recipient) in recipients.iter().enumerate() {
+        let share = if index == recipients.len() - 1 {
+            remaining
+        } else {
+            amount.multiply_ratio(fee_recipient.weight, FEE_ROUTING_TOTAL_WEIGHT)
         };
+        remaining = remaining.checked_sub(share)?;
 
-        messages.push(msg);
-    };
+        if share.is_zero() {
+            continue;
+        }
 
-    if !toll_fee.is_zero() {
-        let msg = execute_transfer_from(deps.storage, &from, &config.fee_pool, toll_fee)?;
+        // recipients are validated when the fee routing is updated
+        let recipient = Addr::unchecked(&fee_recipient.recipient);
+
+        let msg = match from {
+            Some(from) => execute_transfer_from(storage, from, &recipient, share)?,
+            None => execute_transfer(storage, &recipient, share)?,
+        };
         messages.push(msg);
-    };
 
-    Ok(TransferResponse {
-        messages,
-        spread_fee,
-        toll_fee,
-    })
+        // fees sent to the insurance fund are credited to the sub-fund of the vamm
+        if config.insurance_fund.as_ref() == Some(&recipient) {
+            let msg = execute_insurance_fund_credit(storage, vamm, share)?;
+            messages.push(msg);
+        }
+    }
+
+    Ok(messages)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn withdraw(
-    deps: Deps,
+    deps: DepsMut,
     env: Env,
     state: &mut State,
+    vamm: &Addr,
     receiver: &Addr,
     eligible_collateral: AssetInfo,
     amount: Uint128,
     pre_paid_shortfall: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    withdraw_many(
+        deps,
+        env,
+        state,
+        vamm,
+        &[(receiver, amount)],
+        eligible_collateral,
+        pre_paid_shortfall,
+        Uint128::zero(),
+    )
+}
+
+// pays several receivers out of the same balance, so any shortfall is checked against the total.
+// Bad debt realized along with the payouts is withdrawn together with the shortfall, payouts
+// relying on that withdrawal are made in its reply and cut first if the insurance fund comes up
+// short, so the rest of the vault never pays for them.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_many(
+    deps: DepsMut,
+    env: Env,
+    state: &mut State,
+    vamm: &Addr,
+    payouts: &[(&Addr, Uint128)],
+    eligible_collateral: AssetInfo,
+    pre_paid_shortfall: Uint128,
+    bad_debt_withdrawal: Uint128,
 ) -> StdResult<Vec<SubMsg>> {
     let token_balance = eligible_collateral.query_balance(&deps.querier, env.contract.address)?;
 
-    let mut messages: Vec<SubMsg> = vec![];
+    let amount = payouts
+        .iter()
+        .try_fold(Uint128::zero(), |total, (_, amount)| {
+            total.checked_add(*amount)
+        })?;
 
-    if token_balance.checked_add(pre_paid_shortfall)? < amount {
-        let shortfall = amount.checked_sub(token_balance.checked_add(pre_paid_shortfall)?)?;
+    let available = token_balance
+        .checked_add(pre_paid_shortfall)?
+        .checked_add(bad_debt_withdrawal)?;
+    let shortfall = amount.saturating_sub(available);
 
-        // add any shortfall to bad_debt
-        state.prepaid_bad_debt = state.prepaid_bad_debt.checked_add(shortfall)?;
+    // add any shortfall to bad_debt
+    state.prepaid_bad_debt = state.prepaid_bad_debt.checked_add(shortfall)?;
 
-        messages.push(execute_insurance_fund_withdrawal(deps, shortfall)?);
+    let withdrawal = shortfall.checked_add(bad_debt_withdrawal)?;
+    if withdrawal.is_zero() {
+        return payouts
+            .iter()
+            .map(|(receiver, amount)| execute_transfer(deps.storage, receiver, *amount))
+            .collect();
     }
 
-    messages.push(execute_transfer(deps.storage, receiver, amount)?);
+    push_tmp_payouts(
+        deps.storage,
+        TmpPayouts {
+            payouts: payouts
+                .iter()
+                .map(|(receiver, amount)| ((*receiver).clone(), *amount))
+                .collect(),
+            prepaid_bad_debt: shortfall,
+        },
+    )?;
 
-    Ok(messages)
+    Ok(vec![insurance_fund_withdrawal(
+        deps.as_ref(),
+        vamm,
+        withdrawal,
+        INSURANCE_PAYOUT_REPLY_ID,
+    )?])
 }
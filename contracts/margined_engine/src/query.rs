@@ -1,21 +1,28 @@
-use cosmwasm_std::{Deps, Order as OrderBy, StdError, StdResult, Uint128};
-use margined_common::integer::Integer;
+use cosmwasm_std::{Addr, Deps, Env, Order as OrderBy, StdError, StdResult, Uint128};
+use margined_common::{integer::Integer, validate::validate_eligible_collateral};
 use margined_perp::margined_engine::{
-    ConfigResponse, LastPositionIdResponse, PauserResponse, PnlCalcOption, Position,
-    PositionFilter, PositionUnrealizedPnlResponse, Side, StateResponse,
+    Collateral, ConfigResponse, FeeRouting, FeeTier, FundingPayment, LastPositionIdResponse,
+    LimitOrder, LiquidatablePosition, LiquidatablePositionsResponse, MarginMode, PauserResponse,
+    PnlCalcOption, Position, PositionFilter, PositionUnrealizedPnlResponse, Side, StateResponse,
+    TraderFeeTierResponse,
 };
-use margined_utils::contracts::helpers::InsuranceFundController;
+use margined_utils::contracts::helpers::{InsuranceFundController, VammController};
 
 use crate::{
     contract::PAUSER,
     state::{
-        read_config, read_last_position_id, read_position, read_positions,
-        read_positions_with_indexer, read_state, read_vamm_map, PREFIX_POSITION_BY_PRICE,
-        PREFIX_POSITION_BY_SIDE, PREFIX_POSITION_BY_TRADER,
+        read_all_collateral, read_collateral, read_config, read_fee_tiers, read_last_position_id,
+        read_limit_order, read_limit_orders, read_position, read_positions,
+        read_positions_by_tp_sl, read_positions_with_indexer, read_reduce_only, read_state,
+        read_vamm_map, DEFAULT_LIMIT, MAX_LIMIT, PREFIX_POSITION_BY_PRICE, PREFIX_POSITION_BY_SIDE,
+        PREFIX_POSITION_BY_STOP_LOSS, PREFIX_POSITION_BY_TAKE_PROFIT, PREFIX_POSITION_BY_TRADER,
     },
     utils::{
-        calc_funding_payment, calc_remain_margin_with_funding_payment,
-        get_position_notional_unrealized_pnl, keccak_256,
+        calc_account_margin_ratio, calc_collateral_value, calc_funding_payment,
+        calc_remain_margin_with_funding_payment, get_account_margin_ratio_calc_option,
+        get_cross_positions, get_fee_routing, get_margin_ratio_calc_option,
+        get_position_notional_unrealized_pnl, get_trader_fee_tier, keccak_256, stop_loss_reached,
+        take_profit_reached,
     },
 };
 
@@ -24,6 +31,27 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     read_config(deps.storage)
 }
 
+/// Queries the fee routing, the default routing if none has been set
+pub fn query_fee_routing(deps: Deps) -> StdResult<FeeRouting> {
+    get_fee_routing(deps.storage)
+}
+
+/// Queries the fee schedule
+pub fn query_fee_tiers(deps: Deps) -> StdResult<Vec<FeeTier>> {
+    read_fee_tiers(deps.storage)
+}
+
+/// Queries the rolling volume of a trader and its fee tier
+pub fn query_trader_fee_tier(
+    deps: Deps,
+    env: Env,
+    trader: String,
+) -> StdResult<TraderFeeTierResponse> {
+    let trader = deps.api.addr_validate(&trader)?;
+
+    get_trader_fee_tier(deps.storage, &trader, env.block.time.seconds())
+}
+
 /// Queries contract State
 pub fn query_state(deps: Deps) -> StdResult<StateResponse> {
     let state = read_state(deps.storage)?;
@@ -31,6 +59,7 @@ pub fn query_state(deps: Deps) -> StdResult<StateResponse> {
     Ok(StateResponse {
         open_interest_notional: state.open_interest_notional,
         bad_debt: state.prepaid_bad_debt,
+        uncovered_bad_debt: state.uncovered_bad_debt,
     })
 }
 
@@ -43,6 +72,13 @@ pub fn query_pauser(deps: Deps) -> StdResult<PauserResponse> {
     }
 }
 
+/// Queries if a vamm only accepts risk reducing actions
+pub fn query_is_reduce_only(deps: Deps, vamm: String) -> StdResult<bool> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    read_reduce_only(deps.storage, &vamm)
+}
+
 /// Queries user position
 pub fn query_position(deps: Deps, vamm: String, position_id: u64) -> StdResult<Position> {
     // if vamm and trader are not correct, vamm_key will throw not found error
@@ -52,6 +88,38 @@ pub fn query_position(deps: Deps, vamm: String, position_id: u64) -> StdResult<P
     Ok(position)
 }
 
+/// Queries a resting limit order
+pub fn query_limit_order(deps: Deps, vamm: String, order_id: u64) -> StdResult<LimitOrder> {
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+
+    read_limit_order(deps.storage, &vamm_key, order_id)
+}
+
+/// Queries the resting limit orders of a vamm, optionally filtered by trader
+pub fn query_limit_orders(
+    deps: Deps,
+    vamm: String,
+    trader: Option<String>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<i32>,
+) -> StdResult<Vec<LimitOrder>> {
+    let order_by = order_by.map_or(None, |val| OrderBy::try_from(val).ok());
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let trader = trader
+        .map(|trader| deps.api.addr_validate(&trader))
+        .transpose()?;
+
+    read_limit_orders(
+        deps.storage,
+        &vamm_key,
+        trader.as_ref(),
+        start_after,
+        limit,
+        order_by,
+    )
+}
+
 /// Queries and returns users position for all registered vamms
 pub fn query_all_positions(
     deps: Deps,
@@ -151,6 +219,158 @@ pub fn query_positions(
     Ok(positions.unwrap_or_default())
 }
 
+/// Queries a page of positions that can be liquidated together with their margin ratio
+pub fn query_liquidatable_positions(
+    deps: Deps,
+    vamm: String,
+    calc_option: Option<PnlCalcOption>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<LiquidatablePositionsResponse> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let config = read_config(deps.storage)?;
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+
+    let positions = read_positions(
+        deps.storage,
+        &vamm_key,
+        start_after,
+        limit,
+        Some(OrderBy::Ascending),
+    )?;
+    let last_position_id = positions.last().map(|position| position.position_id);
+
+    let over_spread_limit = VammController(vamm.clone()).is_over_spread_limit(&deps.querier)?;
+
+    let mut response: Vec<LiquidatablePosition> = vec![];
+    for position in positions {
+        if position.size.is_zero() {
+            continue;
+        }
+
+        let margin_ratio = calc_liquidation_margin_ratio(
+            deps,
+            &vamm,
+            &position,
+            calc_option.clone(),
+            over_spread_limit,
+        )?;
+
+        if margin_ratio <= Integer::new_positive(config.maintenance_margin_ratio) {
+            response.push(LiquidatablePosition {
+                position,
+                margin_ratio,
+            });
+        }
+    }
+
+    Ok(LiquidatablePositionsResponse {
+        positions: response,
+        last_position_id,
+    })
+}
+
+/// Queries the positions of a side that `trigger_tp_sl` would close at the current spot price
+pub fn query_triggerable_positions(
+    deps: Deps,
+    vamm: String,
+    side: Side,
+    limit: Option<u32>,
+) -> StdResult<Vec<Position>> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+    let config = read_config(deps.storage)?;
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let spot_price = VammController(vamm).spot_price(&deps.querier)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // longs take profit as the price rises and stop out as it falls, shorts the opposite
+    let (tp_order_by, sl_order_by) = match side {
+        Side::Buy => (OrderBy::Ascending, OrderBy::Descending),
+        Side::Sell => (OrderBy::Descending, OrderBy::Ascending),
+    };
+
+    let mut positions = read_positions_by_tp_sl(
+        deps.storage,
+        &vamm_key,
+        PREFIX_POSITION_BY_TAKE_PROFIT,
+        &side,
+        tp_order_by,
+        limit,
+        |position| take_profit_reached(position, spot_price, &config),
+    )?;
+
+    let stop_losses = read_positions_by_tp_sl(
+        deps.storage,
+        &vamm_key,
+        PREFIX_POSITION_BY_STOP_LOSS,
+        &side,
+        sl_order_by,
+        limit,
+        |position| stop_loss_reached(position, spot_price, &config),
+    )?;
+
+    for position in stop_losses {
+        if positions.len() >= limit {
+            break;
+        }
+
+        if !positions
+            .iter()
+            .any(|triggered| triggered.position_id == position.position_id)
+        {
+            positions.push(position);
+        }
+    }
+
+    Ok(positions)
+}
+
+/// Returns the margin ratio a position is liquidated at, if the vamm is over the spread
+/// limit the oracle margin ratio is used when it is more beneficial to the trader
+pub fn calc_liquidation_margin_ratio(
+    deps: Deps,
+    vamm: &Addr,
+    position: &Position,
+    calc_option: Option<PnlCalcOption>,
+    over_spread_limit: bool,
+) -> StdResult<Integer> {
+    // cross margin positions are measured against the whole account of the trader
+    let mut margin_ratio = match (&position.margin_mode, calc_option) {
+        (MarginMode::Isolated, None) => {
+            query_margin_ratio(deps, vamm.to_string(), position.position_id)?
+        }
+        (MarginMode::Isolated, Some(calc_option)) => {
+            get_margin_ratio_calc_option(deps, vamm.to_string(), position.position_id, calc_option)?
+        }
+        (MarginMode::Cross, None) => query_account_margin_ratio(deps, position.trader.to_string())?,
+        (MarginMode::Cross, Some(calc_option)) => {
+            get_account_margin_ratio_calc_option(deps, position.trader.to_string(), calc_option)?
+        }
+    };
+
+    if over_spread_limit {
+        let oracle_margin_ratio = match position.margin_mode {
+            MarginMode::Isolated => get_margin_ratio_calc_option(
+                deps,
+                vamm.to_string(),
+                position.position_id,
+                PnlCalcOption::Oracle,
+            )?,
+            MarginMode::Cross => get_account_margin_ratio_calc_option(
+                deps,
+                position.trader.to_string(),
+                PnlCalcOption::Oracle,
+            )?,
+        };
+
+        if oracle_margin_ratio.checked_sub(margin_ratio)? > Integer::zero() {
+            margin_ratio = oracle_margin_ratio
+        }
+    }
+
+    Ok(margin_ratio)
+}
+
 /// Queries user position
 pub fn query_position_notional_unrealized_pnl(
     deps: Deps,
@@ -167,17 +387,22 @@ pub fn query_position_notional_unrealized_pnl(
     Ok(result)
 }
 
-/// Queries cumulative premium fractions
+/// Queries cumulative premium fractions, the discrete settlements plus the continuous funding
+/// accrued by the vamm up to the current block
 pub fn query_cumulative_premium_fraction(deps: Deps, vamm: String) -> StdResult<Integer> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+
     // retrieve vamm data
-    let vamm_map = read_vamm_map(deps.storage, &deps.api.addr_validate(&vamm)?)?;
+    let vamm_map = read_vamm_map(deps.storage, &vamm)?;
 
     let result = match vamm_map.cumulative_premium_fractions.len() {
         0 => Integer::zero(),
         n => vamm_map.cumulative_premium_fractions[n - 1],
     };
 
-    Ok(result)
+    let funding_index = VammController(vamm).funding_index(&deps.querier)?;
+
+    Ok(result.checked_add(funding_index.cumulative_premium_fraction)?)
 }
 
 /// Queries traders balance across all vamms with funding payment
@@ -241,6 +466,41 @@ pub fn query_trader_position_with_funding_payment(
     Ok(position)
 }
 
+/// Queries the funding payments of a position for each settlement since it was last updated,
+/// rebuilt from the funding records of the vamm
+pub fn query_position_funding_payments(
+    deps: Deps,
+    vamm: String,
+    position_id: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<FundingPayment>> {
+    let config = read_config(deps.storage)?;
+
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    let start_after = start_after
+        .unwrap_or_default()
+        .max(position.last_updated_funding_id);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    let vamm_controller = VammController(position.vamm.clone());
+    let funding_history =
+        vamm_controller.funding_history(&deps.querier, Some(start_after), Some(limit))?;
+
+    Ok(funding_history
+        .into_iter()
+        .map(|record| FundingPayment {
+            funding_id: record.funding_id,
+            timestamp: record.timestamp,
+            premium_fraction: record.premium_fraction,
+            funding_payment: record.premium_fraction * position.size
+                / Integer::new_positive(config.decimals),
+        })
+        .collect())
+}
+
 /// Queries the margin ratio of a trader
 pub fn query_margin_ratio(deps: Deps, vamm: String, position_id: u64) -> StdResult<Integer> {
     let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
@@ -276,11 +536,13 @@ pub fn query_margin_ratio(deps: Deps, vamm: String, position_id: u64) -> StdResu
         }
     };
 
+    let collateral_value = calc_collateral_value(deps, &position)?;
     let remain_margin = calc_remain_margin_with_funding_payment(deps, position, unrealized_pnl)?;
 
     let config = read_config(deps.storage)?;
     let margin_ratio = ((Integer::new_positive(remain_margin.margin)
-        - Integer::new_positive(remain_margin.bad_debt))
+        - Integer::new_positive(remain_margin.bad_debt)
+        + Integer::new_positive(collateral_value))
         * Integer::new_positive(config.decimals))
         / Integer::new_positive(position_notional);
 
@@ -329,6 +591,11 @@ pub fn query_free_collateral(deps: Deps, vamm: String, position_id: u64) -> StdR
         account_value
     };
 
+    // haircut value of any non quote asset collateral also backs the position
+    let collateral_value = calc_collateral_value(deps, &position)?;
+    let minimum_collateral =
+        minimum_collateral.checked_add(Integer::new_positive(collateral_value))?;
+
     let config = read_config(deps.storage)?;
 
     let margin_requirement = if position.size.is_positive() {
@@ -345,11 +612,67 @@ pub fn query_free_collateral(deps: Deps, vamm: String, position_id: u64) -> StdR
     Ok(minimum_collateral.checked_sub(Integer::new_positive(margin_requirement))?)
 }
 
+/// Queries the margin ratio of a trader's cross margin account
+pub fn query_account_margin_ratio(deps: Deps, trader: String) -> StdResult<Integer> {
+    let trader = deps.api.addr_validate(&trader)?;
+
+    let mut account: Vec<(Position, PositionUnrealizedPnlResponse)> = vec![];
+    for position in get_cross_positions(deps, &trader)? {
+        let spot = get_position_notional_unrealized_pnl(deps, &position, PnlCalcOption::SpotPrice)?;
+        let twap = get_position_notional_unrealized_pnl(deps, &position, PnlCalcOption::Twap)?;
+
+        // as for isolated positions take the least beneficial pnl for the trader
+        let pnl = if spot.unrealized_pnl.abs() > twap.unrealized_pnl.abs() {
+            twap
+        } else {
+            spot
+        };
+
+        account.push((position, pnl));
+    }
+
+    calc_account_margin_ratio(deps, account)
+}
+
+/// Queries the withdrawable collateral of a trader's cross margin account
+pub fn query_account_free_collateral(deps: Deps, trader: String) -> StdResult<Integer> {
+    let trader = deps.api.addr_validate(&trader)?;
+
+    let mut free_collateral = Integer::zero();
+    for position in get_cross_positions(deps, &trader)? {
+        free_collateral = free_collateral.checked_add(query_free_collateral(
+            deps,
+            position.vamm.to_string(),
+            position.position_id,
+        )?)?;
+    }
+
+    Ok(free_collateral)
+}
+
 pub fn query_last_position_id(deps: Deps) -> StdResult<LastPositionIdResponse> {
     let last_position_id = read_last_position_id(deps.storage)?;
-    let resp = LastPositionIdResponse {
-        last_position_id,
-    };
+    let resp = LastPositionIdResponse { last_position_id };
 
     Ok(resp)
 }
+
+/// Queries an eligible collateral
+pub fn query_collateral(deps: Deps, asset: String) -> StdResult<Collateral> {
+    let asset = validate_eligible_collateral(deps, asset)?;
+
+    read_collateral(deps.storage, &asset)
+}
+
+/// Queries all eligible collateral
+pub fn query_all_collateral(deps: Deps) -> StdResult<Vec<Collateral>> {
+    read_all_collateral(deps.storage)
+}
+
+/// Queries the haircut value of a position's collateral in the quote asset
+pub fn query_collateral_value(deps: Deps, vamm: String, position_id: u64) -> StdResult<Uint128> {
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+    let position = read_position(deps.storage, &vamm_key, position_id)?;
+
+    calc_collateral_value(deps, &position)
+}
@@ -1,38 +1,43 @@
-use cosmwasm_std::{DepsMut, Env, Response, StdError, StdResult, SubMsg, Uint128};
+use cosmwasm_std::{Addr, Deps, DepsMut, Env, Response, StdError, StdResult, SubMsg, Uint128};
 use margined_utils::contracts::helpers::VammController;
 
 use crate::{
     messages::{
-        execute_insurance_fund_withdrawal, execute_transfer, execute_transfer_from,
-        execute_transfer_to_insurance_fund, transfer_fees, withdraw,
+        execute_insurance_fund_credit, execute_insurance_fund_withdrawal, execute_transfer,
+        execute_transfer_collateral, execute_transfer_from, execute_transfer_to_insurance_fund,
+        route_fee, transfer_fees, withdraw, withdraw_many,
     },
     state::{
-        append_cumulative_premium_fraction, enter_restriction_mode, read_config, read_position,
-        read_sent_funds, read_state, read_tmp_liquidator, read_tmp_swap, remove_position,
-        remove_sent_funds, remove_tmp_liquidator, remove_tmp_swap, store_position, store_state,
-        State,
+        append_cumulative_premium_fraction, enter_restriction_mode, increase_trader_volume,
+        pop_tmp_batch, pop_tmp_payouts, read_config, read_first_tmp_limit_order, read_position,
+        read_sent_funds, read_state, read_tmp_keeper, read_tmp_limit_order, read_tmp_liquidator,
+        read_tmp_swap, read_vamm_map, remove_position, remove_sent_funds, remove_tmp_keeper,
+        remove_tmp_limit_order, remove_tmp_liquidator, remove_tmp_swap, store_limit_order,
+        store_position, store_state, store_vamm_map, State, TmpPayouts,
     },
     utils::{
-        calc_remain_margin_with_funding_payment, check_base_asset_holding_cap, keccak_256,
-        realize_bad_debt, side_to_direction, update_open_interest_notional,
+        calc_remain_margin_with_funding_payment, check_base_asset_holding_cap, convert_collateral,
+        cover_cross_margin_loss, get_fee_routing, keccak_256, prepay_bad_debt, side_to_direction,
+        update_open_interest_notional,
     },
 };
 
 use margined_common::{asset::AssetInfo, integer::Integer};
 use margined_perp::{
-    margined_engine::{Position, RemainMarginResponse, Side},
+    margined_engine::{MarginMode, Position, RemainMarginResponse, Side},
     margined_vamm::Direction,
 };
 
 // Updates position after successful execution of the swap
 pub fn update_position_reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     input: Uint128,
     output: Uint128,
     position_id: u64,
 ) -> StdResult<Response> {
     let mut swap = read_tmp_swap(deps.storage, &position_id.to_be_bytes())?;
+    let funding_msgs = settle_continuous_funding(deps.branch(), env.clone(), &swap.vamm)?;
 
     let mut position: Position = Position {
         position_id: swap.position_id,
@@ -48,7 +53,10 @@ pub fn update_position_reply(
         take_profit: swap.take_profit,
         stop_loss: swap.stop_loss,
         last_updated_premium_fraction: Integer::zero(),
+        last_updated_funding_id: 0u64,
         block_time: env.block.time.seconds(),
+        margin_mode: swap.margin_mode,
+        collateral: None,
     };
 
     // depending on the direction the output is positive or negative
@@ -95,6 +103,7 @@ pub fn update_position_reply(
         margin,
         bad_debt: _,
         latest_premium_fraction,
+        latest_funding_id,
     } = calc_remain_margin_with_funding_payment(deps.as_ref(), position.clone(), margin_delta)?;
 
     // set the new position
@@ -103,6 +112,7 @@ pub fn update_position_reply(
     position.size += signed_output;
     position.margin = margin;
     position.last_updated_premium_fraction = latest_premium_fraction;
+    position.last_updated_funding_id = latest_funding_id;
     position.entry_price = position
         .notional
         .checked_mul(config.decimals)?
@@ -121,72 +131,163 @@ pub fn update_position_reply(
     )?;
 
     let mut msgs: Vec<SubMsg> = vec![];
-    let mut funds = read_sent_funds(deps.storage)?;
-
-    // create transfer messages depending on PnL
-    if swap.margin_to_vault > Integer::zero() {
-        match config.eligible_collateral {
-            AssetInfo::NativeToken { .. } => {
-                funds.required = funds.required.checked_add(swap_margin)?;
-            }
-            AssetInfo::Token { .. } => {
-                msgs.push(execute_transfer_from(
-                    deps.storage,
-                    &swap.trader,
-                    &env.contract.address,
-                    swap.margin_to_vault.value,
-                )?);
-            }
-        }
-    };
 
     // create array for fee amounts
     let mut fees_amount: [Uint128; 2] = [Uint128::zero(), Uint128::zero()];
 
-    // create messages to pay for toll and spread fees, check flag is true if this follows a reverse
-    if !swap.fees_paid {
-        let mut fees = transfer_fees(deps.as_ref(), swap.trader, swap.vamm, swap.open_notional)?;
+    // limit order fills are paid from the margin and fees escrowed when the order was placed
+    if !swap.escrowed {
+        let mut funds = read_sent_funds(deps.storage)?;
+
+        // create transfer messages depending on PnL
+        if swap.margin_to_vault > Integer::zero() {
+            match config.eligible_collateral {
+                AssetInfo::NativeToken { .. } => {
+                    funds.required = funds.required.checked_add(swap_margin)?;
+                }
+                AssetInfo::Token { .. } => {
+                    msgs.push(execute_transfer_from(
+                        deps.storage,
+                        &swap.trader,
+                        &env.contract.address,
+                        swap.margin_to_vault.value,
+                    )?);
+                }
+            }
+        };
 
-        // add the fee transfer messages
-        msgs.append(&mut fees.messages);
+        // create messages to pay for toll and spread fees, check flag is true if this follows a reverse
+        if !swap.fees_paid {
+            let mut fees = transfer_fees(
+                deps.as_ref(),
+                &env,
+                swap.trader,
+                &swap.vamm,
+                swap.spread_fee,
+                swap.toll_fee,
+            )?;
+
+            // add the fee transfer messages
+            msgs.append(&mut fees.messages);
+
+            // add the total fees to the required funds counter
+            funds.required = funds
+                .required
+                .checked_add(fees.spread_fee)?
+                .checked_add(fees.toll_fee)?;
+
+            fees_amount[0] = fees.spread_fee;
+            fees_amount[1] = fees.toll_fee;
+        };
 
-        // add the total fees to the required funds counter
-        funds.required = funds
-            .required
-            .checked_add(fees.spread_fee)?
-            .checked_add(fees.toll_fee)?;
+        // check if native tokens are sufficient
+        if let AssetInfo::NativeToken { .. } = config.eligible_collateral {
+            funds.are_sufficient()?;
+        }
 
-        fees_amount[0] = fees.spread_fee;
-        fees_amount[1] = fees.toll_fee;
-    };
+        remove_sent_funds(deps.storage);
+    } else if let Some(order) = read_tmp_limit_order(deps.storage, position_id)? {
+        // pay the fees from the escrow of the limit order and refund what is left over
+        let fee_routing = get_fee_routing(deps.storage)?;
+        msgs.append(&mut route_fee(
+            deps.storage,
+            &fee_routing.spread_fee,
+            &swap.vamm,
+            None,
+            swap.spread_fee,
+        )?);
+        msgs.append(&mut route_fee(
+            deps.storage,
+            &fee_routing.toll_fee,
+            &swap.vamm,
+            None,
+            swap.toll_fee,
+        )?);
+
+        let fee_refund = order
+            .fee_amount
+            .checked_sub(swap.spread_fee.checked_add(swap.toll_fee)?)?;
+        if !fee_refund.is_zero() {
+            msgs.push(execute_transfer(deps.storage, &swap.trader, fee_refund)?);
+        }
 
-    // check if native tokens are sufficient
-    if let AssetInfo::NativeToken { .. } = config.eligible_collateral {
-        funds.are_sufficient()?;
+        fees_amount[0] = swap.spread_fee;
+        fees_amount[1] = swap.toll_fee;
+
+        remove_tmp_limit_order(deps.storage, position_id);
     }
 
+    increase_trader_volume(
+        deps.storage,
+        &position.trader,
+        env.block.time.seconds(),
+        input,
+    )?;
+
     store_state(deps.storage, &state)?;
 
     remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
-    remove_sent_funds(deps.storage);
 
-    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
-        ("action", "update_position_reply"),
-        ("entry_price", &position.entry_price.to_string()),
-        ("spread_fee", &fees_amount[0].to_string()),
-        ("toll_fee", &fees_amount[1].to_string()),
+    Ok(Response::new()
+        .add_submessages(funding_msgs)
+        .add_submessages(msgs)
+        .add_attributes(vec![
+            ("action", "update_position_reply"),
+            ("entry_price", &position.entry_price.to_string()),
+            ("spread_fee", &fees_amount[0].to_string()),
+            ("toll_fee", &fees_amount[1].to_string()),
+        ]))
+}
+
+// Reports the outcome of a position processed in its own call as part of a batch, a position
+// that fails is reverted on its own without reverting the rest of the batch
+pub fn batch_position_reply(deps: DepsMut, outcome: &str) -> StdResult<Response> {
+    let position_id = pop_tmp_batch(deps.storage)?;
+
+    Ok(Response::new().add_attribute(format!("position_{}", position_id), outcome))
+}
+
+// Reports the outcome of a limit order filled in its own call as part of `execute_limit_orders`,
+// an order whose fill fails stays in the book without reverting the rest of the batch
+pub fn batch_limit_order_reply(deps: DepsMut, outcome: &str) -> StdResult<Response> {
+    let order_id = pop_tmp_batch(deps.storage)?;
+
+    Ok(Response::new().add_attribute(format!("order_{}", order_id), outcome))
+}
+
+// Restores a limit order to the book when its fill fails, e.g. because the swap would execute past
+// the limit price, so that the rest of the batch is still filled
+pub fn limit_order_fill_failure_reply(deps: DepsMut, err: &str) -> StdResult<Response> {
+    let (position_id, order) = match read_first_tmp_limit_order(deps.storage)? {
+        Some(pending) => pending,
+        None => return Err(StdError::generic_err("No pending limit order fill")),
+    };
+
+    let vamm_key = keccak_256(&[order.vamm.as_bytes()].concat());
+    store_limit_order(deps.storage, &vamm_key, &order)?;
+
+    remove_tmp_limit_order(deps.storage, position_id);
+    remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "limit_order_fill_failure"),
+        ("order_id", &order.order_id.to_string()),
+        ("vamm", order.vamm.as_ref()),
+        ("trader", order.trader.as_ref()),
+        ("reason", err),
     ]))
 }
 
 // Closes position after successful execution of the swap
 pub fn close_position_reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     _input: Uint128,
     output: Uint128,
     position_id: u64,
 ) -> StdResult<Response> {
     let swap = read_tmp_swap(deps.storage, &position_id.to_be_bytes())?;
+    let funding_msgs = settle_continuous_funding(deps.branch(), env.clone(), &swap.vamm)?;
     let vamm_key = keccak_256(&[swap.vamm.as_bytes()].concat());
     let position = read_position(deps.storage, &vamm_key, position_id)?;
 
@@ -202,42 +303,90 @@ pub fn close_position_reply(
     let RemainMarginResponse {
         funding_payment,
         margin,
-        bad_debt,
+        mut bad_debt,
         latest_premium_fraction: _,
+        latest_funding_id: _,
     } = calc_remain_margin_with_funding_payment(deps.as_ref(), position.clone(), margin_delta)?;
 
-    let withdraw_amount = Integer::new_positive(margin).checked_add(swap.unrealized_pnl)?;
-
     let mut msgs: Vec<SubMsg> = vec![];
 
+    // a loss larger than the margin is covered by converting the collateral backing the position
+    let (converted, remaining_collateral) =
+        convert_collateral(deps.as_ref(), &position, bad_debt, &mut msgs)?;
+    bad_debt = bad_debt.checked_sub(converted)?;
+
+    // cross margin positions settle their loss against the rest of the account
+    if !bad_debt.is_zero() && position.margin_mode == MarginMode::Cross {
+        bad_debt = cover_cross_margin_loss(deps.branch(), &position, bad_debt)?;
+    }
+
+    let mut withdraw_amount = Integer::new_positive(margin).checked_add(swap.unrealized_pnl)?;
+
     // to prevent attacker to leverage the bad debt to withdraw extra token from insurance fund
     if !bad_debt.is_zero() {
         return Err(StdError::generic_err("Cannot close position - bad debt"));
     }
 
+    let config = read_config(deps.storage)?;
     let mut state = read_state(deps.storage)?;
+
+    // keepers triggering TP/SL are paid out of the remaining margin, capped so no bad debt is created
+    let mut payouts: Vec<(Addr, Uint128)> = vec![];
+    let mut execution_fee = Uint128::zero();
+    if let Some(keeper) = read_tmp_keeper(deps.storage, position_id)? {
+        if withdraw_amount.is_positive() {
+            execution_fee = output
+                .checked_mul(config.execution_fee)?
+                .checked_div(config.decimals)?
+                .min(withdraw_amount.value);
+            withdraw_amount = withdraw_amount - Integer::new_positive(execution_fee);
+        }
+
+        if !execution_fee.is_zero() {
+            payouts.push((keeper, execution_fee));
+        }
+
+        remove_tmp_keeper(deps.storage, position_id);
+    }
+
     if !withdraw_amount.is_zero() {
-        let config = read_config(deps.storage)?;
-        msgs.append(&mut withdraw(
-            deps.as_ref(),
-            env,
+        payouts.push((swap.trader.clone(), withdraw_amount.value));
+    }
+
+    // the keeper and the trader are paid out of the same balance
+    if !payouts.is_empty() {
+        let payouts: Vec<(&Addr, Uint128)> = payouts
+            .iter()
+            .map(|(receiver, amount)| (receiver, *amount))
+            .collect();
+        msgs.append(&mut withdraw_many(
+            deps.branch(),
+            env.clone(),
             &mut state,
-            &swap.trader,
+            &swap.vamm,
+            &payouts,
             config.eligible_collateral,
-            withdraw_amount.value,
+            Uint128::zero(),
             Uint128::zero(),
         )?);
     }
 
+    // return any non quote asset collateral that was not converted
+    if let Some(collateral) = &remaining_collateral {
+        msgs.push(execute_transfer_collateral(collateral, &swap.trader, None)?);
+    }
+
     // create array for fee amounts
     let mut fees_amount: [Uint128; 2] = [Uint128::zero(), Uint128::zero()];
 
     if !position.notional.is_zero() {
         let mut fees = transfer_fees(
             deps.as_ref(),
+            &env,
             swap.trader.clone(),
-            swap.vamm.clone(),
-            position.notional,
+            &swap.vamm,
+            swap.spread_fee,
+            swap.toll_fee,
         )?;
 
         fees_amount[0] = fees.spread_fee;
@@ -246,6 +395,8 @@ pub fn close_position_reply(
         msgs.append(&mut fees.messages);
     }
 
+    increase_trader_volume(deps.storage, &swap.trader, env.block.time.seconds(), output)?;
+
     let value =
         margin_delta + Integer::new_positive(bad_debt) + Integer::new_positive(position.notional);
 
@@ -262,26 +413,32 @@ pub fn close_position_reply(
     store_state(deps.storage, &state)?;
     remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
 
-    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
-        ("action", "close_position_reply"),
-        ("total_position", &total_position.to_string()),
-        ("pnl", &margin_delta.to_string()),
-        ("spread_fee", &fees_amount[0].to_string()),
-        ("toll_fee", &fees_amount[1].to_string()),
-        ("funding_payment", &funding_payment.to_string()),
-        ("bad_debt", &bad_debt.to_string()),
-    ]))
+    Ok(Response::new()
+        .add_submessages(funding_msgs)
+        .add_submessages(msgs)
+        .add_attributes(vec![
+            ("action", "close_position_reply"),
+            ("total_position", &total_position.to_string()),
+            ("pnl", &margin_delta.to_string()),
+            ("spread_fee", &fees_amount[0].to_string()),
+            ("toll_fee", &fees_amount[1].to_string()),
+            ("funding_payment", &funding_payment.to_string()),
+            ("bad_debt", &bad_debt.to_string()),
+            ("execution_fee", &execution_fee.to_string()),
+            ("converted_collateral", &converted.to_string()),
+        ]))
 }
 
 // Partially closes position
 pub fn partial_close_position_reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     input: Uint128,
     output: Uint128,
     position_id: u64,
 ) -> StdResult<Response> {
     let swap = read_tmp_swap(deps.storage, &position_id.to_be_bytes())?;
+    let funding_msgs = settle_continuous_funding(deps.branch(), env.clone(), &swap.vamm)?;
     let vamm_key = keccak_256(&[swap.vamm.as_bytes()].concat());
     let mut position = read_position(deps.storage, &vamm_key, position_id)?;
 
@@ -312,6 +469,7 @@ pub fn partial_close_position_reply(
         margin,
         bad_debt,
         latest_premium_fraction,
+        latest_funding_id,
     } = calc_remain_margin_with_funding_payment(deps.as_ref(), position.clone(), realized_pnl)?;
 
     let unrealized_pnl_after = swap.unrealized_pnl - realized_pnl;
@@ -326,13 +484,28 @@ pub fn partial_close_position_reply(
     };
 
     // calculate the fees
-    let fees = transfer_fees(deps.as_ref(), swap.trader, swap.vamm, swap.open_notional)?;
+    let fees = transfer_fees(
+        deps.as_ref(),
+        &env,
+        swap.trader,
+        &swap.vamm,
+        swap.spread_fee,
+        swap.toll_fee,
+    )?;
+
+    increase_trader_volume(
+        deps.storage,
+        &position.trader,
+        env.block.time.seconds(),
+        input,
+    )?;
 
     // set the new position
     position.size += signed_output;
     position.margin = margin;
     position.notional = remaining_notional.value;
     position.last_updated_premium_fraction = latest_premium_fraction;
+    position.last_updated_funding_id = latest_funding_id;
     position.block_time = env.block.time.seconds();
 
     let vamm_key = keccak_256(&[position.vamm.as_bytes()].concat());
@@ -348,6 +521,7 @@ pub fn partial_close_position_reply(
     remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
 
     Ok(Response::new()
+        .add_submessages(funding_msgs)
         .add_submessages(fees.messages)
         .add_attributes(vec![
             ("action", "partial_close_position_reply"),
@@ -361,15 +535,16 @@ pub fn partial_close_position_reply(
 
 // Liquidates position after successful execution of the swap
 pub fn liquidate_reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     _input: Uint128,
     output: Uint128,
     position_id: u64,
 ) -> StdResult<Response> {
     let swap = read_tmp_swap(deps.storage, &position_id.to_be_bytes())?;
+    let funding_msgs = settle_continuous_funding(deps.branch(), env.clone(), &swap.vamm)?;
 
-    let liquidator = read_tmp_liquidator(deps.storage)?;
+    let liquidator = read_tmp_liquidator(deps.storage, position_id)?;
 
     let vamm_key = keccak_256(&[swap.vamm.as_bytes()].concat());
     let position = read_position(deps.storage, &vamm_key, position_id)?;
@@ -396,6 +571,26 @@ pub fn liquidate_reply(
 
     let liquidation_fee = liquidation_penalty.checked_div(Uint128::from(2u64))?;
 
+    let mut msgs: Vec<SubMsg> = vec![];
+
+    // collateral backing the position is converted to cover the bad debt and the penalty that the
+    // margin can't, the rest of it is returned to the trader
+    let collateral_shortfall = remain_margin
+        .bad_debt
+        .checked_add(liquidation_penalty.saturating_sub(remain_margin.margin))?;
+    let (converted, remaining_collateral) =
+        convert_collateral(deps.as_ref(), &position, collateral_shortfall, &mut msgs)?;
+
+    let converted_bad_debt = converted.min(remain_margin.bad_debt);
+    remain_margin.bad_debt = remain_margin.bad_debt.checked_sub(converted_bad_debt)?;
+    remain_margin.margin = remain_margin
+        .margin
+        .checked_add(converted.checked_sub(converted_bad_debt)?)?;
+
+    if let Some(collateral) = &remaining_collateral {
+        msgs.push(execute_transfer_collateral(collateral, &swap.trader, None)?);
+    }
+
     if liquidation_fee > remain_margin.margin {
         let bad_debt = liquidation_fee.checked_sub(remain_margin.margin)?;
         remain_margin.bad_debt = remain_margin.bad_debt.checked_add(bad_debt)?;
@@ -406,35 +601,56 @@ pub fn liquidate_reply(
         remain_margin.margin = remain_margin.margin.checked_sub(liquidation_fee)?;
     }
 
-    let mut msgs: Vec<SubMsg> = vec![];
+    // cross margin positions settle their bad debt against the rest of the account
+    if !remain_margin.bad_debt.is_zero() && position.margin_mode == MarginMode::Cross {
+        remain_margin.bad_debt =
+            cover_cross_margin_loss(deps.branch(), &position, remain_margin.bad_debt)?;
+    }
 
+    // the bad debt is withdrawn along with the liquidation fee, so the fee is cut first if the
+    // insurance fund can't cover it
     let mut state = read_state(deps.storage)?;
-    let pre_paid_shortfall = if !remain_margin.bad_debt.is_zero() {
-        realize_bad_debt(deps.as_ref(), remain_margin.bad_debt, &mut msgs, &mut state)?
-    } else {
-        Uint128::zero()
-    };
+    let bad_debt_withdrawal = prepay_bad_debt(remain_margin.bad_debt, &mut state)?;
+
+    // the protocol share of the penalty is routed as the liquidation fee
+    let protocol_fee = remain_margin
+        .margin
+        .min(liquidation_penalty.checked_sub(liquidation_fee)?);
+    msgs.append(&mut route_fee(
+        deps.storage,
+        &get_fee_routing(deps.storage)?.liquidation_fee,
+        &swap.vamm,
+        None,
+        protocol_fee,
+    )?);
 
     // any remaining margin goes to the insurance contract
-    if !remain_margin.margin.is_zero() {
+    let remaining_margin = remain_margin.margin.checked_sub(protocol_fee)?;
+    if !remaining_margin.is_zero() {
         let msg = match config.insurance_fund {
             Some(insurance_fund) => {
-                execute_transfer(deps.storage, &insurance_fund, remain_margin.margin)?
+                execute_transfer(deps.storage, &insurance_fund, remaining_margin)?
             }
             None => return Err(StdError::generic_err("insurance fund is not registered")),
         };
 
         msgs.push(msg);
+        msgs.push(execute_insurance_fund_credit(
+            deps.storage,
+            &swap.vamm,
+            remaining_margin,
+        )?);
     }
 
-    msgs.append(&mut withdraw(
-        deps.as_ref(),
+    msgs.append(&mut withdraw_many(
+        deps.branch(),
         env.clone(),
         &mut state,
-        &liquidator,
+        &swap.vamm,
+        &[(&liquidator, liquidation_fee)],
         config.eligible_collateral,
-        liquidation_fee,
-        pre_paid_shortfall,
+        converted,
+        bad_debt_withdrawal,
     )?);
 
     store_state(deps.storage, &state)?;
@@ -443,33 +659,38 @@ pub fn liquidate_reply(
     let total_position = remove_position(deps.storage, &vamm_key, &position).unwrap();
 
     remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
-    remove_tmp_liquidator(deps.storage);
+    remove_tmp_liquidator(deps.storage, position_id);
 
     enter_restriction_mode(deps.storage, swap.vamm, env.block.height)?;
 
-    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
-        ("action", "liquidation_reply"),
-        ("total_position", &total_position.to_string()),
-        ("liquidation_fee", &liquidation_fee.to_string()),
-        ("pnl", &margin_delta.to_string()),
-        (
-            "funding_payment",
-            &remain_margin.funding_payment.to_string(),
-        ),
-        ("bad_debt", &remain_margin.bad_debt.to_string()),
-    ]))
+    Ok(Response::new()
+        .add_submessages(funding_msgs)
+        .add_submessages(msgs)
+        .add_attributes(vec![
+            ("action", "liquidation_reply"),
+            ("total_position", &total_position.to_string()),
+            ("liquidation_fee", &liquidation_fee.to_string()),
+            ("pnl", &margin_delta.to_string()),
+            (
+                "funding_payment",
+                &remain_margin.funding_payment.to_string(),
+            ),
+            ("bad_debt", &remain_margin.bad_debt.to_string()),
+            ("converted_collateral", &converted.to_string()),
+        ]))
 }
 
 // Partially liquidates the position
 pub fn partial_liquidation_reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     input: Uint128,
     output: Uint128,
     position_id: u64,
 ) -> StdResult<Response> {
     let swap = read_tmp_swap(deps.storage, &position_id.to_be_bytes())?;
-    let liquidator = read_tmp_liquidator(deps.storage)?;
+    let funding_msgs = settle_continuous_funding(deps.branch(), env.clone(), &swap.vamm)?;
+    let liquidator = read_tmp_liquidator(deps.storage, position_id)?;
 
     let vamm_key = keccak_256(&[swap.vamm.as_bytes()].concat());
     let mut position = read_position(deps.storage, &vamm_key, position_id)?;
@@ -492,6 +713,16 @@ pub fn partial_liquidation_reply(
         position.size += Integer::new_negative(input);
     }
 
+    // cross margin positions top up the margin they are short of from the rest of the account
+    let margin_loss = realized_pnl.value.checked_add(liquidation_penalty)?;
+    let shortfall = margin_loss.saturating_sub(position.margin);
+    if !shortfall.is_zero() && position.margin_mode == MarginMode::Cross {
+        let uncovered = cover_cross_margin_loss(deps.branch(), &position, shortfall)?;
+        position.margin = position
+            .margin
+            .checked_add(shortfall.checked_sub(uncovered)?)?;
+    }
+
     // reduce the traders margin
     position.margin = position
         .margin
@@ -518,21 +749,21 @@ pub fn partial_liquidation_reply(
     let mut messages: Vec<SubMsg> = vec![];
     let mut state = read_state(deps.storage)?;
     if !liquidation_fee.is_zero() {
-        let msg = match config.insurance_fund {
-            Some(insurance_fund) => {
-                execute_transfer(deps.storage, &insurance_fund, liquidation_fee)?
-            }
-            None => return Err(StdError::generic_err("insurance fund is not registered")),
-        };
-
-        messages.push(msg);
+        messages.append(&mut route_fee(
+            deps.storage,
+            &get_fee_routing(deps.storage)?.liquidation_fee,
+            &swap.vamm,
+            None,
+            liquidation_fee,
+        )?);
 
         // calculate token balance that should be remaining once
         // insurance fees have been paid
         messages.append(&mut withdraw(
-            deps.as_ref(),
+            deps.branch(),
             env.clone(),
             &mut state,
+            &swap.vamm,
             &liquidator,
             config.eligible_collateral,
             liquidation_fee,
@@ -544,11 +775,12 @@ pub fn partial_liquidation_reply(
     store_state(deps.storage, &state)?;
 
     remove_tmp_swap(deps.storage, &position_id.to_be_bytes());
-    remove_tmp_liquidator(deps.storage);
+    remove_tmp_liquidator(deps.storage, position_id);
 
     enter_restriction_mode(deps.storage, swap.vamm, env.block.height)?;
 
     Ok(Response::new()
+        .add_submessages(funding_msgs)
         .add_submessages(messages)
         .add_attributes(vec![
             ("action", "partial_liquidation_reply"),
@@ -563,32 +795,152 @@ pub fn pay_funding_reply(
     deps: DepsMut,
     env: Env,
     premium_fraction: Integer,
+    funding_id: u64,
     sender: &str,
 ) -> StdResult<Response> {
     let vamm = deps.api.addr_validate(sender)?;
 
     // update the cumulative premium fraction
-    append_cumulative_premium_fraction(deps.storage, vamm.clone(), premium_fraction)?;
+    append_cumulative_premium_fraction(deps.storage, vamm.clone(), premium_fraction, funding_id)?;
 
-    let vamm_controller = VammController(vamm);
+    let vamm_controller = VammController(vamm.clone());
     let total_position_size = vamm_controller.state(&deps.querier)?.total_position_size;
 
     let config = read_config(deps.storage)?;
     let funding_payment =
         total_position_size * premium_fraction / Integer::new_positive(config.decimals);
 
+    let msgs = funding_payment_msgs(deps.as_ref(), env, &vamm, funding_payment)?;
+
+    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
+        ("action", "pay_funding_reply"),
+        ("funding_payment", &funding_payment.to_string()),
+    ]))
+}
+
+/// settles the funding paid by the open interest of a vamm since the last settlement when the
+/// vamm accrues funding continuously, the imbalance between longs and shorts is exchanged with
+/// the insurance fund as in the discrete settlement
+fn settle_continuous_funding(deps: DepsMut, env: Env, vamm: &Addr) -> StdResult<Vec<SubMsg>> {
+    let funding_index = VammController(vamm.clone()).funding_index(&deps.querier)?;
+
+    let mut vamm_map = read_vamm_map(deps.storage, vamm)?;
+    let funding_payment = funding_index
+        .cumulative_funding_payment
+        .checked_sub(vamm_map.settled_funding_payment)?;
+
+    vamm_map.settled_funding_payment = funding_index.cumulative_funding_payment;
+    store_vamm_map(deps.storage, vamm.clone(), &vamm_map)?;
+
+    funding_payment_msgs(deps.as_ref(), env, vamm, funding_payment)
+}
+
+/// positive funding payments are what traders paid in excess and go to the insurance fund,
+/// negative ones are covered by it
+fn funding_payment_msgs(
+    deps: Deps,
+    env: Env,
+    vamm: &Addr,
+    funding_payment: Integer,
+) -> StdResult<Vec<SubMsg>> {
+    if funding_payment.is_zero() {
+        return Ok(vec![]);
+    }
+
+    if funding_payment.is_negative() {
+        Ok(vec![execute_insurance_fund_withdrawal(
+            deps,
+            vamm,
+            funding_payment.value,
+        )?])
+    } else {
+        execute_transfer_to_insurance_fund(deps, env, vamm, funding_payment.value)
+    }
+}
+
+/// settles the cost of a repeg or k adjustment, the insurance fund pays what traders gain
+/// and receives what they lose
+pub fn adjust_vamm_reply(
+    deps: DepsMut,
+    env: Env,
+    adjustment_cost: Integer,
+    sender: &str,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(sender)?;
+
     let mut response: Response = Response::new();
 
-    if funding_payment.is_negative() && !funding_payment.is_zero() {
-        let msg = execute_insurance_fund_withdrawal(deps.as_ref(), funding_payment.value)?;
-        response = response.add_submessage(msg);
-    } else if funding_payment.is_positive() && !funding_payment.is_zero() {
-        let msg = execute_transfer_to_insurance_fund(deps.as_ref(), env, funding_payment.value)?;
+    if adjustment_cost.is_positive() && !adjustment_cost.is_zero() {
+        let msg = execute_insurance_fund_withdrawal(deps.as_ref(), &vamm, adjustment_cost.value)?;
         response = response.add_submessage(msg);
+    } else if adjustment_cost.is_negative() && !adjustment_cost.is_zero() {
+        let msgs =
+            execute_transfer_to_insurance_fund(deps.as_ref(), env, &vamm, adjustment_cost.value)?;
+        response = response.add_submessages(msgs);
     };
 
     Ok(response.add_attributes(vec![
-        ("action", "pay_funding_reply"),
-        ("funding_payment", &funding_payment.to_string()),
+        ("action", "adjust_vamm_reply"),
+        ("adjustment_cost", &adjustment_cost.to_string()),
+    ]))
+}
+
+/// records the part of an insurance fund withdrawal the insurance fund couldn't cover as bad debt
+pub fn insurance_withdrawal_reply(deps: DepsMut, shortfall: Uint128) -> StdResult<Response> {
+    if !shortfall.is_zero() {
+        let mut state = read_state(deps.storage)?;
+        state.uncovered_bad_debt = state.uncovered_bad_debt.checked_add(shortfall)?;
+        store_state(deps.storage, &state)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "insurance_withdrawal_reply"),
+        ("uncovered_bad_debt", &shortfall.to_string()),
+    ]))
+}
+
+/// makes the payouts that relied on an insurance fund withdrawal, they are cut pro rata by the
+/// part the insurance fund couldn't cover and anything beyond them is recorded as bad debt
+pub fn insurance_payout_reply(deps: DepsMut, shortfall: Uint128) -> StdResult<Response> {
+    let TmpPayouts {
+        payouts,
+        prepaid_bad_debt,
+    } = pop_tmp_payouts(deps.storage)?;
+
+    let total = payouts
+        .iter()
+        .try_fold(Uint128::zero(), |total, (_, amount)| {
+            total.checked_add(*amount)
+        })?;
+    let cut = shortfall.min(total);
+
+    let mut state = read_state(deps.storage)?;
+    state.prepaid_bad_debt = state
+        .prepaid_bad_debt
+        .saturating_sub(shortfall.min(prepaid_bad_debt));
+    state.uncovered_bad_debt = state
+        .uncovered_bad_debt
+        .checked_add(shortfall.checked_sub(cut)?)?;
+    store_state(deps.storage, &state)?;
+
+    let mut msgs: Vec<SubMsg> = vec![];
+    for (receiver, amount) in payouts {
+        if amount.is_zero() {
+            continue;
+        }
+
+        let amount = amount.multiply_ratio(total.checked_sub(cut)?, total);
+        if !amount.is_zero() {
+            msgs.push(execute_transfer(deps.storage, &receiver, amount)?);
+        }
+    }
+
+    Ok(Response::new().add_submessages(msgs).add_attributes(vec![
+        ("action", "insurance_payout_reply"),
+        ("payout_cut", &cut.to_string()),
+        (
+            "uncovered_bad_debt",
+            &shortfall.checked_sub(cut)?.to_string(),
+        ),
     ]))
 }
@@ -5,9 +5,15 @@ use cosmwasm_std::{
 };
 use cosmwasm_storage::{singleton, singleton_read, Bucket, ReadonlyBucket};
 use std::cmp::Ordering;
+use std::convert::TryInto;
 
-use margined_common::{asset::Asset, integer::Integer};
-use margined_perp::margined_engine::{ConfigResponse, Position, Side};
+use margined_common::{
+    asset::{Asset, AssetInfo},
+    integer::Integer,
+};
+use margined_perp::margined_engine::{
+    Collateral, ConfigResponse, FeeRouting, FeeTier, LimitOrder, MarginMode, Position, Side,
+};
 
 use crate::utils::calc_range_start;
 
@@ -15,19 +21,39 @@ use crate::utils::calc_range_start;
 pub const MAX_LIMIT: u32 = 100;
 pub const DEFAULT_LIMIT: u32 = 10;
 
+// trader volume is tracked per day over a rolling window
+pub const VOLUME_WINDOW_DAYS: u64 = 30;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
 pub static KEY_CONFIG: &[u8] = b"config";
 pub static KEY_STATE: &[u8] = b"state";
 pub static KEY_SENT_FUNDS: &[u8] = b"sent-funds";
 pub static KEY_TMP_SWAP: &[u8] = b"tmp-swap";
 pub static KEY_TMP_LIQUIDATOR: &[u8] = b"tmp-liquidator";
+pub static KEY_TMP_KEEPER: &[u8] = b"tmp-keeper";
+pub static KEY_TMP_LIMIT_ORDER: &[u8] = b"tmp-limit-order";
+pub static KEY_TMP_BATCH: &[u8] = b"tmp-batch";
+pub static KEY_TMP_PAYOUTS: &[u8] = b"tmp-payouts";
 pub static KEY_VAMM_MAP: &[u8] = b"vamm-map";
 pub static KEY_LAST_POSITION_ID: &[u8] = b"last_position_id";
+pub static KEY_LAST_ORDER_ID: &[u8] = b"last_order_id";
+pub static KEY_FEE_ROUTING: &[u8] = b"fee_routing";
+pub static KEY_FEE_TIERS: &[u8] = b"fee_tiers";
 
 static PREFIX_POSITION: &[u8] = b"position"; // prefix position
 pub static PREFIX_POSITION_BY_SIDE: &[u8] = b"position_by_direction"; // position from the direction
 pub static PREFIX_POSITION_BY_PRICE: &[u8] = b"position_by_price"; // position from the price
 pub static PREFIX_POSITION_BY_TRADER: &[u8] = b"position_by_trader"; // position from a trader
+pub static PREFIX_POSITION_BY_TAKE_PROFIT: &[u8] = b"position_by_take_profit"; // position from the take profit price
+pub static PREFIX_POSITION_BY_STOP_LOSS: &[u8] = b"position_by_stop_loss"; // position from the stop loss price
 pub static PREFIX_TICK: &[u8] = b"tick"; // this is tick with value is the total positions
+pub static PREFIX_COLLATERAL: &[u8] = b"collateral"; // eligible non quote asset collateral
+pub static PREFIX_REDUCE_ONLY: &[u8] = b"reduce_only"; // vamms that only accept risk reducing actions
+static PREFIX_LIMIT_ORDER: &[u8] = b"limit_order"; // prefix resting limit order
+pub static PREFIX_LIMIT_ORDER_BY_PRICE: &[u8] = b"limit_order_by_price"; // limit order from the price
+pub static PREFIX_LIMIT_ORDER_BY_TRADER: &[u8] = b"limit_order_by_trader"; // limit order from a trader
+pub static PREFIX_LIMIT_ORDER_TICK: &[u8] = b"limit_order_tick"; // tick with value is the total limit orders
+static PREFIX_TRADER_VOLUME: &[u8] = b"trader_volume"; // daily notional volume of a trader
 
 pub type Config = ConfigResponse;
 
@@ -42,10 +68,130 @@ pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
     }
 }
 
+pub fn store_fee_routing(storage: &mut dyn Storage, fee_routing: &FeeRouting) -> StdResult<()> {
+    singleton(storage, KEY_FEE_ROUTING).save(fee_routing)
+}
+
+pub fn read_fee_routing(storage: &dyn Storage) -> StdResult<Option<FeeRouting>> {
+    singleton_read(storage, KEY_FEE_ROUTING).may_load()
+}
+
+pub fn store_fee_tiers(storage: &mut dyn Storage, fee_tiers: &[FeeTier]) -> StdResult<()> {
+    singleton(storage, KEY_FEE_TIERS).save(&fee_tiers.to_vec())
+}
+
+pub fn read_fee_tiers(storage: &dyn Storage) -> StdResult<Vec<FeeTier>> {
+    Ok(singleton_read(storage, KEY_FEE_TIERS)
+        .may_load()?
+        .unwrap_or_default())
+}
+
+#[cw_serde]
+pub struct DailyVolume {
+    pub day: u64,
+    pub volume: Uint128,
+}
+
+// days that have fallen out of the window are ignored
+fn read_daily_volumes(
+    storage: &dyn Storage,
+    trader: &Addr,
+    now: u64,
+) -> StdResult<Vec<DailyVolume>> {
+    let today = now / SECONDS_PER_DAY;
+
+    let daily_volumes = ReadonlyBucket::<Vec<DailyVolume>>::new(storage, PREFIX_TRADER_VOLUME)
+        .may_load(trader.as_bytes())?
+        .unwrap_or_default();
+
+    Ok(daily_volumes
+        .into_iter()
+        .filter(|daily_volume| daily_volume.day + VOLUME_WINDOW_DAYS > today)
+        .collect())
+}
+
+pub fn increase_trader_volume(
+    storage: &mut dyn Storage,
+    trader: &Addr,
+    now: u64,
+    amount: Uint128,
+) -> StdResult<()> {
+    let today = now / SECONDS_PER_DAY;
+
+    let mut daily_volumes = read_daily_volumes(storage, trader, now)?;
+    match daily_volumes.last_mut() {
+        Some(daily_volume) if daily_volume.day == today => {
+            daily_volume.volume = daily_volume.volume.checked_add(amount)?;
+        }
+        _ => daily_volumes.push(DailyVolume {
+            day: today,
+            volume: amount,
+        }),
+    }
+
+    Bucket::new(storage, PREFIX_TRADER_VOLUME).save(trader.as_bytes(), &daily_volumes)
+}
+
+/// Returns the notional volume of the trader over the rolling window
+pub fn read_trader_volume(storage: &dyn Storage, trader: &Addr, now: u64) -> StdResult<Uint128> {
+    let mut volume = Uint128::zero();
+    for daily_volume in read_daily_volumes(storage, trader, now)? {
+        volume = volume.checked_add(daily_volume.volume)?;
+    }
+
+    Ok(volume)
+}
+
+pub fn store_collateral(storage: &mut dyn Storage, collateral: &Collateral) -> StdResult<()> {
+    Bucket::new(storage, PREFIX_COLLATERAL).save(collateral.asset.as_bytes(), collateral)
+}
+
+pub fn remove_collateral(storage: &mut dyn Storage, asset: &AssetInfo) {
+    Bucket::<Collateral>::new(storage, PREFIX_COLLATERAL).remove(asset.as_bytes())
+}
+
+pub fn read_collateral(storage: &dyn Storage, asset: &AssetInfo) -> StdResult<Collateral> {
+    ReadonlyBucket::new(storage, PREFIX_COLLATERAL)
+        .load(asset.as_bytes())
+        .map_err(|_| StdError::generic_err("Collateral is not registered"))
+}
+
+pub fn read_all_collateral(storage: &dyn Storage) -> StdResult<Vec<Collateral>> {
+    ReadonlyBucket::<Collateral>::new(storage, PREFIX_COLLATERAL)
+        .range(None, None, OrderBy::Ascending)
+        .take(MAX_LIMIT as usize)
+        .map(|item| item.map(|item| item.1))
+        .collect()
+}
+
+pub fn store_reduce_only(
+    storage: &mut dyn Storage,
+    vamm: &Addr,
+    reduce_only: bool,
+) -> StdResult<()> {
+    let mut bucket = Bucket::<bool>::new(storage, PREFIX_REDUCE_ONLY);
+
+    if reduce_only {
+        bucket.save(vamm.as_bytes(), &reduce_only)
+    } else {
+        bucket.remove(vamm.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn read_reduce_only(storage: &dyn Storage, vamm: &Addr) -> StdResult<bool> {
+    Ok(ReadonlyBucket::<bool>::new(storage, PREFIX_REDUCE_ONLY)
+        .may_load(vamm.as_bytes())?
+        .unwrap_or_default())
+}
+
 #[cw_serde]
 pub struct State {
     pub open_interest_notional: Uint128,
     pub prepaid_bad_debt: Uint128,
+    // bad debt the insurance fund couldn't cover, e.g. beyond the sub-fund of an isolated vamm
+    #[serde(default)]
+    pub uncovered_bad_debt: Uint128,
     pub pause: bool,
 }
 
@@ -80,7 +226,16 @@ pub fn store_position(
 ) -> StdResult<u64> {
     let position_id_key = &position.position_id.to_be_bytes();
     let price_key = position.entry_price.to_be_bytes();
+
+    // take profit and stop loss may have been updated since the position was stored
+    if let Some(stored) = ReadonlyBucket::<Position>::multilevel(storage, &[PREFIX_POSITION, key])
+        .may_load(position_id_key)?
+    {
+        remove_tp_sl_indexes(storage, key, &stored);
+    }
+
     Bucket::multilevel(storage, &[PREFIX_POSITION, key]).save(position_id_key, position)?;
+    store_tp_sl_indexes(storage, key, position)?;
 
     let tick_namespaces = &[PREFIX_TICK, key, position.side.as_bytes()];
     // first time then total is 0
@@ -134,6 +289,7 @@ pub fn remove_position(
     let price_key = position.entry_price.to_be_bytes();
 
     Bucket::<Position>::multilevel(storage, &[PREFIX_POSITION, key]).remove(position_id_key);
+    remove_tp_sl_indexes(storage, key, position);
 
     // not found means total is 0
     let tick_namespaces = &[PREFIX_TICK, key, position.side.as_bytes()];
@@ -185,6 +341,96 @@ pub fn remove_position(
     Ok(total_tick_orders)
 }
 
+// tp/sl indexes are sorted by price and then position id
+fn tp_sl_index_key(price: Uint128, position_id: u64) -> Vec<u8> {
+    [price.to_be_bytes().as_slice(), &position_id.to_be_bytes()].concat()
+}
+
+fn store_tp_sl_indexes(
+    storage: &mut dyn Storage,
+    key: &[u8],
+    position: &Position,
+) -> StdResult<()> {
+    Bucket::multilevel(
+        storage,
+        &[
+            PREFIX_POSITION_BY_TAKE_PROFIT,
+            key,
+            position.side.as_bytes(),
+        ],
+    )
+    .save(
+        &tp_sl_index_key(position.take_profit, position.position_id),
+        &position.position_id,
+    )?;
+
+    // a zero stop loss is never triggered
+    if let Some(stop_loss) = position.stop_loss.filter(|stop_loss| !stop_loss.is_zero()) {
+        Bucket::multilevel(
+            storage,
+            &[PREFIX_POSITION_BY_STOP_LOSS, key, position.side.as_bytes()],
+        )
+        .save(
+            &tp_sl_index_key(stop_loss, position.position_id),
+            &position.position_id,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn remove_tp_sl_indexes(storage: &mut dyn Storage, key: &[u8], position: &Position) {
+    Bucket::<u64>::multilevel(
+        storage,
+        &[
+            PREFIX_POSITION_BY_TAKE_PROFIT,
+            key,
+            position.side.as_bytes(),
+        ],
+    )
+    .remove(&tp_sl_index_key(position.take_profit, position.position_id));
+
+    if let Some(stop_loss) = position.stop_loss {
+        Bucket::<u64>::multilevel(
+            storage,
+            &[PREFIX_POSITION_BY_STOP_LOSS, key, position.side.as_bytes()],
+        )
+        .remove(&tp_sl_index_key(stop_loss, position.position_id));
+    }
+}
+
+/// read_positions_by_tp_sl: iterates the take profit or stop loss index of a side in the given
+/// order, returning positions until `reached` no longer holds
+pub fn read_positions_by_tp_sl(
+    storage: &dyn Storage,
+    key: &[u8],
+    prefix: &[u8],
+    side: &Side,
+    order_by: OrderBy,
+    limit: usize,
+    reached: impl Fn(&Position) -> StdResult<bool>,
+) -> StdResult<Vec<Position>> {
+    let index: ReadonlyBucket<u64> =
+        ReadonlyBucket::multilevel(storage, &[prefix, key, side.as_bytes()]);
+    let position_bucket: ReadonlyBucket<Position> =
+        ReadonlyBucket::multilevel(storage, &[PREFIX_POSITION, key]);
+
+    let mut positions: Vec<Position> = vec![];
+    for item in index.range(None, None, order_by) {
+        let (_, position_id) = item?;
+        let position = position_bucket.load(&position_id.to_be_bytes())?;
+
+        // prices are sorted so no further position can be reached
+        if positions.len() >= limit || !reached(&position)? {
+            break;
+        }
+
+        positions.push(position);
+    }
+
+    Ok(positions)
+}
+
 pub fn read_position(storage: &dyn Storage, key: &[u8], position_id: u64) -> StdResult<Position> {
     ReadonlyBucket::multilevel(storage, &[PREFIX_POSITION, key]).load(&position_id.to_be_bytes())
 }
@@ -217,6 +463,25 @@ pub fn read_positions_with_indexer<T: Serialize + DeserializeOwned>(
         .collect()
 }
 
+/// read_trader_positions: returns every position a trader holds in a vamm
+pub fn read_trader_positions(
+    storage: &dyn Storage,
+    key: &[u8],
+    trader: &Addr,
+) -> StdResult<Vec<Position>> {
+    let position_indexer: ReadonlyBucket<Side> = ReadonlyBucket::multilevel(
+        storage,
+        &[PREFIX_POSITION_BY_TRADER, key, trader.as_bytes()],
+    );
+    let position_bucket: ReadonlyBucket<Position> =
+        ReadonlyBucket::multilevel(storage, &[PREFIX_POSITION, key]);
+
+    position_indexer
+        .range(None, None, OrderBy::Ascending)
+        .map(|item| position_bucket.load(&item?.0))
+        .collect()
+}
+
 pub fn read_positions(
     storage: &dyn Storage,
     key: &[u8],
@@ -241,6 +506,209 @@ pub fn read_positions(
         .collect()
 }
 
+pub fn increase_last_order_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    // order ids were introduced after launch so the key may not exist yet
+    let last_order_id = singleton_read::<u64>(storage, KEY_LAST_ORDER_ID)
+        .may_load()?
+        .unwrap_or_default();
+
+    singleton(storage, KEY_LAST_ORDER_ID).save(&(last_order_id + 1))?;
+
+    Ok(last_order_id + 1)
+}
+
+pub fn store_limit_order(
+    storage: &mut dyn Storage,
+    key: &[u8],
+    order: &LimitOrder,
+) -> StdResult<u64> {
+    let order_id_key = &order.order_id.to_be_bytes();
+    let price_key = order.limit_price.to_be_bytes();
+    Bucket::multilevel(storage, &[PREFIX_LIMIT_ORDER, key]).save(order_id_key, order)?;
+
+    let tick_namespaces = &[PREFIX_LIMIT_ORDER_TICK, key, order.side.as_bytes()];
+    // first time then total is 0
+    let total_tick_orders = ReadonlyBucket::<u64>::multilevel(storage, tick_namespaces)
+        .load(&price_key)
+        .unwrap_or_default()
+        + 1;
+
+    // save total orders for a tick
+    Bucket::multilevel(storage, tick_namespaces).save(&price_key, &total_tick_orders)?;
+
+    Bucket::multilevel(
+        storage,
+        &[PREFIX_LIMIT_ORDER_BY_TRADER, key, order.trader.as_bytes()],
+    )
+    .save(order_id_key, &order.side)?;
+
+    Bucket::multilevel(
+        storage,
+        &[
+            PREFIX_LIMIT_ORDER_BY_PRICE,
+            key,
+            order.side.as_bytes(),
+            &price_key,
+        ],
+    )
+    .save(order_id_key, &order.side)?;
+
+    Ok(total_tick_orders)
+}
+
+pub fn remove_limit_order(
+    storage: &mut dyn Storage,
+    key: &[u8],
+    order: &LimitOrder,
+) -> StdResult<u64> {
+    let order_id_key = &order.order_id.to_be_bytes();
+    let price_key = order.limit_price.to_be_bytes();
+
+    Bucket::<LimitOrder>::multilevel(storage, &[PREFIX_LIMIT_ORDER, key]).remove(order_id_key);
+
+    // not found means total is 0
+    let tick_namespaces = &[PREFIX_LIMIT_ORDER_TICK, key, order.side.as_bytes()];
+    let mut total_tick_orders = ReadonlyBucket::<u64>::multilevel(storage, tick_namespaces)
+        .load(&price_key)
+        .unwrap_or_default();
+
+    // substract one order, if total is 0 mean not existed
+    if total_tick_orders > 0 {
+        total_tick_orders -= 1;
+        if total_tick_orders > 0 {
+            Bucket::multilevel(storage, tick_namespaces).save(&price_key, &total_tick_orders)?;
+        } else {
+            Bucket::<u64>::multilevel(storage, tick_namespaces).remove(&price_key);
+        }
+    }
+
+    Bucket::<Side>::multilevel(
+        storage,
+        &[PREFIX_LIMIT_ORDER_BY_TRADER, key, order.trader.as_bytes()],
+    )
+    .remove(order_id_key);
+
+    Bucket::<Side>::multilevel(
+        storage,
+        &[
+            PREFIX_LIMIT_ORDER_BY_PRICE,
+            key,
+            order.side.as_bytes(),
+            &price_key,
+        ],
+    )
+    .remove(order_id_key);
+
+    // return total orders belong to the tick
+    Ok(total_tick_orders)
+}
+
+pub fn read_limit_order(storage: &dyn Storage, key: &[u8], order_id: u64) -> StdResult<LimitOrder> {
+    ReadonlyBucket::multilevel(storage, &[PREFIX_LIMIT_ORDER, key])
+        .load(&order_id.to_be_bytes())
+        .map_err(|_| StdError::generic_err("Limit order not found"))
+}
+
+/// read_limit_orders: namespace is PREFIX + KEY + INDEXER, reads every order when no indexer is given
+pub fn read_limit_orders(
+    storage: &dyn Storage,
+    key: &[u8],
+    trader: Option<&Addr>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order_by: Option<OrderBy>,
+) -> StdResult<Vec<LimitOrder>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|id| id.to_be_bytes().to_vec());
+    let (start, end, order_by) = match order_by {
+        Some(OrderBy::Ascending) => (calc_range_start(start_after), None, OrderBy::Ascending),
+        _ => (None, start_after, OrderBy::Descending),
+    };
+
+    let order_bucket: ReadonlyBucket<LimitOrder> =
+        ReadonlyBucket::multilevel(storage, &[PREFIX_LIMIT_ORDER, key]);
+
+    match trader {
+        Some(trader) => {
+            let order_indexer: ReadonlyBucket<Side> = ReadonlyBucket::multilevel(
+                storage,
+                &[PREFIX_LIMIT_ORDER_BY_TRADER, key, trader.as_bytes()],
+            );
+
+            order_indexer
+                .range(start.as_deref(), end.as_deref(), order_by)
+                .take(limit)
+                .map(|item| order_bucket.load(&item?.0))
+                .collect()
+        }
+        None => order_bucket
+            .range(start.as_deref(), end.as_deref(), order_by)
+            .take(limit)
+            .map(|item| item.map(|item| item.1))
+            .collect(),
+    }
+}
+
+/// read_crossed_limit_orders: returns the orders on a side whose limit price has been crossed
+/// by the spot price, best priced ticks first and oldest orders first within a tick
+pub fn read_crossed_limit_orders(
+    storage: &dyn Storage,
+    key: &[u8],
+    side: &Side,
+    spot_price: Uint128,
+    limit: usize,
+) -> StdResult<Vec<LimitOrder>> {
+    let tick_bucket: ReadonlyBucket<u64> =
+        ReadonlyBucket::multilevel(storage, &[PREFIX_LIMIT_ORDER_TICK, key, side.as_bytes()]);
+    let order_bucket: ReadonlyBucket<LimitOrder> =
+        ReadonlyBucket::multilevel(storage, &[PREFIX_LIMIT_ORDER, key]);
+
+    // buy orders fill once the price falls to their limit, sell orders once it rises to it
+    let order_by = match side {
+        Side::Buy => OrderBy::Descending,
+        Side::Sell => OrderBy::Ascending,
+    };
+
+    let mut orders: Vec<LimitOrder> = vec![];
+    for item in tick_bucket.range(None, None, order_by) {
+        let (price_key, _) = item?;
+        let limit_price = Uint128::from(u128::from_be_bytes(
+            price_key
+                .as_slice()
+                .try_into()
+                .map_err(|_| StdError::generic_err("Invalid tick"))?,
+        ));
+
+        let is_crossed = match side {
+            Side::Buy => spot_price <= limit_price,
+            Side::Sell => spot_price >= limit_price,
+        };
+
+        if !is_crossed || orders.len() >= limit {
+            break;
+        }
+
+        let order_indexer: ReadonlyBucket<Side> = ReadonlyBucket::multilevel(
+            storage,
+            &[
+                PREFIX_LIMIT_ORDER_BY_PRICE,
+                key,
+                side.as_bytes(),
+                &price_key,
+            ],
+        );
+
+        for item in order_indexer
+            .range(None, None, OrderBy::Ascending)
+            .take(limit - orders.len())
+        {
+            orders.push(order_bucket.load(&item?.0)?);
+        }
+    }
+
+    Ok(orders)
+}
+
 /// Used to monitor that transferred native tokens are sufficient when opening a
 /// new position or relevant operations
 #[cw_serde]
@@ -292,6 +760,10 @@ pub struct TmpSwapInfo {
     pub fees_paid: bool,            // true if fees have been paid, used in case of reversing position
     pub take_profit: Uint128,       // take profit price of position
     pub stop_loss: Option<Uint128>, // stop loss price of position
+    pub margin_mode: MarginMode,    // isolated or cross margin
+    pub escrowed: bool,             // true if margin and fees were escrowed by a limit order
+    pub spread_fee: Uint128,        // spread fee quoted before the swap
+    pub toll_fee: Uint128,          // toll fee quoted before the swap
 }
 
 pub fn store_tmp_swap(storage: &mut dyn Storage, swap: &TmpSwapInfo) -> StdResult<()> {
@@ -307,26 +779,139 @@ pub fn read_tmp_swap(storage: &dyn Storage, position_id_key: &[u8]) -> StdResult
     ReadonlyBucket::new(storage, KEY_TMP_SWAP).load(position_id_key)
 }
 
-pub fn store_tmp_liquidator(storage: &mut dyn Storage, liquidator: &Addr) -> StdResult<()> {
-    Ok(storage.set(KEY_TMP_LIQUIDATOR, &to_vec(liquidator)?))
+pub fn store_tmp_liquidator(
+    storage: &mut dyn Storage,
+    position_id: u64,
+    liquidator: &Addr,
+) -> StdResult<()> {
+    Bucket::new(storage, KEY_TMP_LIQUIDATOR).save(&position_id.to_be_bytes(), liquidator)
 }
 
-pub fn remove_tmp_liquidator(storage: &mut dyn Storage) {
-    storage.remove(KEY_TMP_LIQUIDATOR)
+pub fn remove_tmp_liquidator(storage: &mut dyn Storage, position_id: u64) {
+    Bucket::<Addr>::new(storage, KEY_TMP_LIQUIDATOR).remove(&position_id.to_be_bytes())
 }
 
-pub fn read_tmp_liquidator(storage: &dyn Storage) -> StdResult<Addr> {
-    match storage.get(KEY_TMP_LIQUIDATOR) {
-        Some(data) => from_slice(&data),
+pub fn read_tmp_liquidator(storage: &dyn Storage, position_id: u64) -> StdResult<Addr> {
+    match ReadonlyBucket::new(storage, KEY_TMP_LIQUIDATOR).may_load(&position_id.to_be_bytes())? {
+        Some(liquidator) => Ok(liquidator),
         None => Err(StdError::generic_err("Addr not found")),
     }
 }
 
+pub fn store_tmp_keeper(
+    storage: &mut dyn Storage,
+    position_id: u64,
+    keeper: &Addr,
+) -> StdResult<()> {
+    Bucket::new(storage, KEY_TMP_KEEPER).save(&position_id.to_be_bytes(), keeper)
+}
+
+pub fn remove_tmp_keeper(storage: &mut dyn Storage, position_id: u64) {
+    Bucket::<Addr>::new(storage, KEY_TMP_KEEPER).remove(&position_id.to_be_bytes())
+}
+
+pub fn read_tmp_keeper(storage: &dyn Storage, position_id: u64) -> StdResult<Option<Addr>> {
+    ReadonlyBucket::new(storage, KEY_TMP_KEEPER).may_load(&position_id.to_be_bytes())
+}
+
+pub fn store_tmp_limit_order(
+    storage: &mut dyn Storage,
+    position_id: u64,
+    order: &LimitOrder,
+) -> StdResult<()> {
+    Bucket::new(storage, KEY_TMP_LIMIT_ORDER).save(&position_id.to_be_bytes(), order)
+}
+
+pub fn remove_tmp_limit_order(storage: &mut dyn Storage, position_id: u64) {
+    Bucket::<LimitOrder>::new(storage, KEY_TMP_LIMIT_ORDER).remove(&position_id.to_be_bytes())
+}
+
+pub fn read_tmp_limit_order(
+    storage: &dyn Storage,
+    position_id: u64,
+) -> StdResult<Option<LimitOrder>> {
+    ReadonlyBucket::new(storage, KEY_TMP_LIMIT_ORDER).may_load(&position_id.to_be_bytes())
+}
+
+pub fn store_tmp_batch(storage: &mut dyn Storage, position_ids: &[u64]) -> StdResult<()> {
+    singleton(storage, KEY_TMP_BATCH).save(&position_ids.to_vec())
+}
+
+// positions of a batch reply in the order they were submitted, so the first one is replying
+pub fn pop_tmp_batch(storage: &mut dyn Storage) -> StdResult<u64> {
+    let mut position_ids: Vec<u64> = singleton_read(storage, KEY_TMP_BATCH)
+        .may_load()?
+        .unwrap_or_default();
+
+    if position_ids.is_empty() {
+        return Err(StdError::generic_err("No pending batch position"));
+    }
+
+    let position_id = position_ids.remove(0);
+    singleton(storage, KEY_TMP_BATCH).save(&position_ids)?;
+
+    Ok(position_id)
+}
+
+#[cw_serde]
+pub struct TmpPayouts {
+    pub payouts: Vec<(Addr, Uint128)>,
+    pub prepaid_bad_debt: Uint128, // part of the withdrawal added to the prepaid bad debt
+}
+
+pub fn push_tmp_payouts(storage: &mut dyn Storage, payouts: TmpPayouts) -> StdResult<()> {
+    let mut pending: Vec<TmpPayouts> = singleton_read(storage, KEY_TMP_PAYOUTS)
+        .may_load()?
+        .unwrap_or_default();
+
+    pending.push(payouts);
+    singleton(storage, KEY_TMP_PAYOUTS).save(&pending)
+}
+
+// insurance fund withdrawals reply in the order they were submitted, so the first payouts are
+// the ones of the replying withdrawal
+pub fn pop_tmp_payouts(storage: &mut dyn Storage) -> StdResult<TmpPayouts> {
+    let mut pending: Vec<TmpPayouts> = singleton_read(storage, KEY_TMP_PAYOUTS)
+        .may_load()?
+        .unwrap_or_default();
+
+    if pending.is_empty() {
+        return Err(StdError::generic_err("No pending payouts"));
+    }
+
+    let payouts = pending.remove(0);
+    singleton(storage, KEY_TMP_PAYOUTS).save(&pending)?;
+
+    Ok(payouts)
+}
+
+// fills reply in the order they were submitted, so the oldest pending fill is the one replying
+pub fn read_first_tmp_limit_order(storage: &dyn Storage) -> StdResult<Option<(u64, LimitOrder)>> {
+    ReadonlyBucket::<LimitOrder>::new(storage, KEY_TMP_LIMIT_ORDER)
+        .range(None, None, OrderBy::Ascending)
+        .next()
+        .map(|item| {
+            let (key, order) = item?;
+            let position_id = u64::from_be_bytes(
+                key.as_slice()
+                    .try_into()
+                    .map_err(|_| StdError::generic_err("Invalid position id"))?,
+            );
+
+            Ok((position_id, order))
+        })
+        .transpose()
+}
+
 #[cw_serde]
 #[derive(Default)]
 pub struct VammMap {
     pub last_restriction_block: u64,
     pub cumulative_premium_fractions: Vec<Integer>,
+    #[serde(default)]
+    pub latest_funding_id: u64,
+    #[serde(default)]
+    pub settled_funding_payment: Integer, // continuous funding exchanged with the insurance fund
 }
 
 pub fn store_vamm_map(storage: &mut dyn Storage, vamm: Addr, vamm_map: &VammMap) -> StdResult<()> {
@@ -344,13 +929,15 @@ pub fn read_vamm_map(storage: &dyn Storage, vamm: &Addr) -> StdResult<VammMap> {
 }
 
 /// Accumulates the premium fractions at each settlement payment so that eventually users take
-/// their P&L
+/// their P&L, along with the id of the vamm funding record of the settlement
 pub fn append_cumulative_premium_fraction(
     storage: &mut dyn Storage,
     vamm: Addr,
     premium_fraction: Integer,
+    funding_id: u64,
 ) -> StdResult<()> {
     let mut vamm_map = read_vamm_map(storage, &vamm)?;
+    vamm_map.latest_funding_id = funding_id;
     // we push the first premium fraction to an empty array
     // else we add them together prior to pushing
     match vamm_map.cumulative_premium_fractions.len() {
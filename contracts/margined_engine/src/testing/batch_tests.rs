@@ -0,0 +1,261 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::{AppResponse, Executor},
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+// returns the outcome reported for a position in a batch
+fn outcome(res: &AppResponse, position_id: u64) -> String {
+    res.events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == format!("position_{}", position_id))
+        .unwrap()
+        .value
+        .clone()
+}
+
+#[test]
+fn test_liquidate_many_skips_healthy_positions() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    // set maintenance ratio as 10% to allow liquidation
+    let msg = engine
+        .set_margin_ratios(Uint128::from(100_000_000u128))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(20u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(30u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price down leaving both of alice's positions underwater
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(50u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = engine
+        .liquidate_many(vamm.addr().to_string(), vec![1, 2, 3, 2, 99], None)
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+
+    assert_eq!(outcome(&res, 1), "liquidation");
+    assert_eq!(outcome(&res, 2), "liquidation");
+    assert_eq!(outcome(&res, 3), "skipped: Position is overcollateralized");
+    assert!(outcome(&res, 99).starts_with("skipped"));
+
+    // only bob's position remains
+    engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap_err();
+    engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap_err();
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 3)
+        .unwrap();
+    assert_eq!(position.trader, bob);
+}
+
+#[test]
+fn test_trigger_tp_sl_many_skips_untriggered_positions() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    // price moves to 15.625, above alice's take profit
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(30u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = engine
+        .trigger_tp_sl_many(vamm.addr().to_string(), vec![], None)
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: No positions provided"
+    );
+
+    let msg = engine
+        .trigger_tp_sl_many(
+            vamm.addr().to_string(),
+            vec![1, 2],
+            Some(vec![to_decimals(0u64)]),
+        )
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Quote asset limits do not match the positions"
+    );
+
+    let msg = engine
+        .trigger_tp_sl_many(vamm.addr().to_string(), vec![1, 2], None)
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+
+    assert_eq!(outcome(&res, 1), "trigger_take_profit");
+    assert_eq!(
+        outcome(&res, 2),
+        "skipped: TP/SL price has not been reached"
+    );
+
+    engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap_err();
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(position.trader, bob);
+}
+
+#[test]
+fn test_trigger_tp_sl_many_checks_positions_at_execution() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // price moves to 18.225, above both take profits
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(16u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    // closing alice's position drops the price below bob's take profit
+    let msg = engine
+        .trigger_tp_sl_many(vamm.addr().to_string(), vec![1, 2], None)
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+
+    assert_eq!(outcome(&res, 1), "trigger_take_profit");
+    assert!(outcome(&res, 2).starts_with("failed"));
+
+    engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap_err();
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(position.trader, bob);
+
+    // batch positions can only be processed by the engine itself
+    let msg = engine
+        .trigger_tp_sl_batch_position(
+            vamm.addr().to_string(),
+            2,
+            Uint128::zero(),
+            carol.to_string(),
+        )
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+}
@@ -0,0 +1,459 @@
+use cosmwasm_std::{Addr, Uint128};
+use cw20::{Cw20Coin, Cw20Contract, Cw20ExecuteMsg, MinterResponse};
+use margined_common::{asset::AssetInfo, integer::Integer};
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    create_entry_points_testing,
+    cw_multi_test::{App, Executor},
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+// instantiates a second cw20 to be used as collateral, alice holds 100 and approves the engine
+fn setup_weth(router: &mut App, owner: &Addr, alice: &Addr, engine: &Addr) -> Cw20Contract {
+    let weth_id = router.store_code(Box::new(create_entry_points_testing!(cw20_base)));
+    let weth_addr = router
+        .instantiate_contract(
+            weth_id,
+            owner.clone(),
+            &cw20_base::msg::InstantiateMsg {
+                name: "Wrapped Ether".to_string(),
+                symbol: "WETH".to_string(),
+                decimals: 9,
+                initial_balances: vec![Cw20Coin {
+                    address: alice.to_string(),
+                    amount: to_decimals(100),
+                }],
+                mint: Some(MinterResponse {
+                    minter: owner.to_string(),
+                    cap: None,
+                }),
+                marketing: None,
+            },
+            &[],
+            "weth",
+            None,
+        )
+        .unwrap();
+
+    router
+        .execute_contract(
+            alice.clone(),
+            weth_addr.clone(),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: engine.to_string(),
+                amount: to_decimals(100),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    Cw20Contract(weth_addr)
+}
+
+#[test]
+fn test_register_collateral() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        engine,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let weth = setup_weth(&mut router, &owner, &alice, &engine.addr());
+
+    // only the owner can register collateral
+    let msg = engine
+        .register_collateral(
+            weth.addr().to_string(),
+            Uint128::from(800_000_000u128), // 0.8
+            "ETH".to_string(),
+        )
+        .unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+    router.execute(owner.clone(), msg).unwrap();
+
+    // the quote asset cannot be registered
+    let msg = engine
+        .register_collateral(
+            usdc.addr().to_string(),
+            Uint128::from(800_000_000u128),
+            "USDC".to_string(),
+        )
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Quote asset cannot be registered as collateral"
+    );
+
+    // risk factor cannot be greater than one
+    let msg = engine
+        .register_collateral(
+            weth.addr().to_string(),
+            Uint128::from(1_100_000_000u128),
+            "ETH".to_string(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap_err();
+
+    let collateral = engine
+        .collateral(&router.wrap(), weth.addr().to_string())
+        .unwrap();
+    assert_eq!(
+        collateral.asset,
+        AssetInfo::Token {
+            contract_addr: weth.addr()
+        }
+    );
+    assert_eq!(collateral.decimals, to_decimals(1));
+    assert_eq!(collateral.risk_factor, Uint128::from(800_000_000u128));
+
+    let all_collateral = engine.all_collateral(&router.wrap()).unwrap();
+    assert_eq!(all_collateral, vec![collateral]);
+
+    let msg = engine
+        .deregister_collateral(weth.addr().to_string())
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let all_collateral = engine.all_collateral(&router.wrap()).unwrap();
+    assert!(all_collateral.is_empty());
+}
+
+#[test]
+fn test_collateral_value_counts_towards_margin_ratio() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        engine,
+        vamm,
+        pricefeed,
+        ..
+    } = new_simple_scenario();
+
+    let weth = setup_weth(&mut router, &owner, &alice, &engine.addr());
+
+    let msg = engine
+        .register_collateral(
+            weth.addr().to_string(),
+            Uint128::from(800_000_000u128), // 0.8
+            "ETH".to_string(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine.set_pricefeed(pricefeed.addr().to_string()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15),
+            Some(to_decimals(9)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // expect to be 0.1
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(margin_ratio, Integer::new_positive(100_000_000u128));
+
+    let msg = engine
+        .deposit_collateral(
+            vamm.addr().to_string(),
+            1,
+            weth.addr().to_string(),
+            to_decimals(10u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let engine_balance = weth.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, to_decimals(10u64));
+
+    // 10 WETH * 10 * 0.8
+    let collateral_value = engine
+        .get_collateral_value(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(collateral_value, to_decimals(80u64));
+
+    // expect to be (25 + 80) / 250 = 0.42
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(margin_ratio, Integer::new_positive(420_000_000u128));
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(5u64), 1_000_000_000u64)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // expect to be (25 + 40) / 250 = 0.26
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(margin_ratio, Integer::new_positive(260_000_000u128));
+
+    // unregistered assets cannot be deposited
+    let msg = engine
+        .deposit_collateral(
+            vamm.addr().to_string(),
+            1,
+            "orai".to_string(),
+            to_decimals(10u64),
+            vec![],
+        )
+        .unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Collateral is not registered"
+    );
+
+    let msg = engine
+        .withdraw_collateral(vamm.addr().to_string(), 1, to_decimals(10u64))
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let alice_balance = weth.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(100u64));
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(position.collateral, None);
+}
+
+#[test]
+fn test_depreciated_collateral_is_liquidated() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let weth = setup_weth(&mut router, &owner, &alice, &engine.addr());
+
+    let msg = engine
+        .register_collateral(
+            weth.addr().to_string(),
+            Uint128::from(800_000_000u128), // 0.8
+            "ETH".to_string(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine.set_pricefeed(pricefeed.addr().to_string()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(20),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price back down to 10
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(5),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = engine
+        .deposit_collateral(
+            vamm.addr().to_string(),
+            1,
+            weth.addr().to_string(),
+            to_decimals(20u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // the collateral keeps the position afloat
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Position is overcollateralized"
+    );
+
+    let msg = engine
+        .withdraw_collateral(vamm.addr().to_string(), 1, to_decimals(20u64))
+        .unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Position is undercollateralized"
+    );
+
+    // collateral depreciates
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(1u64), 1_000_000_000u64)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert!(margin_ratio.is_negative());
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    // the collateral is seized by the insurance fund
+    let insurance_balance = weth.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(insurance_balance, to_decimals(20u64));
+
+    let engine_balance = weth.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, Uint128::zero());
+}
+
+#[test]
+fn test_close_converts_only_the_collateral_covering_the_loss() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        engine,
+        vamm,
+        usdc,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let weth = setup_weth(&mut router, &owner, &alice, &engine.addr());
+
+    let msg = engine
+        .register_collateral(
+            weth.addr().to_string(),
+            Uint128::from(800_000_000u128), // 0.8
+            "ETH".to_string(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine.set_pricefeed(pricefeed.addr().to_string()).unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(20),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // 20 WETH * 10 * 0.8 = 160
+    let msg = engine
+        .deposit_collateral(
+            vamm.addr().to_string(),
+            1,
+            weth.addr().to_string(),
+            to_decimals(20u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price back down to 10, the long loses more than its margin
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(5),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob, msg).unwrap();
+
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+
+    let msg = engine
+        .close_position(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let res = router.execute(alice.clone(), msg).unwrap();
+    let converted = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "converted_collateral")
+        .unwrap()
+        .value
+        .parse::<u128>()
+        .unwrap();
+    let converted = Uint128::from(converted);
+    assert!(!converted.is_zero());
+
+    // the insurance fund pays out the converted value and takes the collateral worth it
+    let new_insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(new_insurance_balance, insurance_balance - converted);
+
+    let seized = weth.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert!(seized < to_decimals(20u64));
+    assert!(seized.multiply_ratio(8u64, 1u64) >= converted);
+
+    // the rest of the collateral is returned
+    let alice_balance = weth.balance(&router.wrap(), alice).unwrap();
+    assert_eq!(alice_balance, to_decimals(100u64) - seized);
+
+    let engine_balance = weth.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, Uint128::zero());
+}
@@ -0,0 +1,363 @@
+use cosmwasm_std::Uint128;
+use margined_common::integer::Integer;
+use margined_perp::margined_engine::{MarginMode, Side};
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_cross_margin_hedged_account_is_not_liquidated() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    // alice opens a 10x long, entry price 12
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(15),
+            Some(to_decimals(9)),
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // alice hedges with a 2x short, entry price 12
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(100u64),
+            to_decimals(2u64),
+            to_decimals(9),
+            Some(to_decimals(15)),
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(position.margin_mode, MarginMode::Cross);
+
+    // the long alone is under water: (20 - 57.14) / 142.86
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert!(margin_ratio.is_negative());
+
+    // but the account is not: (120 - 57.14) / 342.86 = 18.33%
+    let config = engine.config(&router.wrap()).unwrap();
+    let account_margin_ratio = engine
+        .get_account_margin_ratio(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert!(account_margin_ratio > Integer::new_positive(config.maintenance_margin_ratio));
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Position is overcollateralized"
+    );
+}
+
+#[test]
+fn test_isolated_margin_hedged_position_is_liquidated() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(15),
+            Some(to_decimals(9)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(100u64),
+            to_decimals(2u64),
+            to_decimals(9),
+            Some(to_decimals(15)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(position.margin_mode, MarginMode::Isolated);
+
+    // isolated positions do not count towards the account
+    let account_margin_ratio = engine
+        .get_account_margin_ratio(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(account_margin_ratio, Integer::zero());
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let positions = engine
+        .get_all_positions(&router.wrap(), alice.to_string(), None, None, None)
+        .unwrap();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].position_id, 2);
+}
+
+#[test]
+fn test_cross_margin_account_under_water_is_liquidated() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(20),
+            None,
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(20),
+            None,
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price down to 8.1
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(100u64),
+            to_decimals(5u64),
+            to_decimals(5),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let account_margin_ratio = engine
+        .get_account_margin_ratio(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert!(account_margin_ratio.is_negative());
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let positions = engine
+        .get_all_positions(&router.wrap(), alice.to_string(), None, None, None)
+        .unwrap();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].position_id, 2);
+}
+
+#[test]
+fn test_cross_margin_position_loss_is_covered_by_the_account_on_close() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(15),
+            Some(to_decimals(9)),
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(100u64),
+            to_decimals(2u64),
+            to_decimals(9),
+            Some(to_decimals(15)),
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+
+    // the long loses more than its own margin, the rest is taken from the short
+    let msg = engine
+        .close_position(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let res = router.execute(alice.clone(), msg).unwrap();
+    let pnl = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "pnl")
+        .unwrap()
+        .value
+        .trim_start_matches('-')
+        .parse::<u128>()
+        .unwrap();
+    let loss = Uint128::from(pnl);
+    assert!(loss > to_decimals(20u64));
+
+    let new_alice_balance = usdc.balance(&router.wrap(), alice).unwrap();
+    assert_eq!(new_alice_balance, alice_balance);
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(
+        position.margin,
+        to_decimals(100u64) - (loss - to_decimals(20u64))
+    );
+}
+
+#[test]
+fn test_cross_margin_liquidation_bad_debt_is_covered_by_the_account() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(20),
+            None,
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position_with_margin_mode(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(10u64),
+            to_decimals(1u64),
+            to_decimals(5),
+            Some(to_decimals(15)),
+            to_decimals(0u64),
+            MarginMode::Cross,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price down far enough for the long to lose more than its margin
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(60u64),
+            to_decimals(5u64),
+            to_decimals(5),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let account_margin_ratio = engine
+        .get_account_margin_ratio(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert!(account_margin_ratio.is_negative());
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    // the short's free collateral is taken to cover the long's bad debt
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert!(position.margin < to_decimals(10u64));
+}
@@ -993,3 +993,204 @@ fn test_will_change_nothing_if_funding_rate_is_zero() {
     let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
     assert_eq!(insurance_balance, Uint128::from(5_000_000_000_000u128));
 }
+
+#[test]
+fn test_position_funding_payments_are_rebuilt_from_funding_history() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        owner,
+        engine,
+        vamm,
+        pricefeed,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(300u64),
+            to_decimals(2u64),
+            to_decimals(18),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(1200u64),
+            to_decimals(1u64),
+            to_decimals(5),
+            Some(to_decimals(26)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let price = Uint128::from(1_590_000_000u128);
+    let timestamp = 1_000_000_000;
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), price, timestamp)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    for _ in 0..2 {
+        router.update_block(|block| {
+            block.time = block.time.plus_seconds(NEXT_FUNDING_PERIOD_DELTA);
+            block.height += 1;
+        });
+
+        let msg = engine.pay_funding(vamm.addr().to_string()).unwrap();
+        router.execute(owner.clone(), msg).unwrap();
+    }
+
+    let history = vamm.funding_history(&router.wrap(), None, None).unwrap();
+    assert_eq!(history.len(), 2usize);
+    assert_eq!(
+        history[0].premium_fraction,
+        Integer::new_positive(10_000_000u128)
+    );
+
+    // alice pays 1% of 37.5 each period
+    let payments = engine
+        .get_position_funding_payments(&router.wrap(), vamm.addr().to_string(), 1, None, None)
+        .unwrap();
+    assert_eq!(payments.len(), 2usize);
+    assert_eq!(payments[0].funding_id, history[0].funding_id);
+    assert_eq!(payments[1].timestamp, history[1].timestamp);
+    assert_eq!(
+        payments[0].funding_payment,
+        Integer::new_positive(375_000_000u128)
+    );
+
+    // bob receives 1% of 187.5 each period
+    let payments = engine
+        .get_position_funding_payments(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            2,
+            Some(1u64),
+            None,
+        )
+        .unwrap();
+    assert_eq!(payments.len(), 1usize);
+    assert_eq!(payments[0].funding_id, 2u64);
+    assert_eq!(
+        payments[0].funding_payment,
+        Integer::new_negative(1_875_000_000u128)
+    );
+
+    // the sum matches the margin charged to alice
+    let alice_position = engine
+        .get_position_with_funding_payment(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(alice_position.margin, Uint128::from(299_250_000_000u128));
+}
+
+#[test]
+fn test_continuous_funding_is_settled_on_elapsed_time() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        owner,
+        insurance_fund,
+        usdc,
+        engine,
+        vamm,
+        pricefeed,
+        ..
+    } = new_simple_scenario();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(10u64), 1_000_000_000)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_funding_config(None, None, None, Some(true))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(60u64),
+            to_decimals(1u64),
+            to_decimals(18),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // the premium follows the amm twap, which only moves once alice's price has been in place
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(funding_index.premium_fraction, Integer::zero());
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let msg = vamm.update_funding_config(None, None, None, None).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(funding_index.cumulative_premium_fraction, Integer::zero());
+    assert!(funding_index.premium_fraction > Integer::zero());
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(NEXT_FUNDING_PERIOD_DELTA);
+        block.height += 1;
+    });
+
+    // no keeper is needed, the engine reads the index accrued to the block
+    let msg = engine.pay_funding(vamm.addr().to_string()).unwrap();
+    router.execute(owner.clone(), msg).unwrap_err();
+
+    let premium_fraction = engine
+        .get_latest_cumulative_premium_fraction(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(premium_fraction, funding_index.premium_fraction);
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    let funding_payment =
+        premium_fraction * position.size / Integer::new_positive(to_decimals(1u64));
+
+    let alice_position = engine
+        .get_position_with_funding_payment(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(
+        Integer::new_positive(alice_position.margin),
+        Integer::new_positive(position.margin) - funding_payment
+    );
+
+    // alice is the whole open interest, so the funding she paid goes to the insurance fund
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+
+    let msg = engine
+        .close_position(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(funding_index.cumulative_funding_payment, funding_payment);
+
+    assert_eq!(
+        usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap(),
+        insurance_balance + funding_payment.value
+    );
+}
@@ -1,6 +1,9 @@
 use cosmwasm_std::{StdError, Uint128};
 use cw20::Cw20ExecuteMsg;
-use margined_perp::margined_engine::{PnlCalcOption, Side};
+use margined_perp::{
+    margined_engine::{PnlCalcOption, Side},
+    margined_vamm::Direction,
+};
 use margined_utils::{
     cw_multi_test::Executor,
     testing::{to_decimals, SimpleScenario},
@@ -666,3 +669,58 @@ fn test_has_spread_no_toll() {
         .unwrap();
     assert_eq!(insurance_balance, Uint128::from(5060_000_000_000u128));
 }
+
+#[test]
+fn test_dynamic_spread_is_quoted_before_the_swap() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        usdc,
+        engine,
+        vamm,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(10u64), 1_000_000_000)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_spread_config(Some(true), Some(to_decimals(1u64)))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let quote = vamm
+        .calc_fee(&router.wrap(), to_decimals(20u64), Direction::AddToAmm)
+        .unwrap();
+    assert!(!quote.spread_fee.is_zero());
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(10u64),
+            to_decimals(2u64),
+            to_decimals(18),
+            Some(Uint128::zero()),
+            Uint128::zero(),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // the trader pays the quote, not the spread of the moved vamm
+    let insurance_balance = usdc
+        .balance(&router.wrap(), insurance_fund.addr().clone())
+        .unwrap();
+    assert_eq!(insurance_balance, to_decimals(5_000u64) + quote.spread_fee);
+
+    let moved_quote = vamm
+        .calc_fee(&router.wrap(), to_decimals(20u64), Direction::AddToAmm)
+        .unwrap();
+    assert!(moved_quote.spread_fee > quote.spread_fee);
+}
@@ -0,0 +1,121 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::{FeeRecipient, FeeRouting, Side};
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_default_fee_routing() {
+    let SimpleScenario {
+        router,
+        engine,
+        fee_pool,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let insurance_fund = vec![FeeRecipient {
+        recipient: insurance_fund.addr().to_string(),
+        weight: 10_000u64,
+    }];
+
+    let fee_routing = engine.fee_routing(&router.wrap()).unwrap();
+    assert_eq!(
+        fee_routing,
+        FeeRouting {
+            spread_fee: insurance_fund.clone(),
+            toll_fee: vec![FeeRecipient {
+                recipient: fee_pool.addr().to_string(),
+                weight: 10_000u64,
+            }],
+            liquidation_fee: insurance_fund,
+        }
+    );
+}
+
+#[test]
+fn test_update_fee_routing_splits_toll_fee() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        usdc,
+        fee_pool,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let mut fee_routing = engine.fee_routing(&router.wrap()).unwrap();
+    fee_routing.toll_fee = vec![
+        FeeRecipient {
+            recipient: fee_pool.addr().to_string(),
+            weight: 5_000u64,
+        },
+        FeeRecipient {
+            recipient: bob.to_string(),
+            weight: 4_000u64,
+        },
+    ];
+
+    let msg = engine.update_fee_routing(fee_routing.clone()).unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: fee weights must sum to 10000"
+    );
+
+    let mut empty_routing = fee_routing.clone();
+    empty_routing.spread_fee = vec![];
+    let msg = engine.update_fee_routing(empty_routing).unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: fee recipients cannot be empty"
+    );
+
+    fee_routing.toll_fee[1].weight = 5_000u64;
+    let msg = engine.update_fee_routing(fee_routing.clone()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    assert_eq!(engine.fee_routing(&router.wrap()).unwrap(), fee_routing);
+
+    // 10% fee
+    let msg = vamm.set_toll_ratio(Uint128::from(100_000_000u128)).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm.set_spread_ratio(Uint128::zero()).unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let bob_balance = usdc.balance(&router.wrap(), bob.clone()).unwrap();
+
+    // toll fee = 60 * 10 * 10% = 60, split evenly
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(60u64),
+            to_decimals(10u64),
+            to_decimals(18u64),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice, msg).unwrap();
+
+    let fee_pool_balance = usdc.balance(&router.wrap(), fee_pool.addr()).unwrap();
+    assert_eq!(fee_pool_balance, to_decimals(30u64));
+    let new_bob_balance = usdc.balance(&router.wrap(), bob).unwrap();
+    assert_eq!(new_bob_balance, bob_balance + to_decimals(30u64));
+}
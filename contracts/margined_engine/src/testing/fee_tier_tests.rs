@@ -0,0 +1,148 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::{FeeTier, Side};
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_update_fee_tiers() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        engine,
+        ..
+    } = new_simple_scenario();
+
+    let fee_tiers = vec![
+        FeeTier {
+            min_volume: to_decimals(1_000u64),
+            discount: Uint128::from(100_000_000u128),
+        },
+        FeeTier {
+            min_volume: to_decimals(500u64),
+            discount: Uint128::from(250_000_000u128),
+        },
+    ];
+
+    let msg = engine.update_fee_tiers(fee_tiers.clone()).unwrap();
+    let err = router.execute(alice, msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: fee tiers must be ordered by ascending volume"
+    );
+
+    let mut invalid_tiers = fee_tiers.clone();
+    invalid_tiers[1].min_volume = to_decimals(10_000u64);
+    invalid_tiers[1].discount = Uint128::from(50_000_000u128);
+    let msg = engine.update_fee_tiers(invalid_tiers).unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: fee tier discounts cannot decrease with volume"
+    );
+
+    let mut invalid_tiers = fee_tiers.clone();
+    invalid_tiers[0].discount = to_decimals(2u64);
+    let msg = engine.update_fee_tiers(invalid_tiers).unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Invalid ratio"
+    );
+
+    let mut fee_tiers = fee_tiers;
+    fee_tiers[1].min_volume = to_decimals(10_000u64);
+    let msg = engine.update_fee_tiers(fee_tiers.clone()).unwrap();
+    router.execute(owner, msg).unwrap();
+
+    assert_eq!(engine.fee_tiers(&router.wrap()).unwrap(), fee_tiers);
+}
+
+#[test]
+fn test_fee_tier_discount_follows_rolling_volume() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        usdc,
+        fee_pool,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .update_fee_tiers(vec![FeeTier {
+            min_volume: to_decimals(500u64),
+            discount: Uint128::from(500_000_000u128),
+        }])
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // 10% fee
+    let msg = vamm.set_toll_ratio(Uint128::from(100_000_000u128)).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm.set_spread_ratio(Uint128::zero()).unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let fee_tier = engine
+        .trader_fee_tier(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(fee_tier.volume, Uint128::zero());
+    assert_eq!(fee_tier.tier, None);
+    assert_eq!(fee_tier.discount, Uint128::zero());
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(60u64),
+            to_decimals(10u64),
+            to_decimals(18u64),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg.clone()).unwrap();
+
+    // the first trade pays the full fee of 60
+    let fee_pool_balance = usdc.balance(&router.wrap(), fee_pool.addr()).unwrap();
+    assert_eq!(fee_pool_balance, to_decimals(60u64));
+
+    let fee_tier = engine
+        .trader_fee_tier(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(fee_tier.volume, to_decimals(600u64));
+    assert_eq!(fee_tier.tier, Some(0u32));
+    assert_eq!(fee_tier.discount, Uint128::from(500_000_000u128));
+
+    // the second trade is discounted by half
+    router.execute(alice.clone(), msg).unwrap();
+
+    let fee_pool_balance = usdc.balance(&router.wrap(), fee_pool.addr()).unwrap();
+    assert_eq!(fee_pool_balance, to_decimals(90u64));
+
+    // volume falls out of the window after 30 days
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(30 * 86_400u64);
+        block.height += 1;
+    });
+
+    let fee_tier = engine
+        .trader_fee_tier(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(fee_tier.volume, Uint128::zero());
+    assert_eq!(fee_tier.tier, None);
+}
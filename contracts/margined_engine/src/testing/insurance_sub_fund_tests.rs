@@ -0,0 +1,305 @@
+use cosmwasm_std::Uint128;
+use cw20::Cw20ExecuteMsg;
+use margined_perp::{margined_engine::Side, margined_insurance_fund::SpilloverPolicy};
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_fund_vamm_moves_backstop_funds_into_the_sub_fund() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = insurance_fund
+        .fund_vamm(vamm.addr().to_string(), to_decimals(1_000u64))
+        .unwrap();
+    let err = router.execute(alice, msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    router.execute(owner.clone(), msg).unwrap();
+
+    let coverage = insurance_fund
+        .vamm_coverage(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(coverage.sub_fund, to_decimals(1_000u64));
+    assert_eq!(coverage.backstop, to_decimals(4_000u64));
+    assert_eq!(coverage.coverage, to_decimals(5_000u64));
+
+    let msg = insurance_fund
+        .fund_vamm(vamm.addr().to_string(), to_decimals(4_001u64))
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: insufficient backstop funds"
+    );
+
+    let msg = insurance_fund
+        .update_spillover_policy(SpilloverPolicy::Isolated)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let all_coverage = insurance_fund
+        .all_vamm_coverage(&router.wrap(), None)
+        .unwrap();
+    assert_eq!(all_coverage.vamm_coverage.len(), 1);
+    assert_eq!(all_coverage.vamm_coverage[0].vamm, vamm.addr());
+    assert_eq!(
+        all_coverage.vamm_coverage[0].coverage,
+        to_decimals(1_000u64)
+    );
+
+    // removing the vamm releases its sub-fund to the backstop
+    let msg = insurance_fund.remove_vamm(vamm.addr().to_string()).unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let coverage = insurance_fund
+        .vamm_coverage(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(coverage.sub_fund, Uint128::zero());
+    assert_eq!(coverage.backstop, to_decimals(5_000u64));
+}
+
+#[test]
+fn test_bad_debt_is_taken_from_the_sub_fund_first() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        bob,
+        engine,
+        vamm,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = insurance_fund
+        .fund_vamm(vamm.addr().to_string(), to_decimals(10u64))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(15u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund.shutdown_vamms().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+    let res = router.execute(bob, msg).unwrap();
+    let bad_debt = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "bad_debt")
+        .unwrap()
+        .value
+        .parse::<u128>()
+        .unwrap();
+    let bad_debt = Uint128::from(bad_debt);
+
+    // the sub-fund is used up before the backstop
+    let coverage = insurance_fund
+        .vamm_coverage(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(coverage.sub_fund, Uint128::zero());
+    assert_eq!(
+        coverage.backstop,
+        to_decimals(4_990u64) - (bad_debt - to_decimals(10u64))
+    );
+}
+
+#[test]
+fn test_isolated_vamm_records_bad_debt_beyond_its_sub_fund() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        bob,
+        engine,
+        vamm,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = insurance_fund
+        .fund_vamm(vamm.addr().to_string(), to_decimals(10u64))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund
+        .update_spillover_policy(SpilloverPolicy::Isolated)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(15u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund.shutdown_vamms().unwrap();
+    router.execute(owner, msg).unwrap();
+
+    // the bad debt is larger than the sub-fund, the insurance fund only pays the sub-fund
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+    let res = router.execute(bob, msg).unwrap();
+    let bad_debt = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "bad_debt")
+        .unwrap()
+        .value
+        .parse::<u128>()
+        .unwrap();
+    let bad_debt = Uint128::from(bad_debt);
+    assert!(bad_debt > to_decimals(10u64));
+
+    let coverage = insurance_fund
+        .vamm_coverage(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(coverage.sub_fund, Uint128::zero());
+    assert_eq!(coverage.backstop, to_decimals(4_990u64));
+
+    // the rest is recorded as bad debt in the engine
+    let state = engine.state(&router.wrap()).unwrap();
+    assert_eq!(state.uncovered_bad_debt, bad_debt - to_decimals(10u64));
+}
+
+#[test]
+fn test_isolated_vamm_shortfall_cuts_the_payout_instead_of_the_vault() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    // the vamm has no sub-fund so none of its bad debt is covered
+    let msg = insurance_fund
+        .update_spillover_policy(SpilloverPolicy::Isolated)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // margin the engine holds for the positions of other vamms
+    router
+        .execute_contract(
+            owner,
+            usdc.addr(),
+            &Cw20ExecuteMsg::Mint {
+                recipient: engine.addr().to_string(),
+                amount: to_decimals(1_000u64),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(150u64),
+            to_decimals(4u64),
+            to_decimals(18),
+            Some(to_decimals(9)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice, msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(500u64),
+            to_decimals(1u64),
+            to_decimals(8),
+            Some(to_decimals(26)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob, msg).unwrap();
+
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    let carol_balance = usdc.balance(&router.wrap(), carol.clone()).unwrap();
+
+    // alice's loss exceeds her margin so her liquidation fee is bad debt as well
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+    let read_attribute = |key: &str| {
+        let value = res
+            .events
+            .iter()
+            .flat_map(|event| event.attributes.iter())
+            .find(|attr| attr.key == key)
+            .unwrap()
+            .value
+            .parse::<u128>()
+            .unwrap();
+        Uint128::from(value)
+    };
+    let bad_debt = read_attribute("bad_debt");
+    let liquidation_fee = read_attribute("liquidation_fee");
+    assert!(bad_debt > liquidation_fee);
+
+    // the liquidator isn't paid out of the margin backing the other vamms
+    let new_engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(new_engine_balance, engine_balance);
+
+    let new_carol_balance = usdc.balance(&router.wrap(), carol).unwrap();
+    assert_eq!(new_carol_balance, carol_balance);
+
+    let state = engine.state(&router.wrap()).unwrap();
+    assert_eq!(state.uncovered_bad_debt, bad_debt - liquidation_fee);
+}
@@ -0,0 +1,260 @@
+use cosmwasm_std::Uint128;
+use cw20::Cw20ExecuteMsg;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_insurance_vault_shares_accrue_fees_and_absorb_bad_debt() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        engine,
+        vamm,
+        usdc,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    router
+        .execute_contract(
+            alice.clone(),
+            usdc.addr(),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: insurance_fund.addr().to_string(),
+                amount: to_decimals(1_000u64),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    let msg = insurance_fund.stake(Uint128::zero(), vec![]).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amount cannot be zero"
+    );
+
+    // the 5000 held before the first stake are owned by the protocol
+    let msg = insurance_fund.stake(to_decimals(1_000u64), vec![]).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let vault = insurance_fund.vault(&router.wrap()).unwrap();
+    assert_eq!(vault.protocol_shares, to_decimals(5_000u64));
+    assert_eq!(vault.total_shares, to_decimals(6_000u64));
+    assert_eq!(vault.total_assets, to_decimals(6_000u64));
+
+    let staker = insurance_fund
+        .staker(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(staker.shares, to_decimals(1_000u64));
+    assert_eq!(staker.value, to_decimals(1_000u64));
+
+    // fees routed to the insurance fund accrue to the shares
+    let msg = usdc
+        .call(Cw20ExecuteMsg::Transfer {
+            recipient: insurance_fund.addr().to_string(),
+            amount: to_decimals(600u64),
+        })
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let staker = insurance_fund
+        .staker(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(staker.value, to_decimals(1_100u64));
+
+    // bad debt taken from the insurance fund is absorbed pro rata
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(15u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund.shutdown_vamms().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+    router.execute(bob, msg).unwrap();
+
+    let vault = insurance_fund.vault(&router.wrap()).unwrap();
+    assert!(vault.total_assets < to_decimals(6_600u64));
+
+    let staker = insurance_fund
+        .staker(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(
+        staker.value,
+        staker
+            .shares
+            .multiply_ratio(vault.total_assets, vault.total_shares)
+    );
+    assert!(staker.value < to_decimals(1_100u64));
+}
+
+#[test]
+fn test_insurance_vault_unstake_goes_through_cooldown() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        usdc,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = insurance_fund.update_vault_config(Some(86_400u64)).unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+    router.execute(owner, msg).unwrap();
+
+    router
+        .execute_contract(
+            alice.clone(),
+            usdc.addr(),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: insurance_fund.addr().to_string(),
+                amount: to_decimals(1_000u64),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    let msg = insurance_fund.stake(to_decimals(1_000u64), vec![]).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = insurance_fund.unstake(to_decimals(2_000u64)).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: insufficient shares"
+    );
+
+    let msg = insurance_fund.unstake(to_decimals(600u64)).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // shares pending unstake can't be unstaked twice
+    let msg = insurance_fund.unstake(to_decimals(500u64)).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: insufficient shares"
+    );
+
+    let msg = insurance_fund.claim_unstake().unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unstake is still cooling down"
+    );
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(86_400u64);
+        block.height += 1;
+    });
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+
+    router.execute(alice.clone(), msg.clone()).unwrap();
+
+    let new_alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(new_alice_balance, alice_balance + to_decimals(600u64));
+
+    let staker = insurance_fund
+        .staker(&router.wrap(), alice.to_string())
+        .unwrap();
+    assert_eq!(staker.shares, to_decimals(400u64));
+    assert_eq!(staker.pending_shares, Uint128::zero());
+
+    let vault = insurance_fund.vault(&router.wrap()).unwrap();
+    assert_eq!(vault.total_shares, to_decimals(5_400u64));
+    assert_eq!(vault.total_assets, to_decimals(5_400u64));
+
+    let err = router.execute(alice, msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: no pending unstake"
+    );
+}
+
+#[test]
+fn test_insurance_vault_claim_unstake_reduces_sub_funds_pro_rata() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        usdc,
+        vamm,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    router
+        .execute_contract(
+            alice.clone(),
+            usdc.addr(),
+            &Cw20ExecuteMsg::IncreaseAllowance {
+                spender: insurance_fund.addr().to_string(),
+                amount: to_decimals(1_000u64),
+                expires: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    let msg = insurance_fund.stake(to_decimals(1_000u64), vec![]).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = insurance_fund
+        .fund_vamm(vamm.addr().to_string(), to_decimals(1_200u64))
+        .unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let msg = insurance_fund.unstake(to_decimals(600u64)).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(604_800u64);
+        block.height += 1;
+    });
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+
+    let msg = insurance_fund.claim_unstake().unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let new_alice_balance = usdc.balance(&router.wrap(), alice).unwrap();
+    assert_eq!(new_alice_balance, alice_balance + to_decimals(600u64));
+
+    // a tenth of the shares were burned so both the sub-fund and the backstop shrink by a tenth
+    let coverage = insurance_fund
+        .vamm_coverage(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert_eq!(coverage.sub_fund, to_decimals(1_080u64));
+    assert_eq!(coverage.backstop, to_decimals(4_320u64));
+}
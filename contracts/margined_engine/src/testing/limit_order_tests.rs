@@ -0,0 +1,424 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_place_and_cancel_limit_order() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    // alice bids at 9 while the price is 10
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(9u64),
+            to_decimals(15u64),
+            None,
+            None,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // the margin is escrowed by the engine
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(4980u64));
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, to_decimals(20u64));
+
+    let order = engine
+        .limit_order(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    assert_eq!(order.trader, alice);
+    assert_eq!(order.limit_price, to_decimals(9u64));
+
+    let tick = engine
+        .get_limit_order_tick(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(9u64),
+        )
+        .unwrap();
+    assert_eq!(tick.total_orders, 1u64);
+
+    // the order has not been crossed
+    let msg = engine
+        .execute_limit_orders(vamm.addr().to_string(), None)
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: No limit orders have been crossed"
+    );
+
+    let msg = engine
+        .cancel_limit_order(vamm.addr().to_string(), 1)
+        .unwrap();
+    let err = router.execute(bob.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Unauthorized"
+    );
+
+    let msg = engine
+        .cancel_limit_order(vamm.addr().to_string(), 1)
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(5000u64));
+
+    let ticks = engine
+        .get_limit_order_ticks(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            Side::Buy,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(ticks.ticks.is_empty());
+
+    let orders = engine
+        .get_limit_orders(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            Some(alice.to_string()),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(orders.is_empty());
+}
+
+#[test]
+fn test_execute_crossed_limit_orders() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(9u64),
+            to_decimals(15u64),
+            None,
+            None,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price down to 6.4
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(5u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    // anyone can fill crossed orders
+    let msg = engine
+        .execute_limit_orders(vamm.addr().to_string(), None)
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(position.trader, alice);
+    assert_eq!(position.side, Side::Buy);
+    assert_eq!(position.margin, to_decimals(20u64));
+    assert_eq!(position.take_profit, to_decimals(15u64));
+
+    // the escrowed margin now backs the position
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(4980u64));
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, to_decimals(40u64));
+
+    let orders = engine
+        .get_limit_orders(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    assert!(orders.is_empty());
+}
+
+#[test]
+fn test_expired_limit_order_is_refunded() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let expiry = router.block_info().time.seconds() + 100;
+
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(11u64),
+            to_decimals(8u64),
+            Some(to_decimals(12u64)),
+            Some(expiry),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // only the trader can cancel before expiry
+    let msg = engine
+        .cancel_limit_order(vamm.addr().to_string(), 1)
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Unauthorized"
+    );
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(200);
+        block.height += 1;
+    });
+
+    let msg = engine
+        .cancel_limit_order(vamm.addr().to_string(), 1)
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(5000u64));
+
+    let err = engine
+        .limit_order(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap_err();
+    assert!(err.to_string().contains("Limit order not found"));
+
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, Uint128::zero());
+}
+
+#[test]
+fn test_limit_order_is_not_filled_past_its_limit_price() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(9u64),
+            to_decimals(15u64),
+            None,
+            None,
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // bob pushes the price down to 6.4
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(5u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    // bob bids at 6.5, after alice's order is filled the swap would average 9
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            Uint128::from(6_500_000_000u128),
+            to_decimals(15u64),
+            None,
+            None,
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    // the batch fills alice's order and skips bob's
+    let msg = engine
+        .execute_limit_orders(vamm.addr().to_string(), None)
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+    assert!(res.events.iter().any(|event| event
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "action" && attr.value == "limit_order_fill_failure")));
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(position.trader, alice);
+
+    // bob's order stays in the book with its escrow
+    let order = engine
+        .limit_order(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(order.trader, bob);
+
+    let bob_balance = usdc.balance(&router.wrap(), bob.clone()).unwrap();
+    assert_eq!(bob_balance, to_decimals(4960u64));
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, to_decimals(60u64));
+
+    let tick = engine
+        .get_limit_order_tick(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            Side::Buy,
+            Uint128::from(6_500_000_000u128),
+        )
+        .unwrap();
+    assert_eq!(tick.total_orders, 1u64);
+}
+
+#[test]
+fn test_limit_order_breaching_the_open_interest_cap_is_skipped() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = vamm
+        .set_open_interest_notional_cap(to_decimals(400u64))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // the second order would take the open interest over the cap
+    for margin_amount in [20u64, 60u64, 10u64] {
+        let msg = engine
+            .place_limit_order(
+                vamm.addr().to_string(),
+                Side::Buy,
+                to_decimals(margin_amount),
+                to_decimals(5u64),
+                to_decimals(9u64),
+                to_decimals(15u64),
+                None,
+                None,
+                vec![],
+            )
+            .unwrap();
+        router.execute(alice.clone(), msg).unwrap();
+    }
+
+    // bob pushes the price down to 6.4
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(10u64),
+            to_decimals(5u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let msg = engine
+        .execute_limit_orders(vamm.addr().to_string(), None)
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+    assert!(res.events.iter().any(|event| event
+        .attributes
+        .iter()
+        .any(|attr| attr.key == "order_2" && attr.value.starts_with("failed"))));
+
+    // the orders either side of it are still filled
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(position.trader, alice);
+    assert_eq!(position.margin, to_decimals(20u64));
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 3)
+        .unwrap();
+    assert_eq!(position.trader, alice);
+    assert_eq!(position.margin, to_decimals(10u64));
+
+    // the breaching order stays in the book
+    let order = engine
+        .limit_order(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert_eq!(order.margin_amount, to_decimals(60u64));
+}
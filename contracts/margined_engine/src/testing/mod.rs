@@ -1,11 +1,19 @@
 mod bad_debt_tests;
+mod batch_tests;
+mod collateral_tests;
+mod cross_margin_tests;
 mod cw_token_add_remove_margin_tests;
 mod cw_token_liquidation_frontrun_hack_tests;
 mod cw_token_liquidation_tests;
 mod cw_token_pay_funding_tests;
 mod cw_token_position_fee_tests;
 mod fee_calculation_tests;
+mod fee_routing_tests;
+mod fee_tier_tests;
 mod fluctuation_tests;
+mod insurance_sub_fund_tests;
+mod insurance_vault_tests;
+mod limit_order_tests;
 mod margin_engine_tests;
 mod margin_ratio_tests;
 mod native_token_add_remove_margin_tests;
@@ -19,9 +27,12 @@ mod personal_position_tests;
 mod position_liquidation_tests;
 mod position_tests;
 mod position_upper_bound_tests;
+mod reduce_only_tests;
+mod settlement_tests;
 mod tests;
 mod whitelist_tests;
 mod tp_sl_test;
+mod vamm_adjustment_tests;
 
 use margined_utils::{
     create_entry_points_testing,
@@ -38,6 +49,17 @@ pub fn new_simple_scenario() -> SimpleScenario {
     )
 }
 
+pub fn new_simple_scenario_with_pricefeed() -> SimpleScenario {
+    SimpleScenario::new(
+        Box::new(create_entry_points_testing!(margined_fee_pool)),
+        Box::new(create_entry_points_testing!(cw20_base)),
+        Box::new(create_entry_points_testing!(crate).with_reply(crate::contract::reply)),
+        Box::new(create_entry_points_testing!(margined_vamm)),
+        Box::new(create_entry_points_testing!(margined_insurance_fund)),
+        Box::new(create_entry_points_testing!(margined_pricefeed)),
+    )
+}
+
 pub fn new_native_token_scenario() -> NativeTokenScenario {
     NativeTokenScenario::new(
         Box::new(create_entry_points_testing!(margined_fee_pool)),
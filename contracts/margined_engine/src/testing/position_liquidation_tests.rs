@@ -7,7 +7,7 @@ use margined_utils::{
     testing::{to_decimals, SimpleScenario},
 };
 
-use crate::testing::new_simple_scenario;
+use crate::testing::{new_simple_scenario, new_simple_scenario_with_pricefeed};
 
 #[test]
 fn test_liquidation_fee_100_percent() {
@@ -1228,3 +1228,174 @@ fn test_close_whole_partial_position_when_partial_liquidation_ratio_is_one() {
         err
     );
 }
+
+#[test]
+fn test_query_liquidatable_positions() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    // set maintenance ratio as 10% to allow liquidation
+    let msg = engine
+        .set_margin_ratios(Uint128::from(100_000_000u128))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(20u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(30u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // nothing can be liquidated yet
+    let res = engine
+        .get_liquidatable_positions(&router.wrap(), vamm.addr().to_string(), None, None, None)
+        .unwrap();
+    assert!(res.positions.is_empty());
+    assert_eq!(res.last_position_id, Some(2));
+
+    // bob pushes the price down leaving both of alice's positions underwater
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(50u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let res = engine
+        .get_liquidatable_positions(&router.wrap(), vamm.addr().to_string(), None, None, None)
+        .unwrap();
+    assert_eq!(res.positions.len(), 2);
+    assert_eq!(res.positions[0].position.position_id, 1);
+    assert!(res.positions[0].margin_ratio.is_negative());
+    assert_eq!(res.positions[1].position.position_id, 2);
+    assert!(res.positions[1].margin_ratio.is_negative());
+    assert_eq!(res.last_position_id, Some(3));
+
+    // the reported margin ratio matches the one used by liquidate
+    let margin_ratio = engine
+        .get_margin_ratio(&router.wrap(), vamm.addr().to_string(), 2)
+        .unwrap();
+    assert!(res.positions[1].margin_ratio >= margin_ratio);
+
+    let res = engine
+        .get_liquidatable_positions(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            Some(PnlCalcOption::SpotPrice),
+            Some(1),
+            Some(1),
+        )
+        .unwrap();
+    assert_eq!(res.positions.len(), 1);
+    assert_eq!(res.positions[0].position.position_id, 2);
+    assert_eq!(res.last_position_id, Some(2));
+}
+
+#[test]
+fn test_liquidate_refuses_stale_oracle_price() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        carol,
+        engine,
+        vamm,
+        pricefeed,
+        ..
+    } = new_simple_scenario_with_pricefeed();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            Some(to_decimals(9u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // the scenario's price is years old
+    let msg = pricefeed
+        .update_price_guard("ETH".to_string(), Some(3_600u64), None)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .liquidate(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let err = router.execute(carol.clone(), msg).unwrap_err();
+    assert!(err
+        .source()
+        .unwrap()
+        .to_string()
+        .contains("Price for key ETH is stale"));
+
+    let err = engine
+        .get_unrealized_pnl(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            1,
+            PnlCalcOption::Oracle,
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("Price for key ETH is stale"));
+
+    let msg = pricefeed
+        .append_price(
+            "ETH".to_string(),
+            to_decimals(10u64),
+            router.block_info().time.seconds(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let pnl = engine
+        .get_unrealized_pnl(
+            &router.wrap(),
+            vamm.addr().to_string(),
+            1,
+            PnlCalcOption::Oracle,
+        )
+        .unwrap();
+    assert_eq!(pnl.position_notional, to_decimals(200u64));
+}
@@ -0,0 +1,131 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_reduce_only_vamm_lets_traders_exit() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        owner,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(10u64),
+            to_decimals(2u64),
+            to_decimals(20u64),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // only the pauser can change the status
+    let msg = engine
+        .set_reduce_only(vamm.addr().to_string(), true)
+        .unwrap();
+    let err = router.execute(bob.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    router.execute(owner.clone(), msg).unwrap();
+
+    let is_reduce_only = engine
+        .is_reduce_only(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert!(is_reduce_only);
+
+    // adding risk is rejected
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(10u64),
+            to_decimals(2u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    let err = router.execute(bob.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: vAMM is reduce only"
+    );
+
+    let msg = engine
+        .place_limit_order(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(10u64),
+            to_decimals(2u64),
+            to_decimals(9u64),
+            to_decimals(15u64),
+            None,
+            None,
+            vec![],
+        )
+        .unwrap();
+    let err = router.execute(bob.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: vAMM is reduce only"
+    );
+
+    // managing margin and exiting keep working
+    let msg = engine
+        .deposit_margin(vamm.addr().to_string(), 1, to_decimals(1u64), vec![])
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .withdraw_margin(vamm.addr().to_string(), 1, to_decimals(1u64))
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .close_position(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // lifting the status reopens the market
+    let msg = engine
+        .set_reduce_only(vamm.addr().to_string(), false)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let is_reduce_only = engine
+        .is_reduce_only(&router.wrap(), vamm.addr().to_string())
+        .unwrap();
+    assert!(!is_reduce_only);
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(10u64),
+            to_decimals(2u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+}
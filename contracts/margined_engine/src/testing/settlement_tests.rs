@@ -0,0 +1,160 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_settle_position_pays_out_at_the_settlement_price() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        engine,
+        vamm,
+        usdc,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(60u64),
+            to_decimals(2u64),
+            to_decimals(20u64),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: vAMM has not been shut down"
+    );
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(12u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund.shutdown_vamms().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let settlement_price = vamm.settlement_price(&router.wrap()).unwrap();
+    assert_eq!(settlement_price, Some(to_decimals(12u64)));
+
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+
+    let position = engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap();
+    let settled_notional = position
+        .size
+        .value
+        .checked_mul(to_decimals(12u64))
+        .unwrap()
+        .checked_div(to_decimals(1u64))
+        .unwrap();
+    let pnl = settled_notional - position.notional;
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+
+    // anyone can settle the position, the payout goes to the trader
+    router.execute(bob, msg).unwrap();
+
+    // margin plus pnl is paid out, the profit is covered by the insurance fund
+    let new_alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(new_alice_balance, alice_balance + position.margin + pnl);
+    let new_insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(new_insurance_balance, insurance_balance - pnl);
+
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, Uint128::zero());
+
+    let state = engine.state(&router.wrap()).unwrap();
+    assert_eq!(state.open_interest_notional, Uint128::zero());
+
+    engine
+        .position(&router.wrap(), vamm.addr().to_string(), 1)
+        .unwrap_err();
+
+    // the vamm reserves are untouched
+    let vamm_state = vamm.state(&router.wrap()).unwrap();
+    assert!(!vamm_state.open);
+    assert!(!vamm_state.total_position_size.is_zero());
+}
+
+#[test]
+fn test_settle_position_bad_debt_is_covered_by_insurance_fund() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        bob,
+        engine,
+        vamm,
+        usdc,
+        pricefeed,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Sell,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(5u64),
+            Some(to_decimals(20u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    // 9.09 ETH short from 100 settled at 15 loses more than the margin
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(15u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = insurance_fund.shutdown_vamms().unwrap();
+    router.execute(owner, msg).unwrap();
+
+    let bob_balance = usdc.balance(&router.wrap(), bob.clone()).unwrap();
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+
+    let msg = engine.settle_position(vamm.addr().to_string(), 1).unwrap();
+    let res = router.execute(bob.clone(), msg).unwrap();
+    let bad_debt = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "bad_debt")
+        .unwrap()
+        .value
+        .parse::<u128>()
+        .unwrap();
+    let bad_debt = Uint128::from(bad_debt);
+    assert!(!bad_debt.is_zero());
+
+    let new_bob_balance = usdc.balance(&router.wrap(), bob).unwrap();
+    assert_eq!(new_bob_balance, bob_balance);
+    let new_insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(new_insurance_balance, insurance_balance - bad_debt);
+    let new_engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(new_engine_balance, engine_balance + bad_debt);
+}
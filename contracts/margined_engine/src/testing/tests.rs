@@ -44,6 +44,8 @@ fn test_instantiation() {
             partial_liquidation_ratio: Uint128::zero(),
             tp_sl_spread: Uint128::from(50_000u128),
             liquidation_fee: Uint128::from(100u128),
+            pricefeed: None,
+            execution_fee: Uint128::zero(),
         }
     );
 }
@@ -74,6 +76,8 @@ fn test_update_config() {
         partial_liquidation_ratio: None,
         tp_sl_spread: None,
         liquidation_fee: None,
+        pricefeed: None,
+        execution_fee: None,
     };
 
     let info = mock_info(OWNER, &[]);
@@ -96,6 +100,8 @@ fn test_update_config() {
             partial_liquidation_ratio: Uint128::zero(),
             tp_sl_spread: Uint128::from(50_000u128),
             liquidation_fee: Uint128::from(100u128),
+            pricefeed: None,
+            execution_fee: Uint128::zero(),
         }
     );
 
@@ -109,6 +115,8 @@ fn test_update_config() {
         partial_liquidation_ratio: None,
         tp_sl_spread: None,
         liquidation_fee: None,
+        pricefeed: None,
+        execution_fee: None,
     };
 
     let info = mock_info(OWNER, &[]);
@@ -125,6 +133,8 @@ fn test_update_config() {
         partial_liquidation_ratio: None,
         tp_sl_spread: None,
         liquidation_fee: None,
+        pricefeed: None,
+        execution_fee: None,
     };
 
     let info = mock_info(OWNER, &[]);
@@ -181,4 +181,185 @@ fn test_trigger_tp_sl() {
         .unwrap();
     let ret = router.execute(alice.clone(), msg).unwrap();
     println!("[LOG] trigger take profit event: {:?}", ret);
-}
\ No newline at end of file
+}
+#[test]
+fn test_trigger_tp_sl_pays_execution_fee() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .set_execution_fee(Uint128::from(10_000_000u128)) // 0.01
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // price moves to 15.625, above the take profit
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let msg = engine
+        .trigger_tp_sl(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    let res = router.execute(carol.clone(), msg).unwrap();
+
+    // 250 * 0.01 = 2.5
+    let execution_fee = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "execution_fee")
+        .unwrap();
+    assert_eq!(execution_fee.value, "2500000000");
+
+    let carol_balance = usdc.balance(&router.wrap(), carol.clone()).unwrap();
+    assert_eq!(carol_balance, Uint128::from(2_500_000_000u128));
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, Uint128::from(4_997_500_000_000u128));
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, Uint128::zero());
+}
+
+#[test]
+fn test_execution_fee_is_capped_at_remaining_margin() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        carol,
+        engine,
+        vamm,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .set_execution_fee(Uint128::from(500_000_000u128)) // 0.5
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // 250 * 0.5 = 125 is more than the 25 margin left
+    let msg = engine
+        .trigger_tp_sl(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let carol_balance = usdc.balance(&router.wrap(), carol.clone()).unwrap();
+    assert_eq!(carol_balance, to_decimals(25u64));
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(alice_balance, to_decimals(4975u64));
+
+    let state = engine.state(&router.wrap()).unwrap();
+    assert_eq!(state.bad_debt, Uint128::zero());
+}
+
+#[test]
+fn test_query_triggerable_positions() {
+    let SimpleScenario {
+        mut router,
+        alice,
+        bob,
+        carol,
+        engine,
+        vamm,
+        ..
+    } = new_simple_scenario();
+
+    // price moves to 15.625, above alice's take profit
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(25u64),
+            to_decimals(10u64),
+            to_decimals(15u64),
+            None,
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // price moves to 18.225
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(20u64),
+            to_decimals(5u64),
+            to_decimals(30u64),
+            Some(to_decimals(16u64)),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let positions = engine
+        .get_triggerable_positions(&router.wrap(), vamm.addr().to_string(), Side::Buy, None)
+        .unwrap();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].position_id, 1);
+
+    let positions = engine
+        .get_triggerable_positions(&router.wrap(), vamm.addr().to_string(), Side::Sell, None)
+        .unwrap();
+    assert!(positions.is_empty());
+
+    // the index follows updates of the take profit
+    let msg = engine
+        .update_tp_sl(vamm.addr().to_string(), 2, Some(to_decimals(18u64)), None)
+        .unwrap();
+    router.execute(bob.clone(), msg).unwrap();
+
+    let positions = engine
+        .get_triggerable_positions(&router.wrap(), vamm.addr().to_string(), Side::Buy, None)
+        .unwrap();
+    assert_eq!(positions.len(), 2);
+    assert_eq!(positions[0].position_id, 1);
+    assert_eq!(positions[1].position_id, 2);
+
+    // closing alice's position drops the price to 11.3, below bob's stop loss
+    let msg = engine
+        .trigger_tp_sl(vamm.addr().to_string(), 1, to_decimals(0u64))
+        .unwrap();
+    router.execute(carol.clone(), msg).unwrap();
+
+    let positions = engine
+        .get_triggerable_positions(&router.wrap(), vamm.addr().to_string(), Side::Buy, None)
+        .unwrap();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(positions[0].position_id, 2);
+    assert_eq!(positions[0].stop_loss, Some(to_decimals(16u64)));
+}
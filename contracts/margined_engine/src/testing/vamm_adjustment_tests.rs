@@ -0,0 +1,78 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_engine::Side;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{adjustment_cost, to_decimals, SimpleScenario},
+};
+
+use crate::testing::new_simple_scenario;
+
+#[test]
+fn test_vamm_adjustments_are_settled_with_insurance_fund() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        engine,
+        vamm,
+        usdc,
+        insurance_fund,
+        ..
+    } = new_simple_scenario();
+
+    let msg = engine
+        .open_position(
+            vamm.addr().to_string(),
+            Side::Buy,
+            to_decimals(60u64),
+            to_decimals(2u64),
+            to_decimals(20u64),
+            Some(Uint128::zero()),
+            to_decimals(0u64),
+            vec![],
+        )
+        .unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    // only the owner can adjust the vamm
+    let msg = engine
+        .repeg_vamm(vamm.addr().to_string(), to_decimals(10u64))
+        .unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+
+    // pulling the price back down, the long loses and the insurance fund is paid
+    let msg = engine
+        .repeg_vamm(vamm.addr().to_string(), to_decimals(10u64))
+        .unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    let cost = adjustment_cost(&res);
+    assert!(cost.is_negative());
+
+    let spot_price = vamm.spot_price(&router.wrap()).unwrap();
+    assert_eq!(spot_price, to_decimals(10u64));
+
+    let new_engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(new_engine_balance, engine_balance - cost.value);
+    let new_insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(new_insurance_balance, insurance_balance + cost.value);
+
+    // a deeper curve makes the long gain and the insurance fund pays
+    let msg = engine
+        .adjust_vamm_k(vamm.addr().to_string(), to_decimals(2u64))
+        .unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    let cost = adjustment_cost(&res);
+    assert!(cost.is_positive() && !cost.is_zero());
+
+    let engine_balance = usdc.balance(&router.wrap(), engine.addr()).unwrap();
+    assert_eq!(engine_balance, new_engine_balance + cost.value);
+    let insurance_balance = usdc.balance(&router.wrap(), insurance_fund.addr()).unwrap();
+    assert_eq!(insurance_balance, new_insurance_balance - cost.value);
+}
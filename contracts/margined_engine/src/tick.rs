@@ -1,10 +1,12 @@
 use cosmwasm_std::{Deps, Order as OrderBy, StdResult, Uint128};
 use cosmwasm_storage::ReadonlyBucket;
-use margined_perp::margined_engine::{Side, TickResponse, TicksResponse};
+use margined_perp::margined_engine::{
+    LimitOrderTickResponse, LimitOrderTicksResponse, Side, TickResponse, TicksResponse,
+};
 use std::convert::{TryFrom, TryInto};
 
 use crate::{
-    state::{DEFAULT_LIMIT, MAX_LIMIT, PREFIX_TICK},
+    state::{DEFAULT_LIMIT, MAX_LIMIT, PREFIX_LIMIT_ORDER_TICK, PREFIX_TICK},
     utils::{calc_range_start, keccak_256},
 };
 
@@ -63,3 +65,63 @@ pub fn query_tick(
         entry_price,
     })
 }
+
+pub fn query_limit_order_ticks(
+    deps: Deps,
+    vamm: String,
+    side: Side,
+    start_after: Option<Uint128>,
+    limit: Option<u32>,
+    order_by: Option<i32>,
+) -> StdResult<LimitOrderTicksResponse> {
+    let order_by = order_by.map_or(None, |val| OrderBy::try_from(val).ok());
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+
+    let order_bucket: ReadonlyBucket<u64> = ReadonlyBucket::multilevel(
+        deps.storage,
+        &[PREFIX_LIMIT_ORDER_TICK, &vamm_key, side.as_bytes()],
+    );
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|id| id.to_be_bytes().to_vec());
+    let (start, end, order_by) = match order_by {
+        Some(OrderBy::Ascending) => (calc_range_start(start_after), None, OrderBy::Ascending),
+        _ => (None, start_after, OrderBy::Descending),
+    };
+
+    let ticks = order_bucket
+        .range(start.as_deref(), end.as_deref(), order_by)
+        .take(limit)
+        .map(|item| {
+            let (k, total_orders) = item?;
+            let limit_price = Uint128::from(u128::from_be_bytes(k.try_into().unwrap()));
+            Ok(LimitOrderTickResponse {
+                limit_price,
+                total_orders,
+            })
+        })
+        .collect::<StdResult<Vec<LimitOrderTickResponse>>>()?;
+
+    Ok(LimitOrderTicksResponse { ticks })
+}
+
+pub fn query_limit_order_tick(
+    deps: Deps,
+    vamm: String,
+    side: Side,
+    limit_price: Uint128,
+) -> StdResult<LimitOrderTickResponse> {
+    let price_key = limit_price.to_be_bytes();
+    let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+
+    let total_orders = ReadonlyBucket::<u64>::multilevel(
+        deps.storage,
+        &[PREFIX_LIMIT_ORDER_TICK, &vamm_key, side.as_bytes()],
+    )
+    .load(&price_key)?;
+
+    Ok(LimitOrderTickResponse {
+        total_orders,
+        limit_price,
+    })
+}
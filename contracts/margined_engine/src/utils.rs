@@ -2,7 +2,9 @@ use cosmwasm_std::{
     Addr, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, SubMsg,
     SubMsgResponse, Uint128,
 };
-use margined_utils::contracts::helpers::{InsuranceFundController, VammController};
+use margined_utils::contracts::helpers::{
+    InsuranceFundController, PricefeedController, VammController,
+};
 use sha3::{Digest, Sha3_256};
 
 use std::str::FromStr;
@@ -13,15 +15,20 @@ use margined_common::{
     messages::{read_event, read_response},
 };
 use margined_perp::margined_engine::{
-    PnlCalcOption, Position, PositionUnrealizedPnlResponse, RemainMarginResponse, Side,
+    FeeRecipient, FeeRouting, MarginMode, PnlCalcOption, Position, PositionUnrealizedPnlResponse,
+    RemainMarginResponse, Side, TraderFeeTierResponse,
 };
 use margined_perp::margined_vamm::Direction;
 
 use crate::{
-    contract::{PAUSER, WHITELIST},
-    messages::execute_insurance_fund_withdrawal,
-    query::query_cumulative_premium_fraction,
-    state::{read_config, read_position, read_state, read_vamm_map, store_state, State},
+    contract::{FEE_ROUTING_TOTAL_WEIGHT, PAUSER, WHITELIST},
+    messages::{execute_insurance_fund_withdrawal, execute_transfer_collateral},
+    query::{query_cumulative_premium_fraction, query_free_collateral},
+    state::{
+        read_collateral, read_config, read_fee_routing, read_fee_tiers, read_position,
+        read_reduce_only, read_state, read_trader_positions, read_trader_volume, read_vamm_map,
+        store_position, store_reduce_only, store_state, Config, State,
+    },
 };
 
 pub fn keccak_256(input: &[u8]) -> Vec<u8> {
@@ -57,25 +64,114 @@ pub fn get_asset(info: MessageInfo, eligible_collateral: AssetInfo) -> Asset {
 
 pub fn realize_bad_debt(
     deps: Deps,
+    vamm: &Addr,
     bad_debt: Uint128,
     messages: &mut Vec<SubMsg>,
     state: &mut State,
 ) -> StdResult<Uint128> {
+    let bad_debt_delta = prepay_bad_debt(bad_debt, state)?;
+
+    if !bad_debt_delta.is_zero() {
+        messages.push(execute_insurance_fund_withdrawal(
+            deps,
+            vamm,
+            bad_debt_delta,
+        )?);
+    }
+
+    Ok(bad_debt_delta)
+}
+
+// returns the part of the bad debt the vault needs from the insurance fund to realize it
+pub fn prepay_bad_debt(bad_debt: Uint128, state: &mut State) -> StdResult<Uint128> {
     if state.prepaid_bad_debt > bad_debt {
         // no need to move extra tokens because vault already prepay bad debt, only need to update the numbers
         state.prepaid_bad_debt = state.prepaid_bad_debt.checked_sub(bad_debt)?;
-    } else {
-        // in order to realize all the bad debt vault need extra tokens from insuranceFund
-        let bad_debt_delta = bad_debt.checked_sub(state.prepaid_bad_debt)?;
 
-        messages.push(execute_insurance_fund_withdrawal(deps, bad_debt_delta)?);
+        return Ok(Uint128::zero());
+    }
 
-        state.prepaid_bad_debt = Uint128::zero();
+    // in order to realize all the bad debt vault need extra tokens from insuranceFund
+    let bad_debt_delta = bad_debt.checked_sub(state.prepaid_bad_debt)?;
+    state.prepaid_bad_debt = Uint128::zero();
+
+    Ok(bad_debt_delta)
+}
 
-        return Ok(bad_debt_delta);
+// without a fee routing the spread fee and liquidation penalty go to the insurance fund and the
+// toll fee goes to the fee pool
+pub fn get_fee_routing(storage: &dyn Storage) -> StdResult<FeeRouting> {
+    if let Some(fee_routing) = read_fee_routing(storage)? {
+        return Ok(fee_routing);
+    }
+
+    let config = read_config(storage)?;
+
+    let to_recipient = |recipient: &Addr| {
+        vec![FeeRecipient {
+            recipient: recipient.to_string(),
+            weight: FEE_ROUTING_TOTAL_WEIGHT,
+        }]
     };
 
-    Ok(Uint128::zero())
+    let insurance_fund = config
+        .insurance_fund
+        .as_ref()
+        .map(to_recipient)
+        .unwrap_or_default();
+
+    Ok(FeeRouting {
+        spread_fee: insurance_fund.clone(),
+        toll_fee: to_recipient(&config.fee_pool),
+        liquidation_fee: insurance_fund,
+    })
+}
+
+/// Returns the rolling volume of the trader and the highest fee tier it reaches
+pub fn get_trader_fee_tier(
+    storage: &dyn Storage,
+    trader: &Addr,
+    now: u64,
+) -> StdResult<TraderFeeTierResponse> {
+    let volume = read_trader_volume(storage, trader, now)?;
+
+    let mut response = TraderFeeTierResponse {
+        volume,
+        tier: None,
+        discount: Uint128::zero(),
+    };
+    for (index, fee_tier) in read_fee_tiers(storage)?.into_iter().enumerate() {
+        if volume < fee_tier.min_volume {
+            break;
+        }
+
+        response.tier = Some(index as u32);
+        response.discount = fee_tier.discount;
+    }
+
+    Ok(response)
+}
+
+// reduces the spread and toll fees by the discount of the trader's fee tier
+pub fn apply_fee_discount(
+    storage: &dyn Storage,
+    trader: &Addr,
+    now: u64,
+    spread_fee: Uint128,
+    toll_fee: Uint128,
+) -> StdResult<(Uint128, Uint128)> {
+    let TraderFeeTierResponse { discount, .. } = get_trader_fee_tier(storage, trader, now)?;
+    if discount.is_zero() {
+        return Ok((spread_fee, toll_fee));
+    }
+
+    let config = read_config(storage)?;
+    let fee_ratio = config.decimals.checked_sub(discount)?;
+
+    Ok((
+        spread_fee.multiply_ratio(fee_ratio, config.decimals),
+        toll_fee.multiply_ratio(fee_ratio, config.decimals),
+    ))
 }
 
 // this blocks trades if open interest is too high, required during the bootstrapping of the project
@@ -154,16 +250,229 @@ pub fn get_margin_ratio_calc_option(
         unrealized_pnl,
     } = get_position_notional_unrealized_pnl(deps, &position, calc_option)?;
 
+    let collateral_value = calc_collateral_value(deps, &position)?;
     let remain_margin = calc_remain_margin_with_funding_payment(deps, position, unrealized_pnl)?;
 
     let margin_ratio = ((Integer::new_positive(remain_margin.margin)
-        - Integer::new_positive(remain_margin.bad_debt))
+        - Integer::new_positive(remain_margin.bad_debt)
+        + Integer::new_positive(collateral_value))
         * Integer::new_positive(config.decimals))
         / Integer::new_positive(position_notional);
 
     Ok(margin_ratio)
 }
 
+// returns the cross margin positions of a trader across all registered vamms
+pub fn get_cross_positions(deps: Deps, trader: &Addr) -> StdResult<Vec<Position>> {
+    let config = read_config(deps.storage)?;
+
+    let vamms = match config.insurance_fund {
+        Some(insurance_fund) => {
+            let insurance_controller = InsuranceFundController(insurance_fund);
+            insurance_controller
+                .all_vamms(&deps.querier, None)?
+                .vamm_list
+        }
+        None => return Err(StdError::generic_err("insurance fund is not registered")),
+    };
+
+    let mut positions: Vec<Position> = vec![];
+    for vamm in vamms.iter() {
+        let vamm_key = keccak_256(&[vamm.as_bytes()].concat());
+        positions.extend(
+            read_trader_positions(deps.storage, &vamm_key, trader)?
+                .into_iter()
+                .filter(|position| position.margin_mode == MarginMode::Cross),
+        );
+    }
+
+    Ok(positions)
+}
+
+// the loss of a closing cross margin position is taken from the free collateral of the trader's
+// other cross margin positions, returns the loss that could not be covered
+pub fn cover_cross_margin_loss(
+    deps: DepsMut,
+    position: &Position,
+    loss: Uint128,
+) -> StdResult<Uint128> {
+    let mut remaining_loss = loss;
+    for mut cross_position in get_cross_positions(deps.as_ref(), &position.trader)? {
+        if remaining_loss.is_zero() {
+            break;
+        }
+
+        if cross_position.vamm == position.vamm
+            && cross_position.position_id == position.position_id
+        {
+            continue;
+        }
+
+        let free_collateral = query_free_collateral(
+            deps.as_ref(),
+            cross_position.vamm.to_string(),
+            cross_position.position_id,
+        )?;
+        if free_collateral.is_negative() {
+            continue;
+        }
+
+        let amount = free_collateral
+            .value
+            .min(cross_position.margin)
+            .min(remaining_loss);
+
+        cross_position.margin = cross_position.margin.checked_sub(amount)?;
+        remaining_loss = remaining_loss.checked_sub(amount)?;
+
+        let vamm_key = keccak_256(&[cross_position.vamm.as_bytes()].concat());
+        store_position(deps.storage, &vamm_key, &cross_position, false)?;
+    }
+
+    Ok(remaining_loss)
+}
+
+pub fn get_account_margin_ratio_calc_option(
+    deps: Deps,
+    trader: String,
+    calc_option: PnlCalcOption,
+) -> StdResult<Integer> {
+    let trader = deps.api.addr_validate(&trader)?;
+
+    let mut account: Vec<(Position, PositionUnrealizedPnlResponse)> = vec![];
+    for position in get_cross_positions(deps, &trader)? {
+        let pnl = get_position_notional_unrealized_pnl(deps, &position, calc_option.clone())?;
+        account.push((position, pnl));
+    }
+
+    calc_account_margin_ratio(deps, account)
+}
+
+// margin ratio of an account is the sum of the remaining margins over the sum of the notionals
+pub fn calc_account_margin_ratio(
+    deps: Deps,
+    account: Vec<(Position, PositionUnrealizedPnlResponse)>,
+) -> StdResult<Integer> {
+    let config = read_config(deps.storage)?;
+
+    let mut account_value = Integer::zero();
+    let mut account_notional = Uint128::zero();
+
+    for (position, pnl) in account {
+        if position.size.is_zero() {
+            continue;
+        }
+
+        let collateral_value = calc_collateral_value(deps, &position)?;
+        let remain_margin =
+            calc_remain_margin_with_funding_payment(deps, position, pnl.unrealized_pnl)?;
+
+        account_value = account_value + Integer::new_positive(remain_margin.margin)
+            - Integer::new_positive(remain_margin.bad_debt)
+            + Integer::new_positive(collateral_value);
+        account_notional = account_notional.checked_add(pnl.position_notional)?;
+    }
+
+    if account_notional.is_zero() {
+        return Ok(Integer::zero());
+    }
+
+    Ok(account_value * Integer::new_positive(config.decimals)
+        / Integer::new_positive(account_notional))
+}
+
+// values the collateral of a position in the quote asset, where
+// collateral_value = exchange_rate * amount * risk_factor
+pub fn calc_collateral_value(deps: Deps, position: &Position) -> StdResult<Uint128> {
+    let asset = match &position.collateral {
+        Some(asset) => asset,
+        None => return Ok(Uint128::zero()),
+    };
+
+    // collateral that has been deregistered is no longer valued
+    let collateral = match read_collateral(deps.storage, &asset.info) {
+        Ok(collateral) => collateral,
+        Err(_) => return Ok(Uint128::zero()),
+    };
+
+    let config = read_config(deps.storage)?;
+    let pricefeed = match config.pricefeed {
+        Some(pricefeed) => PricefeedController(pricefeed),
+        None => return Err(StdError::generic_err("pricefeed is not registered")),
+    };
+
+    let exchange_rate = pricefeed.get_price(&deps.querier, collateral.price_key)?;
+
+    let value = asset
+        .amount
+        .checked_mul(exchange_rate)?
+        .checked_div(collateral.decimals)?
+        .checked_mul(collateral.risk_factor)?
+        .checked_div(config.decimals)?;
+
+    Ok(value)
+}
+
+// converts the collateral of a position into the quote asset through the insurance fund, which
+// takes the collateral worth `amount` at its haircut value and pays that value to the engine.
+// returns the converted amount and the collateral that is left to the trader
+pub fn convert_collateral(
+    deps: Deps,
+    position: &Position,
+    amount: Uint128,
+    messages: &mut Vec<SubMsg>,
+) -> StdResult<(Uint128, Option<Asset>)> {
+    let asset = match &position.collateral {
+        Some(asset) => asset.clone(),
+        None => return Ok((Uint128::zero(), None)),
+    };
+
+    let collateral_value = calc_collateral_value(deps, position)?;
+    if amount.is_zero() || collateral_value.is_zero() {
+        return Ok((Uint128::zero(), Some(asset)));
+    }
+
+    let converted = amount.min(collateral_value);
+
+    // rounded up in favour of the insurance fund
+    let mut seized_amount = asset.amount.multiply_ratio(converted, collateral_value);
+    if asset.amount.full_mul(converted) > seized_amount.full_mul(collateral_value) {
+        seized_amount = seized_amount.checked_add(Uint128::one())?;
+    }
+
+    let config = read_config(deps.storage)?;
+    let insurance_fund = match config.insurance_fund {
+        Some(insurance_fund) => insurance_fund,
+        None => return Err(StdError::generic_err("insurance fund is not registered")),
+    };
+
+    messages.push(execute_transfer_collateral(
+        &Asset {
+            info: asset.info.clone(),
+            amount: seized_amount,
+        },
+        &insurance_fund,
+        None,
+    )?);
+    messages.push(execute_insurance_fund_withdrawal(
+        deps,
+        &position.vamm,
+        converted,
+    )?);
+
+    let remaining_amount = asset.amount.checked_sub(seized_amount)?;
+    let remaining = if remaining_amount.is_zero() {
+        None
+    } else {
+        Some(Asset {
+            info: asset.info,
+            amount: remaining_amount,
+        })
+    };
+
+    Ok((converted, remaining))
+}
+
 pub fn get_position_notional_unrealized_pnl(
     deps: Deps,
     position: &Position,
@@ -222,6 +531,7 @@ pub fn calc_remain_margin_with_funding_payment(
     // calculate the funding payment
     let latest_premium_fraction =
         query_cumulative_premium_fraction(deps, position.vamm.to_string())?;
+    let latest_funding_id = read_vamm_map(deps.storage, &position.vamm)?.latest_funding_id;
     let config = read_config(deps.storage)?;
     let funding_payment = (latest_premium_fraction - position.last_updated_premium_fraction)
         * position.size
@@ -245,6 +555,7 @@ pub fn calc_remain_margin_with_funding_payment(
         margin: remaining_margin.value,
         bad_debt: bad_debt.value,
         latest_premium_fraction,
+        latest_funding_id,
     })
 }
 
@@ -308,6 +619,28 @@ pub fn set_pause(deps: DepsMut, _env: Env, info: MessageInfo, pause: bool) -> St
     Ok(Response::default().add_attribute("action", "set_pause"))
 }
 
+pub fn set_reduce_only(
+    deps: DepsMut,
+    info: MessageInfo,
+    vamm: String,
+    reduce_only: bool,
+) -> StdResult<Response> {
+    let vamm = deps.api.addr_validate(&vamm)?;
+
+    // check permission
+    if !PAUSER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    store_reduce_only(deps.storage, &vamm, reduce_only)?;
+
+    Ok(Response::default().add_attributes(vec![
+        ("action", "set_reduce_only"),
+        ("vamm", vamm.as_ref()),
+        ("reduce_only", &reduce_only.to_string()),
+    ]))
+}
+
 pub fn require_vamm(deps: Deps, insurance: &Option<Addr>, vamm: &Addr) -> StdResult<Response> {
     let insurance = match insurance {
         Some(arr) => arr,
@@ -339,6 +672,52 @@ pub fn require_bad_debt(bad_debt: Uint128) -> StdResult<Response> {
     Ok(Response::new())
 }
 
+// Returns true if the spot price is at, or within the spread of, the take profit of the position
+pub fn take_profit_reached(
+    position: &Position,
+    spot_price: Uint128,
+    config: &Config,
+) -> StdResult<bool> {
+    let tp_spread = position
+        .take_profit
+        .checked_mul(config.tp_sl_spread)?
+        .checked_div(config.decimals)?;
+
+    Ok(match position.side {
+        Side::Buy => {
+            spot_price > position.take_profit
+                || position.take_profit.abs_diff(spot_price) <= tp_spread
+        }
+        Side::Sell => {
+            position.take_profit > spot_price
+                || spot_price.abs_diff(position.take_profit) <= tp_spread
+        }
+    })
+}
+
+// Returns true if the spot price is at, or within the spread of, the stop loss of the position
+pub fn stop_loss_reached(
+    position: &Position,
+    spot_price: Uint128,
+    config: &Config,
+) -> StdResult<bool> {
+    let stop_loss = position.stop_loss.unwrap_or_default();
+    let sl_spread = stop_loss
+        .checked_mul(config.tp_sl_spread)?
+        .checked_div(config.decimals)?;
+
+    Ok(match position.side {
+        Side::Buy => {
+            stop_loss > spot_price
+                || stop_loss > Uint128::zero() && spot_price.abs_diff(stop_loss) <= sl_spread
+        }
+        Side::Sell => {
+            stop_loss > Uint128::zero() && spot_price > stop_loss
+                || stop_loss.abs_diff(spot_price) <= sl_spread
+        }
+    })
+}
+
 // Checks that position isn't zero
 pub fn require_position_not_zero(size: Uint128) -> StdResult<Response> {
     if size.is_zero() {
@@ -394,6 +773,15 @@ pub fn require_not_paused(paused: bool) -> StdResult<Response> {
     Ok(Response::new())
 }
 
+// check the vamm accepts positions that add risk
+pub fn require_not_reduce_only(storage: &dyn Storage, vamm: &Addr) -> StdResult<Response> {
+    if read_reduce_only(storage, vamm)? {
+        return Err(StdError::generic_err("vAMM is reduce only"));
+    }
+
+    Ok(Response::new())
+}
+
 // check an input is non-zero
 pub fn require_non_zero_input(input: Uint128) -> StdResult<Response> {
     if input.is_zero() {
@@ -435,15 +823,44 @@ pub fn parse_swap(response: &SubMsgResponse) -> StdResult<(Uint128, Uint128, u64
     }
 }
 
-pub fn parse_pay_funding<'a>(response: &'a SubMsgResponse) -> StdResult<(Integer, &'a str)> {
+pub fn parse_pay_funding<'a>(response: &'a SubMsgResponse) -> StdResult<(Integer, u64, &'a str)> {
     // Find swap inputs and output events
     let wasm = read_response("wasm", response)?;
     let premium_str = read_event("premium_fraction", wasm)?;
     let premium = Integer::from_str(premium_str)?;
 
+    let funding_id_str = read_event("funding_id", wasm)?;
+    let funding_id =
+        u64::from_str(funding_id_str).map_err(|_| StdError::generic_err("Invalid funding id"))?;
+
+    let sender = read_event("_contract_address", wasm)?;
+
+    Ok((premium, funding_id, sender))
+}
+
+// returns the action a position of a batch was processed with
+pub fn parse_batch_position(response: &SubMsgResponse) -> StdResult<&str> {
+    let wasm = read_response("wasm", response)?;
+
+    read_event("action", wasm)
+}
+
+// returns the part of an insurance fund withdrawal the insurance fund couldn't cover
+pub fn parse_insurance_withdrawal(response: &SubMsgResponse) -> StdResult<Uint128> {
+    let wasm = read_response("wasm", response)?;
+    let shortfall_str = read_event("shortfall", wasm)?;
+
+    Uint128::from_str(shortfall_str)
+}
+
+pub fn parse_adjust_vamm<'a>(response: &'a SubMsgResponse) -> StdResult<(Integer, &'a str)> {
+    let wasm = read_response("wasm", response)?;
+    let cost_str = read_event("adjustment_cost", wasm)?;
+    let cost = Integer::from_str(cost_str)?;
+
     let sender = read_event("_contract_address", wasm)?;
 
-    Ok((premium, sender))
+    Ok((cost, sender))
 }
 
 // takes the side (buy|sell) and returns the direction (long|short)
@@ -1,8 +1,14 @@
 #[cfg(not(feature = "library"))]
 use crate::error::ContractError;
 use crate::{
-    handle::{add_token, remove_token, send_token, update_owner},
-    query::{query_all_token, query_config, query_is_token, query_owner, query_token_list_length},
+    handle::{
+        add_token, bond, claim_rewards, claim_unbonded, distribute_rewards, remove_token,
+        send_token, unbond, update_owner, update_staking_config,
+    },
+    query::{
+        query_all_token, query_config, query_distribution, query_is_token, query_owner,
+        query_staker, query_token_list_length,
+    },
     state::{store_config, Config},
 };
 
@@ -20,6 +26,9 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Owner admin
 pub const OWNER: Admin = Admin::new("owner");
 
+const DEFAULT_EPOCH_DURATION: u64 = 24 * 60 * 60;
+const DEFAULT_UNBONDING_PERIOD: u64 = DEFAULT_EPOCH_DURATION;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -29,7 +38,11 @@ pub fn instantiate(
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    let config = Config {};
+    let config = Config {
+        staking_token: None,
+        epoch_duration: DEFAULT_EPOCH_DURATION,
+        unbonding_period: DEFAULT_UNBONDING_PERIOD,
+    };
 
     store_config(deps.storage, &config)?;
 
@@ -49,6 +62,16 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             amount,
             recipient,
         } => send_token(deps.as_ref(), env, info, token, amount, recipient),
+        ExecuteMsg::UpdateStakingConfig {
+            staking_token,
+            epoch_duration,
+            unbonding_period,
+        } => update_staking_config(deps, info, staking_token, epoch_duration, unbonding_period),
+        ExecuteMsg::Bond { amount } => bond(deps, env, info, amount),
+        ExecuteMsg::Unbond { amount } => unbond(deps, env, info, amount),
+        ExecuteMsg::ClaimUnbonded {} => claim_unbonded(deps, env, info),
+        ExecuteMsg::DistributeRewards {} => distribute_rewards(deps, env),
+        ExecuteMsg::ClaimRewards { token } => claim_rewards(deps, info, token),
     }
 }
 
@@ -60,6 +83,8 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::IsToken { token } => to_binary(&query_is_token(deps, token)?),
         QueryMsg::GetTokenList { limit } => to_binary(&query_all_token(deps, limit)?),
         QueryMsg::GetTokenLength {} => to_binary(&query_token_list_length(deps)?),
+        QueryMsg::Distribution {} => to_binary(&query_distribution(deps)?),
+        QueryMsg::Staker { address } => to_binary(&query_staker(deps, address)?),
     }
 }
 
@@ -1,9 +1,14 @@
 use cosmwasm_std::{Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128};
-use margined_common::validate::validate_eligible_collateral as validate_funds;
+use margined_common::{asset::AssetInfo, validate::validate_eligible_collateral as validate_funds};
 
 use crate::{
     contract::OWNER,
-    state::{is_token, remove_token as remove_token_from_list, save_token},
+    state::{
+        accrue_staker_rewards, is_token, read_config, read_distribution, read_staker,
+        read_token_list, remove_token as remove_token_from_list, save_token, store_config,
+        store_distribution, store_staker, Config, Distribution, RewardIndex,
+        REWARD_INDEX_PRECISION, TOKEN_LIMIT,
+    },
 };
 
 pub fn update_owner(deps: DepsMut, info: MessageInfo, owner: String) -> StdResult<Response> {
@@ -77,8 +82,15 @@ pub fn send_token(
     // query the balance of the given token that this contract holds
     let balance = valid_token.query_balance(&deps.querier, env.contract.address)?;
 
+    // rewards distributed to stakers and the bonded tokens can't be sent
+    let reserved = reserved_balance(
+        &read_config(deps.storage)?,
+        &read_distribution(deps.storage)?,
+        &valid_token,
+    );
+
     // check that the balance is sufficient to pay the amount
-    if balance < amount {
+    if balance.saturating_sub(reserved) < amount {
         return Err(StdError::generic_err("Insufficient funds"));
     }
     Ok(Response::default()
@@ -86,3 +98,300 @@ pub fn send_token(
         .add_attribute("action", "send_token")
     )
 }
+
+pub fn update_staking_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    staking_token: Option<String>,
+    epoch_duration: Option<u64>,
+    unbonding_period: Option<u64>,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut config = read_config(deps.storage)?;
+
+    if let Some(staking_token) = staking_token {
+        let staking_token = validate_funds(deps.as_ref(), staking_token)?;
+
+        let distribution = read_distribution(deps.storage)?;
+        let is_staked =
+            !distribution.total_bonded.is_zero() || !distribution.total_unbonding.is_zero();
+        if is_staked && config.staking_token.as_ref() != Some(&staking_token) {
+            return Err(StdError::generic_err(
+                "cannot change the staking token while tokens are bonded",
+            ));
+        }
+
+        config.staking_token = Some(staking_token);
+    }
+
+    if let Some(epoch_duration) = epoch_duration {
+        config.epoch_duration = epoch_duration;
+    }
+
+    if let Some(unbonding_period) = unbonding_period {
+        config.unbonding_period = unbonding_period;
+    }
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_staking_config"))
+}
+
+/// Bonds the staking token, bonded tokens earn a pro rata share of the fees distributed
+pub fn bond(deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> StdResult<Response> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("amount cannot be zero"));
+    }
+
+    let config = read_config(deps.storage)?;
+    let staking_token = match config.staking_token {
+        Some(staking_token) => staking_token,
+        None => return Err(StdError::generic_err("staking is not enabled")),
+    };
+
+    let mut msgs = vec![];
+    match &staking_token {
+        AssetInfo::NativeToken { denom } => {
+            let sent = info
+                .funds
+                .iter()
+                .find(|coin| coin.denom.eq(denom))
+                .map(|coin| coin.amount)
+                .unwrap_or_default();
+
+            if sent != amount {
+                return Err(StdError::generic_err("sent funds do not match the amount"));
+            }
+        }
+        AssetInfo::Token { .. } => {
+            msgs.push(staking_token.into_msg(
+                env.contract.address.to_string(),
+                amount,
+                Some(info.sender.to_string()),
+            )?);
+        }
+    }
+
+    let mut distribution = read_distribution(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    accrue_staker_rewards(&mut staker, &distribution)?;
+
+    staker.bonded = staker.bonded.checked_add(amount)?;
+    distribution.total_bonded = distribution.total_bonded.checked_add(amount)?;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_distribution(deps.storage, &distribution)?;
+
+    Ok(Response::default().add_messages(msgs).add_attributes(vec![
+        ("action", "bond"),
+        ("staker", info.sender.as_ref()),
+        ("amount", &amount.to_string()),
+    ]))
+}
+
+/// Unbonds the staking token, unbonded tokens stop earning and are locked for the unbonding
+/// period so bonding just before a distribution doesn't pay off, the rewards earned so far stay
+/// claimable. Unbonding more tokens restarts the unbonding period.
+pub fn unbond(deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> StdResult<Response> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("amount cannot be zero"));
+    }
+
+    let config = read_config(deps.storage)?;
+    let mut distribution = read_distribution(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    if staker.bonded < amount {
+        return Err(StdError::generic_err("insufficient bonded tokens"));
+    }
+
+    accrue_staker_rewards(&mut staker, &distribution)?;
+
+    staker.bonded = staker.bonded.checked_sub(amount)?;
+    staker.unbonding = staker.unbonding.checked_add(amount)?;
+    staker.unlock_time = env.block.time.seconds() + config.unbonding_period;
+    distribution.total_bonded = distribution.total_bonded.checked_sub(amount)?;
+    distribution.total_unbonding = distribution.total_unbonding.checked_add(amount)?;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_distribution(deps.storage, &distribution)?;
+
+    Ok(Response::default().add_attributes(vec![
+        ("action", "unbond"),
+        ("staker", info.sender.as_ref()),
+        ("amount", &amount.to_string()),
+        ("unlock_time", &staker.unlock_time.to_string()),
+    ]))
+}
+
+/// Returns the unbonding tokens to the staker once the unbonding period has passed
+pub fn claim_unbonded(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    let mut distribution = read_distribution(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    if staker.unbonding.is_zero() {
+        return Err(StdError::generic_err("no unbonding tokens"));
+    }
+
+    if env.block.time.seconds() < staker.unlock_time {
+        return Err(StdError::generic_err("unbonding period has not ended"));
+    }
+
+    let staking_token = match config.staking_token {
+        Some(staking_token) => staking_token,
+        None => return Err(StdError::generic_err("staking is not enabled")),
+    };
+
+    let amount = staker.unbonding;
+    staker.unbonding = Uint128::zero();
+    distribution.total_unbonding = distribution.total_unbonding.checked_sub(amount)?;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_distribution(deps.storage, &distribution)?;
+
+    Ok(Response::default()
+        .add_message(staking_token.into_msg(info.sender.to_string(), amount, None)?)
+        .add_attributes(vec![
+            ("action", "claim_unbonded"),
+            ("staker", info.sender.as_ref()),
+            ("amount", &amount.to_string()),
+        ]))
+}
+
+/// Distributes the fees received since the last epoch to the bonded tokens by increasing
+/// the reward index of every token in the token list
+pub fn distribute_rewards(deps: DepsMut, env: Env) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    let mut distribution = read_distribution(deps.storage)?;
+
+    if env.block.time.seconds() < distribution.last_distribution + config.epoch_duration {
+        return Err(StdError::generic_err("epoch has not ended"));
+    }
+
+    if distribution.total_bonded.is_zero() {
+        return Err(StdError::generic_err("no tokens are bonded"));
+    }
+
+    let mut attributes = vec![("action".to_string(), "distribute_rewards".to_string())];
+    for token in read_token_list(deps.storage, TOKEN_LIMIT)? {
+        let balance = token.query_balance(&deps.querier, env.contract.address.clone())?;
+        let rewards = balance.saturating_sub(reserved_balance(&config, &distribution, &token));
+
+        let index_delta = rewards.multiply_ratio(REWARD_INDEX_PRECISION, distribution.total_bonded);
+
+        // only what the stakers can claim after rounding is distributed
+        let distributed = distribution
+            .total_bonded
+            .multiply_ratio(index_delta, REWARD_INDEX_PRECISION);
+
+        let position = distribution
+            .reward_indexes
+            .iter()
+            .position(|reward_index| reward_index.token == token);
+
+        let reward_index = match position {
+            Some(position) => &mut distribution.reward_indexes[position],
+            None => {
+                distribution.reward_indexes.push(RewardIndex {
+                    token: token.clone(),
+                    index: Uint128::zero(),
+                    unclaimed: Uint128::zero(),
+                });
+                distribution.reward_indexes.last_mut().unwrap()
+            }
+        };
+
+        reward_index.index = reward_index.index.checked_add(index_delta)?;
+        reward_index.unclaimed = reward_index.unclaimed.checked_add(distributed)?;
+
+        attributes.push(("rewards".to_string(), format!("{}{}", distributed, token)));
+    }
+
+    distribution.last_distribution = env.block.time.seconds();
+
+    store_distribution(deps.storage, &distribution)?;
+
+    Ok(Response::default().add_attributes(attributes))
+}
+
+/// Claims the pending rewards of the staker, either of a single token or all of them
+pub fn claim_rewards(
+    deps: DepsMut,
+    info: MessageInfo,
+    token: Option<String>,
+) -> StdResult<Response> {
+    let token = token
+        .map(|token| validate_funds(deps.as_ref(), token))
+        .transpose()?;
+
+    let mut distribution = read_distribution(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    accrue_staker_rewards(&mut staker, &distribution)?;
+
+    let mut msgs = vec![];
+    for reward in staker.rewards.iter_mut() {
+        if reward.pending.is_zero() {
+            continue;
+        }
+
+        if let Some(token) = &token {
+            if token != &reward.token {
+                continue;
+            }
+        }
+
+        // rounding can leave the unclaimed total a little short of the pending rewards
+        if let Some(reward_index) = distribution
+            .reward_indexes
+            .iter_mut()
+            .find(|reward_index| reward_index.token == reward.token)
+        {
+            reward_index.unclaimed = reward_index.unclaimed.saturating_sub(reward.pending);
+        }
+
+        msgs.push(
+            reward
+                .token
+                .clone()
+                .into_msg(info.sender.to_string(), reward.pending, None)?,
+        );
+        reward.pending = Uint128::zero();
+    }
+
+    if msgs.is_empty() {
+        return Err(StdError::generic_err("no rewards to claim"));
+    }
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_distribution(deps.storage, &distribution)?;
+
+    Ok(Response::default().add_messages(msgs).add_attributes(vec![
+        ("action", "claim_rewards"),
+        ("staker", info.sender.as_ref()),
+    ]))
+}
+
+// the part of the balance of a token that belongs to the stakers
+fn reserved_balance(config: &Config, distribution: &Distribution, token: &AssetInfo) -> Uint128 {
+    let unclaimed = distribution
+        .reward_indexes
+        .iter()
+        .find(|reward_index| &reward_index.token == token)
+        .map(|reward_index| reward_index.unclaimed)
+        .unwrap_or_default();
+
+    if config.staking_token.as_ref() == Some(token) {
+        unclaimed
+            .saturating_add(distribution.total_bonded)
+            .saturating_add(distribution.total_unbonding)
+    } else {
+        unclaimed
+    }
+}
@@ -1,20 +1,24 @@
 use cosmwasm_std::{Deps, StdError, StdResult};
 use margined_common::validate::validate_eligible_collateral as validate_funds;
 use margined_perp::margined_fee_pool::{
-    AllTokenResponse, ConfigResponse, OwnerResponse, TokenLengthResponse, TokenResponse,
+    AllTokenResponse, ConfigResponse, DistributionResponse, OwnerResponse, PendingRewardResponse,
+    RewardIndexResponse, StakerResponse, TokenLengthResponse, TokenResponse,
 };
 
 use crate::{
     contract::OWNER,
-    state::{is_token, read_token_list, TOKEN_LIMIT},
+    state::{
+        accrue_staker_rewards, is_token, read_config, read_distribution, read_staker,
+        read_token_list, TOKEN_LIMIT,
+    },
 };
 
 const DEFAULT_PAGINATION_LIMIT: u32 = 10u32;
 const MAX_PAGINATION_LIMIT: u32 = TOKEN_LIMIT as u32;
 
 /// Queries contract config
-pub fn query_config(_: Deps) -> StdResult<ConfigResponse> {
-    Ok(ConfigResponse {})
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    read_config(deps.storage)
 }
 
 /// Queries contract owner from the admin
@@ -60,3 +64,48 @@ pub fn query_token_list_length(deps: Deps) -> StdResult<TokenLengthResponse> {
         length: list_length,
     })
 }
+
+/// Queries the bonded total and the reward index of every distributed token
+pub fn query_distribution(deps: Deps) -> StdResult<DistributionResponse> {
+    let config = read_config(deps.storage)?;
+    let distribution = read_distribution(deps.storage)?;
+
+    Ok(DistributionResponse {
+        total_bonded: distribution.total_bonded,
+        last_distribution: distribution.last_distribution,
+        next_distribution: distribution.last_distribution + config.epoch_duration,
+        reward_indexes: distribution
+            .reward_indexes
+            .into_iter()
+            .map(|reward_index| RewardIndexResponse {
+                token: reward_index.token,
+                index: reward_index.index,
+                unclaimed: reward_index.unclaimed,
+            })
+            .collect(),
+    })
+}
+
+/// Queries the bonded and unbonding tokens of a staker and their pending rewards per token
+pub fn query_staker(deps: Deps, address: String) -> StdResult<StakerResponse> {
+    let address = deps.api.addr_validate(&address)?;
+
+    let distribution = read_distribution(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &address)?;
+
+    accrue_staker_rewards(&mut staker, &distribution)?;
+
+    Ok(StakerResponse {
+        bonded: staker.bonded,
+        unbonding: staker.unbonding,
+        unlock_time: staker.unlock_time,
+        pending_rewards: staker
+            .rewards
+            .into_iter()
+            .map(|reward| PendingRewardResponse {
+                token: reward.token,
+                amount: reward.pending,
+            })
+            .collect(),
+    })
+}
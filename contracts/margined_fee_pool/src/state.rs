@@ -1,10 +1,14 @@
-use cosmwasm_std::{from_slice, to_vec, StdError, StdResult, Storage};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, Addr, StdError, StdResult, Storage, Uint128};
 use margined_common::asset::AssetInfo;
 use margined_perp::margined_fee_pool::ConfigResponse;
 
 pub static KEY_CONFIG: &[u8] = b"config";
+pub static KEY_DISTRIBUTION: &[u8] = b"distribution";
+pub static PREFIX_STAKER: &[u8] = b"staker";
 pub const TOKEN_LIST: &[u8] = b"token-list";
 pub const TOKEN_LIMIT: usize = 3usize;
+pub const REWARD_INDEX_PRECISION: Uint128 = Uint128::new(1_000_000_000_000_000_000u128);
 
 pub type Config = ConfigResponse;
 
@@ -12,6 +16,14 @@ pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()>
     Ok(storage.set(KEY_CONFIG, &to_vec(config)?))
 }
 
+// the config was empty before staking was added
+pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
+    match storage.get(KEY_CONFIG) {
+        Some(data) => from_slice(&data),
+        None => Ok(Config::default()),
+    }
+}
+
 // function checks if an addr is already added and adds it if not
 // We also check that we have not reached the limit of tokens here
 pub fn save_token(storage: &mut dyn Storage, input: AssetInfo) -> StdResult<()> {
@@ -82,3 +94,105 @@ pub fn remove_token(storage: &mut dyn Storage, token: AssetInfo) -> StdResult<()
     // saves the updated token_list
     Ok(storage.set(TOKEN_LIST, &to_vec(&token_list)?))
 }
+
+#[cw_serde]
+pub struct RewardIndex {
+    pub token: AssetInfo,
+    pub index: Uint128, // rewards per bonded token scaled by REWARD_INDEX_PRECISION
+    pub unclaimed: Uint128, // distributed rewards that are still held by the fee pool
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Distribution {
+    pub total_bonded: Uint128,
+    #[serde(default)]
+    pub total_unbonding: Uint128,
+    pub last_distribution: u64,
+    pub reward_indexes: Vec<RewardIndex>,
+}
+
+pub fn store_distribution(storage: &mut dyn Storage, distribution: &Distribution) -> StdResult<()> {
+    Ok(storage.set(KEY_DISTRIBUTION, &to_vec(distribution)?))
+}
+
+pub fn read_distribution(storage: &dyn Storage) -> StdResult<Distribution> {
+    match storage.get(KEY_DISTRIBUTION) {
+        Some(data) => from_slice(&data),
+        None => Ok(Distribution::default()),
+    }
+}
+
+#[cw_serde]
+pub struct StakerReward {
+    pub token: AssetInfo,
+    pub index: Uint128, // reward index when the rewards were last accrued
+    pub pending: Uint128,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Staker {
+    pub bonded: Uint128,
+    #[serde(default)]
+    pub unbonding: Uint128, // unbonded tokens that no longer earn and are locked until unlock_time
+    #[serde(default)]
+    pub unlock_time: u64,
+    pub rewards: Vec<StakerReward>,
+}
+
+// stakers without bonded or unbonding tokens or pending rewards are removed
+pub fn store_staker(storage: &mut dyn Storage, address: &Addr, staker: &Staker) -> StdResult<()> {
+    let key = [PREFIX_STAKER, address.as_bytes()].concat();
+
+    if staker.bonded.is_zero()
+        && staker.unbonding.is_zero()
+        && staker.rewards.iter().all(|reward| reward.pending.is_zero())
+    {
+        storage.remove(&key);
+        return Ok(());
+    }
+
+    Ok(storage.set(&key, &to_vec(staker)?))
+}
+
+pub fn read_staker(storage: &dyn Storage, address: &Addr) -> StdResult<Staker> {
+    match storage.get(&[PREFIX_STAKER, address.as_bytes()].concat()) {
+        Some(data) => from_slice(&data),
+        None => Ok(Staker::default()),
+    }
+}
+
+// moves the rewards earned since the last accrual into pending, must be called before the
+// bonded amount of the staker changes
+pub fn accrue_staker_rewards(staker: &mut Staker, distribution: &Distribution) -> StdResult<()> {
+    for reward_index in distribution.reward_indexes.iter() {
+        let position = staker
+            .rewards
+            .iter()
+            .position(|reward| reward.token == reward_index.token);
+
+        // tokens distributed after the last accrual are earned from a zero index
+        let reward = match position {
+            Some(position) => &mut staker.rewards[position],
+            None => {
+                staker.rewards.push(StakerReward {
+                    token: reward_index.token.clone(),
+                    index: Uint128::zero(),
+                    pending: Uint128::zero(),
+                });
+                staker.rewards.last_mut().unwrap()
+            }
+        };
+
+        let earned = staker.bonded.multiply_ratio(
+            reward_index.index.checked_sub(reward.index)?,
+            REWARD_INDEX_PRECISION,
+        );
+
+        reward.pending = reward.pending.checked_add(earned)?;
+        reward.index = reward_index.index;
+    }
+
+    Ok(())
+}
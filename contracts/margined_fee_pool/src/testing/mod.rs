@@ -1,3 +1,4 @@
+mod staking_tests;
 mod tests;
 
 use margined_utils::{
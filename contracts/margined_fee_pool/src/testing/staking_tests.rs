@@ -0,0 +1,186 @@
+use crate::testing::new_simple_scenario;
+use cosmwasm_std::Uint128;
+use cw20::Cw20ExecuteMsg;
+use margined_common::asset::AssetInfo;
+use margined_perp::margined_fee_pool::PendingRewardResponse;
+use margined_utils::cw_multi_test::Executor;
+use margined_utils::testing::{to_decimals, SimpleScenario};
+
+#[test]
+fn test_bonded_tokens_earn_the_fees_of_each_epoch() {
+    let SimpleScenario {
+        mut router,
+        owner,
+        alice,
+        bob,
+        fee_pool,
+        usdc,
+        ..
+    } = new_simple_scenario();
+
+    let msg = fee_pool.bond(to_decimals(100u64), vec![]).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: staking is not enabled"
+    );
+
+    let msg = fee_pool
+        .update_staking_config(Some(usdc.addr().to_string()), None, None)
+        .unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = fee_pool.add_token(usdc.addr().to_string()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    for (staker, amount) in [(alice.clone(), 100u64), (bob.clone(), 300u64)] {
+        router
+            .execute_contract(
+                staker.clone(),
+                usdc.addr(),
+                &Cw20ExecuteMsg::IncreaseAllowance {
+                    spender: fee_pool.addr().to_string(),
+                    amount: to_decimals(amount),
+                    expires: None,
+                },
+                &[],
+            )
+            .unwrap();
+
+        let msg = fee_pool.bond(to_decimals(amount), vec![]).unwrap();
+        router.execute(staker, msg).unwrap();
+    }
+
+    // fees sent to the fee pool
+    router
+        .execute_contract(
+            owner.clone(),
+            usdc.addr(),
+            &Cw20ExecuteMsg::Mint {
+                recipient: fee_pool.addr().to_string(),
+                amount: to_decimals(400u64),
+            },
+            &[],
+        )
+        .unwrap();
+
+    let msg = fee_pool.distribute_rewards().unwrap();
+    router.execute(bob.clone(), msg.clone()).unwrap();
+
+    let err = router.execute(bob.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: epoch has not ended"
+    );
+
+    let token = AssetInfo::Token {
+        contract_addr: usdc.addr(),
+    };
+
+    let staker = fee_pool.staker(&router.wrap(), alice.to_string()).unwrap();
+    assert_eq!(staker.bonded, to_decimals(100u64));
+    assert_eq!(
+        staker.pending_rewards,
+        vec![PendingRewardResponse {
+            token: token.clone(),
+            amount: to_decimals(100u64),
+        }]
+    );
+
+    // the bonded tokens and distributed rewards can't be sent by the owner
+    let send_msg = fee_pool
+        .send_token(
+            usdc.addr().to_string(),
+            Uint128::from(1u64),
+            owner.to_string(),
+        )
+        .unwrap();
+    let err = router.execute(owner.clone(), send_msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Insufficient funds"
+    );
+
+    let alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+
+    let claim_msg = fee_pool.claim_rewards(None).unwrap();
+    router.execute(alice.clone(), claim_msg.clone()).unwrap();
+
+    let err = router.execute(alice.clone(), claim_msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: no rewards to claim"
+    );
+
+    let unbond_msg = fee_pool.unbond(to_decimals(100u64)).unwrap();
+    router.execute(alice.clone(), unbond_msg).unwrap();
+
+    let new_alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(new_alice_balance, alice_balance + to_decimals(100u64));
+
+    // unbonded tokens are locked for the unbonding period
+    let staker = fee_pool.staker(&router.wrap(), alice.to_string()).unwrap();
+    assert_eq!(staker.bonded, Uint128::zero());
+    assert_eq!(staker.unbonding, to_decimals(100u64));
+
+    let claim_unbonded_msg = fee_pool.claim_unbonded().unwrap();
+    let err = router
+        .execute(alice.clone(), claim_unbonded_msg.clone())
+        .unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unbonding period has not ended"
+    );
+
+    // the next epoch only goes to the tokens still bonded
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(86_400u64);
+        block.height += 1;
+    });
+
+    router.execute(alice.clone(), claim_unbonded_msg).unwrap();
+
+    let new_alice_balance = usdc.balance(&router.wrap(), alice.clone()).unwrap();
+    assert_eq!(new_alice_balance, alice_balance + to_decimals(200u64));
+
+    router
+        .execute_contract(
+            owner.clone(),
+            usdc.addr(),
+            &Cw20ExecuteMsg::Mint {
+                recipient: fee_pool.addr().to_string(),
+                amount: to_decimals(300u64),
+            },
+            &[],
+        )
+        .unwrap();
+
+    router.execute(bob.clone(), msg).unwrap();
+
+    let staker = fee_pool.staker(&router.wrap(), alice.to_string()).unwrap();
+    assert_eq!(staker.bonded, Uint128::zero());
+    assert!(staker
+        .pending_rewards
+        .iter()
+        .all(|reward| reward.amount.is_zero()));
+
+    let bob_balance = usdc.balance(&router.wrap(), bob.clone()).unwrap();
+
+    let claim_msg = fee_pool
+        .claim_rewards(Some(usdc.addr().to_string()))
+        .unwrap();
+    router.execute(bob.clone(), claim_msg).unwrap();
+
+    let new_bob_balance = usdc.balance(&router.wrap(), bob).unwrap();
+    assert_eq!(new_bob_balance, bob_balance + to_decimals(600u64));
+
+    let distribution = fee_pool.distribution(&router.wrap()).unwrap();
+    assert_eq!(distribution.total_bonded, to_decimals(300u64));
+    assert_eq!(distribution.reward_indexes[0].token, token);
+    assert_eq!(distribution.reward_indexes[0].unclaimed, Uint128::zero());
+}
@@ -23,7 +23,14 @@ fn test_instantiation() {
 
     let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
     let config: ConfigResponse = from_binary(&res).unwrap();
-    assert_eq!(config, ConfigResponse {});
+    assert_eq!(
+        config,
+        ConfigResponse {
+            staking_token: None,
+            epoch_duration: 86_400u64,
+            unbonding_period: 86_400u64,
+        }
+    );
 }
 
 #[test]
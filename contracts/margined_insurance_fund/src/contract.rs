@@ -1,10 +1,13 @@
 #[cfg(not(feature = "library"))]
 use crate::error::ContractError;
 use crate::{
-    handle::{add_vamm, remove_vamm, shutdown_all_vamm, update_owner, withdraw},
+    handle::{
+        add_vamm, claim_unstake, fund_vamm, remove_vamm, shutdown_all_vamm, stake, unstake,
+        update_owner, update_spillover_policy, update_vault_config, withdraw,
+    },
     query::{
-        query_all_vamm, query_config, query_is_vamm, query_owner, query_status_all_vamm,
-        query_vamm_status,
+        query_all_vamm, query_all_vamm_coverage, query_config, query_is_vamm, query_owner,
+        query_staker, query_status_all_vamm, query_vamm_coverage, query_vamm_status, query_vault,
     },
     state::{store_config, Config},
 };
@@ -13,7 +16,9 @@ use cosmwasm_std::{
 };
 use cw2::set_contract_version;
 use cw_controllers::Admin;
-use margined_perp::margined_insurance_fund::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use margined_perp::margined_insurance_fund::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SpilloverPolicy,
+};
 
 /// Contract name that is used for migration.
 const CONTRACT_NAME: &str = "crates.io:margined-insurance-fund";
@@ -22,6 +27,8 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Owner admin
 pub const OWNER: Admin = Admin::new("owner");
 
+const DEFAULT_UNSTAKE_COOLDOWN: u64 = 7 * 24 * 60 * 60;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -33,6 +40,8 @@ pub fn instantiate(
 
     let config = Config {
         engine: deps.api.addr_validate(&msg.engine)?,
+        unstake_cooldown: DEFAULT_UNSTAKE_COOLDOWN,
+        spillover_policy: SpilloverPolicy::default(),
     };
 
     store_config(deps.storage, &config)?;
@@ -48,13 +57,27 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         ExecuteMsg::UpdateOwner { owner } => update_owner(deps, info, owner),
         ExecuteMsg::AddVamm { vamm } => add_vamm(deps, info, vamm),
         ExecuteMsg::RemoveVamm { vamm } => remove_vamm(deps, info, vamm),
-        ExecuteMsg::Withdraw { token, amount } => withdraw(deps, info, token, amount),
+        ExecuteMsg::Withdraw {
+            token,
+            amount,
+            vamm,
+        } => withdraw(deps, env, info, token, amount, vamm),
+        ExecuteMsg::FundVamm { vamm, amount } => fund_vamm(deps, env, info, vamm, amount),
+        ExecuteMsg::UpdateSpilloverPolicy { spillover_policy } => {
+            update_spillover_policy(deps, info, spillover_policy)
+        }
         ExecuteMsg::ShutdownVamms {} => shutdown_all_vamm(deps, env, info),
+        ExecuteMsg::UpdateVaultConfig { unstake_cooldown } => {
+            update_vault_config(deps, info, unstake_cooldown)
+        }
+        ExecuteMsg::Stake { amount } => stake(deps, env, info, amount),
+        ExecuteMsg::Unstake { shares } => unstake(deps, env, info, shares),
+        ExecuteMsg::ClaimUnstake {} => claim_unstake(deps, env, info),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::GetOwner {} => to_binary(&query_owner(deps)?),
@@ -62,6 +85,12 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetAllVamm { limit } => to_binary(&query_all_vamm(deps, limit)?),
         QueryMsg::GetVammStatus { vamm } => to_binary(&query_vamm_status(deps, vamm)?),
         QueryMsg::GetAllVammStatus { limit } => to_binary(&query_status_all_vamm(deps, limit)?),
+        QueryMsg::Vault {} => to_binary(&query_vault(deps, env)?),
+        QueryMsg::Staker { address } => to_binary(&query_staker(deps, env, address)?),
+        QueryMsg::VammCoverage { vamm } => to_binary(&query_vamm_coverage(deps, env, vamm)?),
+        QueryMsg::AllVammCoverage { limit } => {
+            to_binary(&query_all_vamm_coverage(deps, env, limit)?)
+        }
     }
 }
 
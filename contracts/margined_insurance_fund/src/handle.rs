@@ -1,10 +1,18 @@
 use crate::{
     contract::OWNER,
-    state::{read_config, read_vammlist, remove_vamm as remove_amm, save_vamm, VAMM_LIMIT},
+    query::query_vault_assets,
+    state::{
+        is_vamm, read_config, read_staker, read_vamm_fund, read_vamm_funds, read_vammlist,
+        read_vault, remove_vamm as remove_amm, save_vamm, store_config, store_staker,
+        store_vamm_fund, store_vault, sum_vamm_funds, VAMM_LIMIT,
+    },
+};
+use cosmwasm_std::{
+    Addr, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, Uint128,
 };
-use cosmwasm_std::{DepsMut, Env, MessageInfo, Response, StdError, StdResult, Uint128};
 
 use margined_common::{asset::AssetInfo, messages::wasm_execute};
+use margined_perp::margined_insurance_fund::SpilloverPolicy;
 use margined_perp::margined_vamm::ExecuteMsg as VammExecuteMessage;
 use margined_utils::contracts::helpers::{EngineController, VammController};
 
@@ -56,8 +64,9 @@ pub fn remove_vamm(deps: DepsMut, info: MessageInfo, vamm: String) -> StdResult<
     // validate address
     let vamm_valid = deps.api.addr_validate(&vamm)?;
 
-    // remove vamm here
-    remove_amm(deps.storage, vamm_valid)?;
+    // remove vamm here, its sub-fund is released to the backstop
+    remove_amm(deps.storage, vamm_valid.clone())?;
+    store_vamm_fund(deps.storage, &vamm_valid, Uint128::zero())?;
 
     Ok(Response::default().add_attribute("action", "remove_amm"))
 }
@@ -74,7 +83,7 @@ pub fn shutdown_all_vamm(deps: DepsMut, _env: Env, info: MessageInfo) -> StdResu
     // initialise the submsgs vec
     let mut msgs = vec![];
     for vamm in keys.iter() {
-        let msg = wasm_execute(vamm, &VammExecuteMessage::SetOpen { open: false }, vec![])?;
+        let msg = wasm_execute(vamm, &VammExecuteMessage::Shutdown {}, vec![])?;
         msgs.push(msg);
     }
 
@@ -86,9 +95,11 @@ pub fn shutdown_all_vamm(deps: DepsMut, _env: Env, info: MessageInfo) -> StdResu
 
 pub fn withdraw(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     token: AssetInfo,
     amount: Uint128,
+    vamm: Option<String>,
 ) -> StdResult<Response> {
     let config = read_config(deps.storage)?;
 
@@ -97,14 +108,336 @@ pub fn withdraw(
         return Err(StdError::generic_err("unauthorized"));
     }
 
-    // send tokens if native or cw20
-    let transfer_msg = token.into_msg(config.engine.to_string(), amount, None)?;
+    let vamm = vamm.map(|vamm| deps.api.addr_validate(&vamm)).transpose()?;
+
+    let total_assets = token.query_balance(&deps.querier, env.contract.address)?;
+    let (sub_fund_debit, shortfall) = debit_vamm_funds(
+        deps.storage,
+        vamm.as_ref(),
+        amount,
+        total_assets,
+        config.spillover_policy,
+    )?;
+
+    // an isolated vamm is only paid what its sub-fund covers, the engine records the shortfall
+    let amount = amount.checked_sub(shortfall)?;
+
+    let mut msgs = vec![];
+    if !amount.is_zero() {
+        // send tokens if native or cw20
+        msgs.push(token.into_msg(config.engine.to_string(), amount, None)?);
+    }
 
     Ok(Response::default()
-        .add_message(transfer_msg)
+        .add_messages(msgs)
         .add_attributes(vec![
             ("action", "insurance_withdraw"),
             ("amount", &amount.to_string()),
+            ("sub_fund_debit", &sub_fund_debit.to_string()),
+            ("shortfall", &shortfall.to_string()),
         ])
     )
 }
+
+// Takes a withdrawal out of the sub-funds, the sub-fund of the vamm is used first and the rest
+// follows the spillover policy. Withdrawals not made for a vamm can use the whole fund. Returns
+// the amount debited from sub-funds and, under the isolated policy, the part left uncovered.
+fn debit_vamm_funds(
+    storage: &mut dyn Storage,
+    vamm: Option<&Addr>,
+    amount: Uint128,
+    total_assets: Uint128,
+    spillover_policy: SpilloverPolicy,
+) -> StdResult<(Uint128, Uint128)> {
+    let vamm_funds = read_vamm_funds(storage)?;
+    let backstop = total_assets.saturating_sub(sum_vamm_funds(&vamm_funds)?);
+
+    let mut remaining = amount;
+    let mut sub_fund_debit = Uint128::zero();
+
+    let spillover_policy = match vamm {
+        Some(vamm) => {
+            let sub_fund = read_vamm_fund(storage, vamm)?;
+            let debit = sub_fund.min(remaining);
+
+            store_vamm_fund(storage, vamm, sub_fund.checked_sub(debit)?)?;
+            remaining = remaining.checked_sub(debit)?;
+            sub_fund_debit = sub_fund_debit.checked_add(debit)?;
+
+            spillover_policy
+        }
+        None => SpilloverPolicy::Shared,
+    };
+
+    if spillover_policy != SpilloverPolicy::Isolated {
+        remaining = remaining.saturating_sub(backstop);
+    }
+
+    if spillover_policy == SpilloverPolicy::Shared {
+        for (other, sub_fund) in vamm_funds {
+            if remaining.is_zero() {
+                break;
+            }
+
+            if vamm == Some(&other) {
+                continue;
+            }
+
+            let debit = sub_fund.min(remaining);
+
+            store_vamm_fund(storage, &other, sub_fund.checked_sub(debit)?)?;
+            remaining = remaining.checked_sub(debit)?;
+            sub_fund_debit = sub_fund_debit.checked_add(debit)?;
+        }
+    }
+
+    if !remaining.is_zero() && spillover_policy != SpilloverPolicy::Isolated {
+        return Err(StdError::generic_err("insufficient insurance coverage"));
+    }
+
+    Ok((sub_fund_debit, remaining))
+}
+
+/// Moves funds of the backstop into the sub-fund of a vAMM, the engine uses this to credit the
+/// fees and margin it sends to the insurance fund for a vAMM
+pub fn fund_vamm(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    vamm: String,
+    amount: Uint128,
+) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+
+    // check permission
+    let is_engine = info.sender == config.engine;
+    if !is_engine && !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if amount.is_zero() {
+        return Err(StdError::generic_err("amount cannot be zero"));
+    }
+
+    // validate address
+    let vamm_valid = deps.api.addr_validate(&vamm)?;
+
+    let (_, total_assets) = query_vault_assets(deps.as_ref(), &env)?;
+    let backstop = total_assets.saturating_sub(sum_vamm_funds(&read_vamm_funds(deps.storage)?)?);
+
+    // funds the engine sends for a vamm that has been removed stay in the backstop
+    let amount = if is_engine {
+        if !is_vamm(deps.storage, vamm_valid.clone()) {
+            return Ok(Response::default().add_attribute("action", "fund_vamm"));
+        }
+
+        amount.min(backstop)
+    } else {
+        if !is_vamm(deps.storage, vamm_valid.clone()) {
+            return Err(StdError::generic_err("This vAMM has not been added"));
+        }
+
+        if amount > backstop {
+            return Err(StdError::generic_err("insufficient backstop funds"));
+        }
+
+        amount
+    };
+
+    let sub_fund = read_vamm_fund(deps.storage, &vamm_valid)?.checked_add(amount)?;
+    store_vamm_fund(deps.storage, &vamm_valid, sub_fund)?;
+
+    Ok(Response::default().add_attributes(vec![
+        ("action", "fund_vamm"),
+        ("vamm", vamm_valid.as_ref()),
+        ("amount", &amount.to_string()),
+        ("sub_fund", &sub_fund.to_string()),
+    ]))
+}
+
+pub fn update_spillover_policy(
+    deps: DepsMut,
+    info: MessageInfo,
+    spillover_policy: SpilloverPolicy,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut config = read_config(deps.storage)?;
+    config.spillover_policy = spillover_policy;
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_spillover_policy"))
+}
+
+pub fn update_vault_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    unstake_cooldown: Option<u64>,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut config = read_config(deps.storage)?;
+
+    if let Some(unstake_cooldown) = unstake_cooldown {
+        config.unstake_cooldown = unstake_cooldown;
+    }
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_vault_config"))
+}
+
+/// Deposits the eligible collateral of the engine into the vault in exchange for shares
+pub fn stake(deps: DepsMut, env: Env, info: MessageInfo, amount: Uint128) -> StdResult<Response> {
+    if amount.is_zero() {
+        return Err(StdError::generic_err("amount cannot be zero"));
+    }
+
+    let (eligible_collateral, mut total_assets) = query_vault_assets(deps.as_ref(), &env)?;
+
+    let mut msgs = vec![];
+    match &eligible_collateral {
+        // native funds are already part of the balance
+        AssetInfo::NativeToken { denom } => {
+            let sent = info
+                .funds
+                .iter()
+                .find(|coin| coin.denom.eq(denom))
+                .map(|coin| coin.amount)
+                .unwrap_or_default();
+
+            if sent != amount {
+                return Err(StdError::generic_err("sent funds do not match the amount"));
+            }
+
+            total_assets = total_assets.checked_sub(amount)?;
+        }
+        AssetInfo::Token { .. } => {
+            msgs.push(eligible_collateral.into_msg(
+                env.contract.address.to_string(),
+                amount,
+                Some(info.sender.to_string()),
+            )?);
+        }
+    }
+
+    let mut vault = read_vault(deps.storage)?;
+
+    // funds held before the first stake are owned by the protocol so they can't be claimed
+    let shares = if vault.total_shares.is_zero() {
+        vault.protocol_shares = total_assets;
+        vault.total_shares = total_assets;
+
+        amount
+    } else {
+        if total_assets.is_zero() {
+            return Err(StdError::generic_err("insurance fund has no assets"));
+        }
+
+        amount
+            .checked_mul(vault.total_shares)?
+            .checked_div(total_assets)?
+    };
+
+    if shares.is_zero() {
+        return Err(StdError::generic_err("amount is too small to mint shares"));
+    }
+
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+    staker.shares = staker.shares.checked_add(shares)?;
+    vault.total_shares = vault.total_shares.checked_add(shares)?;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_vault(deps.storage, &vault)?;
+
+    Ok(Response::default().add_messages(msgs).add_attributes(vec![
+        ("action", "stake"),
+        ("staker", info.sender.as_ref()),
+        ("amount", &amount.to_string()),
+        ("shares", &shares.to_string()),
+    ]))
+}
+
+/// Starts the cooldown of the shares, they keep their exposure to the vault until claimed
+pub fn unstake(deps: DepsMut, env: Env, info: MessageInfo, shares: Uint128) -> StdResult<Response> {
+    if shares.is_zero() {
+        return Err(StdError::generic_err("shares cannot be zero"));
+    }
+
+    let config = read_config(deps.storage)?;
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    if staker.shares.checked_sub(staker.pending_shares)? < shares {
+        return Err(StdError::generic_err("insufficient shares"));
+    }
+
+    // unstaking more shares restarts the cooldown
+    staker.pending_shares = staker.pending_shares.checked_add(shares)?;
+    staker.unlock_time = env.block.time.seconds() + config.unstake_cooldown;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+
+    Ok(Response::default().add_attributes(vec![
+        ("action", "unstake"),
+        ("staker", info.sender.as_ref()),
+        ("shares", &shares.to_string()),
+        ("unlock_time", &staker.unlock_time.to_string()),
+    ]))
+}
+
+/// Burns the shares pending unstake once the cooldown has passed and pays out their value
+pub fn claim_unstake(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let mut staker = read_staker(deps.storage, &info.sender)?;
+
+    if staker.pending_shares.is_zero() {
+        return Err(StdError::generic_err("no pending unstake"));
+    }
+
+    if env.block.time.seconds() < staker.unlock_time {
+        return Err(StdError::generic_err("unstake is still cooling down"));
+    }
+
+    let (eligible_collateral, total_assets) = query_vault_assets(deps.as_ref(), &env)?;
+    let mut vault = read_vault(deps.storage)?;
+
+    let amount = staker
+        .pending_shares
+        .checked_mul(total_assets)?
+        .checked_div(vault.total_shares)?;
+
+    // the payout is taken pro rata from the sub-funds and the backstop, otherwise unstaking
+    // would drain the backstop and leave the sub-funds exceeding the balance
+    let shares = staker.pending_shares;
+    for (vamm, sub_fund) in read_vamm_funds(deps.storage)? {
+        let debit = sub_fund
+            .checked_mul(shares)?
+            .checked_div(vault.total_shares)?;
+        store_vamm_fund(deps.storage, &vamm, sub_fund.checked_sub(debit)?)?;
+    }
+
+    staker.shares = staker.shares.checked_sub(shares)?;
+    staker.pending_shares = Uint128::zero();
+    vault.total_shares = vault.total_shares.checked_sub(shares)?;
+
+    store_staker(deps.storage, &info.sender, &staker)?;
+    store_vault(deps.storage, &vault)?;
+
+    let mut msgs = vec![];
+    if !amount.is_zero() {
+        msgs.push(eligible_collateral.into_msg(info.sender.to_string(), amount, None)?);
+    }
+
+    Ok(Response::default().add_messages(msgs).add_attributes(vec![
+        ("action", "claim_unstake"),
+        ("staker", info.sender.as_ref()),
+        ("shares", &shares.to_string()),
+        ("amount", &amount.to_string()),
+    ]))
+}
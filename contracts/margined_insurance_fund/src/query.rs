@@ -1,13 +1,18 @@
-use cosmwasm_std::{Addr, Deps, StdError, StdResult};
+use cosmwasm_std::{Addr, Deps, Env, StdError, StdResult, Uint128};
+use margined_common::asset::AssetInfo;
 use margined_perp::margined_insurance_fund::{
-    AllVammResponse, AllVammStatusResponse, ConfigResponse, OwnerResponse, VammResponse,
-    VammStatusResponse,
+    AllVammCoverageResponse, AllVammResponse, AllVammStatusResponse, ConfigResponse, OwnerResponse,
+    SpilloverPolicy, StakerResponse, VammCoverageResponse, VammResponse, VammStatusResponse,
+    VaultResponse,
 };
-use margined_utils::contracts::helpers::VammController;
+use margined_utils::contracts::helpers::{EngineController, VammController};
 
 use crate::{
     contract::OWNER,
-    state::{is_vamm, read_config, read_vammlist, VAMM_LIMIT},
+    state::{
+        is_vamm, read_config, read_staker, read_vamm_funds, read_vammlist, read_vault,
+        sum_vamm_funds, VAMM_LIMIT,
+    },
 };
 
 const DEFAULT_PAGINATION_LIMIT: u32 = 10u32;
@@ -84,3 +89,126 @@ pub fn query_status_all_vamm(deps: Deps, limit: Option<u32>) -> StdResult<AllVam
         vamm_list_status: status_list,
     })
 }
+
+/// Returns the eligible collateral of the engine and how much of it the vault holds
+pub fn query_vault_assets(deps: Deps, env: &Env) -> StdResult<(AssetInfo, Uint128)> {
+    let config = read_config(deps.storage)?;
+
+    let eligible_collateral = EngineController(config.engine)
+        .config(&deps.querier)?
+        .eligible_collateral;
+
+    let total_assets =
+        eligible_collateral.query_balance(&deps.querier, env.contract.address.clone())?;
+
+    Ok((eligible_collateral, total_assets))
+}
+
+/// Queries the shares and assets of the staking vault
+pub fn query_vault(deps: Deps, env: Env) -> StdResult<VaultResponse> {
+    let (_, total_assets) = query_vault_assets(deps, &env)?;
+    let vault = read_vault(deps.storage)?;
+
+    Ok(VaultResponse {
+        total_shares: vault.total_shares,
+        total_assets,
+        protocol_shares: vault.protocol_shares,
+    })
+}
+
+/// Queries the shares of a staker and their current value
+pub fn query_staker(deps: Deps, env: Env, address: String) -> StdResult<StakerResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let staker = read_staker(deps.storage, &address)?;
+
+    if staker.shares.is_zero() {
+        return Ok(StakerResponse::default());
+    }
+
+    let (_, total_assets) = query_vault_assets(deps, &env)?;
+    let vault = read_vault(deps.storage)?;
+
+    Ok(StakerResponse {
+        shares: staker.shares,
+        pending_shares: staker.pending_shares,
+        unlock_time: staker.unlock_time,
+        value: staker
+            .shares
+            .checked_mul(total_assets)?
+            .checked_div(vault.total_shares)?,
+    })
+}
+
+/// Queries the sub-fund of a vAMM and how much of the insurance fund covers it
+pub fn query_vamm_coverage(deps: Deps, env: Env, vamm: String) -> StdResult<VammCoverageResponse> {
+    // validate address
+    let vamm_valid = deps.api.addr_validate(&vamm)?;
+
+    let config = read_config(deps.storage)?;
+    let (_, total_assets) = query_vault_assets(deps, &env)?;
+    let vamm_funds = read_vamm_funds(deps.storage)?;
+
+    calculate_vamm_coverage(
+        config.spillover_policy,
+        vamm_valid,
+        total_assets,
+        &vamm_funds,
+    )
+}
+
+/// Queries the coverage of multiple vAMMs
+pub fn query_all_vamm_coverage(
+    deps: Deps,
+    env: Env,
+    limit: Option<u32>,
+) -> StdResult<AllVammCoverageResponse> {
+    // set the limit for pagination
+    let limit = limit
+        .unwrap_or(DEFAULT_PAGINATION_LIMIT)
+        .min(MAX_PAGINATION_LIMIT) as usize;
+
+    let config = read_config(deps.storage)?;
+    let (_, total_assets) = query_vault_assets(deps, &env)?;
+    let vamm_funds = read_vamm_funds(deps.storage)?;
+
+    let mut vamm_coverage: Vec<VammCoverageResponse> = vec![];
+    for vamm in read_vammlist(deps.storage, limit)? {
+        vamm_coverage.push(calculate_vamm_coverage(
+            config.spillover_policy,
+            vamm,
+            total_assets,
+            &vamm_funds,
+        )?);
+    }
+
+    Ok(AllVammCoverageResponse { vamm_coverage })
+}
+
+fn calculate_vamm_coverage(
+    spillover_policy: SpilloverPolicy,
+    vamm: Addr,
+    total_assets: Uint128,
+    vamm_funds: &[(Addr, Uint128)],
+) -> StdResult<VammCoverageResponse> {
+    let total_vamm_funds = sum_vamm_funds(vamm_funds)?;
+    let backstop = total_assets.saturating_sub(total_vamm_funds);
+
+    let sub_fund = vamm_funds
+        .iter()
+        .find(|(addr, _)| addr == &vamm)
+        .map(|(_, amount)| *amount)
+        .unwrap_or_default();
+
+    let coverage = match spillover_policy {
+        SpilloverPolicy::Isolated => sub_fund,
+        SpilloverPolicy::Backstop => sub_fund.checked_add(backstop)?,
+        SpilloverPolicy::Shared => total_vamm_funds.checked_add(backstop)?,
+    };
+
+    Ok(VammCoverageResponse {
+        vamm,
+        sub_fund,
+        backstop,
+        coverage,
+    })
+}
@@ -1,7 +1,11 @@
-use cosmwasm_std::{from_slice, to_vec, Addr, StdError, StdResult, Storage};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, Addr, StdError, StdResult, Storage, Uint128};
 use margined_perp::margined_insurance_fund::ConfigResponse;
 
 pub static KEY_CONFIG: &[u8] = b"config";
+pub static KEY_VAULT: &[u8] = b"vault";
+pub static PREFIX_STAKER: &[u8] = b"staker";
+pub static PREFIX_VAMM_FUND: &[u8] = b"vamm-fund";
 pub const VAMM_LIST: &[u8] = b"vamm-list";
 pub const VAMM_LIMIT: usize = 3usize;
 
@@ -87,3 +91,92 @@ pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
         None => Err(StdError::generic_err("Config not found")),
     }
 }
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Vault {
+    pub total_shares: Uint128,
+    pub protocol_shares: Uint128, // minted for the funds held before the first stake, never unstaked
+}
+
+pub fn store_vault(storage: &mut dyn Storage, vault: &Vault) -> StdResult<()> {
+    Ok(storage.set(KEY_VAULT, &to_vec(vault)?))
+}
+
+pub fn read_vault(storage: &dyn Storage) -> StdResult<Vault> {
+    match storage.get(KEY_VAULT) {
+        Some(data) => from_slice(&data),
+        None => Ok(Vault::default()),
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Staker {
+    pub shares: Uint128, // includes the shares pending unstake
+    pub pending_shares: Uint128,
+    pub unlock_time: u64,
+}
+
+// stakers without any shares are removed
+pub fn store_staker(storage: &mut dyn Storage, address: &Addr, staker: &Staker) -> StdResult<()> {
+    let key = [PREFIX_STAKER, address.as_bytes()].concat();
+
+    if staker.shares.is_zero() {
+        storage.remove(&key);
+        return Ok(());
+    }
+
+    Ok(storage.set(&key, &to_vec(staker)?))
+}
+
+pub fn read_staker(storage: &dyn Storage, address: &Addr) -> StdResult<Staker> {
+    match storage.get(&[PREFIX_STAKER, address.as_bytes()].concat()) {
+        Some(data) => from_slice(&data),
+        None => Ok(Staker::default()),
+    }
+}
+
+// empty sub-funds are removed, their balance is part of the backstop
+pub fn store_vamm_fund(storage: &mut dyn Storage, vamm: &Addr, amount: Uint128) -> StdResult<()> {
+    let key = [PREFIX_VAMM_FUND, vamm.as_bytes()].concat();
+
+    if amount.is_zero() {
+        storage.remove(&key);
+        return Ok(());
+    }
+
+    Ok(storage.set(&key, &to_vec(&amount)?))
+}
+
+pub fn read_vamm_fund(storage: &dyn Storage, vamm: &Addr) -> StdResult<Uint128> {
+    match storage.get(&[PREFIX_VAMM_FUND, vamm.as_bytes()].concat()) {
+        Some(data) => from_slice(&data),
+        None => Ok(Uint128::zero()),
+    }
+}
+
+// returns the sub-funds of all the stored vAMMs, in the order of the vAMM list
+pub fn read_vamm_funds(storage: &dyn Storage) -> StdResult<Vec<(Addr, Uint128)>> {
+    let vamm_list: Vec<Addr> = match storage.get(VAMM_LIST) {
+        None => vec![],
+        Some(data) => from_slice(&data)?,
+    };
+
+    vamm_list
+        .into_iter()
+        .map(|vamm| {
+            let amount = read_vamm_fund(storage, &vamm)?;
+            Ok((vamm, amount))
+        })
+        .collect()
+}
+
+pub fn sum_vamm_funds(vamm_funds: &[(Addr, Uint128)]) -> StdResult<Uint128> {
+    let mut total = Uint128::zero();
+    for (_, amount) in vamm_funds {
+        total = total.checked_add(*amount)?;
+    }
+
+    Ok(total)
+}
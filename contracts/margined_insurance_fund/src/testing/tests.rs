@@ -1,12 +1,21 @@
 use crate::contract::{execute, instantiate, query};
+use crate::state::{read_vamm_fund, save_vamm};
 use crate::testing::new_shutdown_scenario;
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-use cosmwasm_std::{from_binary, Addr, StdError};
+use cosmwasm_std::testing::{
+    mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+};
+use cosmwasm_std::{
+    coins, from_binary, to_binary, Addr, BankMsg, ContractResult, CosmosMsg, OwnedDeps, StdError,
+    SystemResult, Uint128,
+};
+use margined_common::asset::{AssetInfo, NATIVE_DENOM};
+use margined_perp::margined_engine::ConfigResponse as EngineConfigResponse;
 use margined_perp::margined_insurance_fund::{
-    ConfigResponse, ExecuteMsg, InstantiateMsg, OwnerResponse, QueryMsg,
+    ConfigResponse, ExecuteMsg, InstantiateMsg, OwnerResponse, QueryMsg, SpilloverPolicy,
+    StakerResponse, VaultResponse,
 };
 use margined_utils::cw_multi_test::Executor;
-use margined_utils::testing::ShutdownScenario;
+use margined_utils::testing::{parse_event, ShutdownScenario};
 
 const ENGINE: &str = "engine";
 
@@ -25,6 +34,8 @@ fn test_instantiation() {
         config,
         ConfigResponse {
             engine: Addr::unchecked(ENGINE.to_string()),
+            unstake_cooldown: 604_800u64,
+            spillover_policy: SpilloverPolicy::Backstop,
         }
     );
 }
@@ -310,12 +321,12 @@ fn test_off_vamm_off_again() {
     let msg = insurance_fund.shutdown_vamms().unwrap();
     router.execute(owner.clone(), msg).unwrap();
 
-    //turn vamm off again (note the unauthorized error comes from state.open == open)
+    //turn vamm off again (note the error comes from the settlement price already being recorded)
     let msg = insurance_fund.shutdown_vamms().unwrap();
     let err = router.execute(owner, msg).unwrap_err();
     assert_eq!(
         StdError::GenericErr {
-            msg: "unauthorized".to_string(),
+            msg: "amm is already shut down".to_string(),
         },
         err.downcast().unwrap()
     );
@@ -765,3 +776,322 @@ fn test_incompatible_decimals() {
         err.downcast().unwrap()
     );
 }
+
+// instantiates the insurance fund with an engine whose eligible collateral is the native denom
+fn mock_insurance_fund(balance: u128) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        engine: ENGINE.to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    deps.querier.update_wasm(|_| {
+        let config = EngineConfigResponse {
+            owner: Addr::unchecked("addr0000"),
+            insurance_fund: None,
+            fee_pool: Addr::unchecked("fee_pool"),
+            eligible_collateral: AssetInfo::NativeToken {
+                denom: NATIVE_DENOM.to_string(),
+            },
+            decimals: Uint128::from(1_000_000_000u128),
+            initial_margin_ratio: Uint128::zero(),
+            maintenance_margin_ratio: Uint128::zero(),
+            partial_liquidation_ratio: Uint128::zero(),
+            tp_sl_spread: Uint128::zero(),
+            liquidation_fee: Uint128::zero(),
+            pricefeed: None,
+            execution_fee: Uint128::zero(),
+        };
+        SystemResult::Ok(ContractResult::Ok(to_binary(&config).unwrap()))
+    });
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR, coins(balance, NATIVE_DENOM));
+
+    deps
+}
+
+// adds two vamms with a sub-fund of 300 each out of a balance of 1000, leaving 400 in the backstop
+fn mock_sub_funds(
+    spillover_policy: SpilloverPolicy,
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+    let mut deps = mock_insurance_fund(1_000);
+
+    for vamm in ["vamm1", "vamm2"] {
+        save_vamm(deps.as_mut().storage, Addr::unchecked(vamm)).unwrap();
+
+        let msg = ExecuteMsg::FundVamm {
+            vamm: vamm.to_string(),
+            amount: Uint128::from(300u128),
+        };
+        let info = mock_info("addr0000", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    let msg = ExecuteMsg::UpdateSpilloverPolicy { spillover_policy };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    deps
+}
+
+fn withdraw_msg(amount: u128, vamm: Option<&str>) -> ExecuteMsg {
+    ExecuteMsg::Withdraw {
+        token: AssetInfo::NativeToken {
+            denom: NATIVE_DENOM.to_string(),
+        },
+        amount: Uint128::from(amount),
+        vamm: vamm.map(|vamm| vamm.to_string()),
+    }
+}
+
+fn sub_fund(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, vamm: &str) -> Uint128 {
+    read_vamm_fund(&deps.storage, &Addr::unchecked(vamm)).unwrap()
+}
+
+#[test]
+fn test_stake_mints_protocol_shares_for_pre_existing_funds() {
+    // 1000 is held before the first stake, alice's 500 is already part of the balance
+    let mut deps = mock_insurance_fund(1_500);
+
+    let msg = ExecuteMsg::Stake {
+        amount: Uint128::from(500u128),
+    };
+    let info = mock_info("alice", &coins(500, NATIVE_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(deps.as_ref(), mock_env(), QueryMsg::Vault {}).unwrap();
+    let vault: VaultResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        vault,
+        VaultResponse {
+            total_shares: Uint128::from(1_500u128),
+            total_assets: Uint128::from(1_500u128),
+            protocol_shares: Uint128::from(1_000u128),
+        }
+    );
+
+    let msg = QueryMsg::Staker {
+        address: "alice".to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let staker: StakerResponse = from_binary(&res).unwrap();
+    assert_eq!(staker.shares, Uint128::from(500u128));
+    assert_eq!(staker.value, Uint128::from(500u128));
+
+    // after a payout of 300 the shares are worth less, so bob's 300 mints more of them
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR, coins(1_500, NATIVE_DENOM));
+
+    let msg = ExecuteMsg::Stake {
+        amount: Uint128::from(300u128),
+    };
+    let info = mock_info("bob", &coins(300, NATIVE_DENOM));
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    assert_eq!(parse_event(&res, "shares"), "375");
+
+    // sent funds have to match the amount
+    let msg = ExecuteMsg::Stake {
+        amount: Uint128::from(300u128),
+    };
+    let info = mock_info("bob", &coins(200, NATIVE_DENOM));
+    let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Generic error: sent funds do not match the amount"
+    );
+}
+
+#[test]
+fn test_unstake_cooldown() {
+    let mut deps = mock_insurance_fund(1_000);
+
+    let msg = ExecuteMsg::Stake {
+        amount: Uint128::from(1_000u128),
+    };
+    let info = mock_info("alice", &coins(1_000, NATIVE_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("alice", &[]);
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        info.clone(),
+        ExecuteMsg::ClaimUnstake {},
+    )
+    .unwrap_err();
+    assert_eq!(err.to_string(), "Generic error: no pending unstake");
+
+    let msg = ExecuteMsg::Unstake {
+        shares: Uint128::from(1_001u128),
+    };
+    let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+    assert_eq!(err.to_string(), "Generic error: insufficient shares");
+
+    let msg = ExecuteMsg::Unstake {
+        shares: Uint128::from(400u128),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    // the shares can't be claimed until the cooldown has passed
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(604_799);
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::ClaimUnstake {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Generic error: unstake is still cooling down"
+    );
+
+    env.block.time = env.block.time.plus_seconds(1);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::ClaimUnstake {}).unwrap();
+    assert_eq!(
+        res.messages[0].msg,
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: "alice".to_string(),
+            amount: coins(400, NATIVE_DENOM),
+        })
+    );
+
+    let msg = QueryMsg::Staker {
+        address: "alice".to_string(),
+    };
+    let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+    let staker: StakerResponse = from_binary(&res).unwrap();
+    assert_eq!(staker.shares, Uint128::from(600u128));
+    assert_eq!(staker.pending_shares, Uint128::zero());
+}
+
+#[test]
+fn test_claim_unstake_debits_sub_funds_pro_rata() {
+    let mut deps = mock_insurance_fund(1_000);
+
+    let msg = ExecuteMsg::Stake {
+        amount: Uint128::from(1_000u128),
+    };
+    let info = mock_info("alice", &coins(1_000, NATIVE_DENOM));
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    save_vamm(deps.as_mut().storage, Addr::unchecked("vamm1")).unwrap();
+    let msg = ExecuteMsg::FundVamm {
+        vamm: "vamm1".to_string(),
+        amount: Uint128::from(400u128),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let info = mock_info("alice", &[]);
+    let msg = ExecuteMsg::Unstake {
+        shares: Uint128::from(500u128),
+    };
+    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(604_800);
+    let res = execute(deps.as_mut(), env, info, ExecuteMsg::ClaimUnstake {}).unwrap();
+    assert_eq!(parse_event(&res, "amount"), "500");
+
+    // half of the vault is paid out, so half of the sub-fund goes with it
+    assert_eq!(sub_fund(&deps, "vamm1"), Uint128::from(200u128));
+}
+
+#[test]
+fn test_isolated_withdrawal_is_limited_to_the_sub_fund() {
+    let mut deps = mock_sub_funds(SpilloverPolicy::Isolated);
+
+    let info = mock_info(ENGINE, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        withdraw_msg(500, Some("vamm1")),
+    )
+    .unwrap();
+    assert_eq!(parse_event(&res, "amount"), "300");
+    assert_eq!(parse_event(&res, "sub_fund_debit"), "300");
+    assert_eq!(parse_event(&res, "shortfall"), "200");
+
+    assert_eq!(sub_fund(&deps, "vamm1"), Uint128::zero());
+    assert_eq!(sub_fund(&deps, "vamm2"), Uint128::from(300u128));
+}
+
+#[test]
+fn test_backstop_withdrawal_spills_into_the_backstop() {
+    let mut deps = mock_sub_funds(SpilloverPolicy::Backstop);
+
+    let info = mock_info(ENGINE, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        withdraw_msg(500, Some("vamm1")),
+    )
+    .unwrap();
+    assert_eq!(parse_event(&res, "amount"), "500");
+    assert_eq!(parse_event(&res, "sub_fund_debit"), "300");
+    assert_eq!(parse_event(&res, "shortfall"), "0");
+
+    assert_eq!(sub_fund(&deps, "vamm1"), Uint128::zero());
+    assert_eq!(sub_fund(&deps, "vamm2"), Uint128::from(300u128));
+
+    // the sub-funds of other vamms are never used
+    let mut deps = mock_sub_funds(SpilloverPolicy::Backstop);
+
+    let info = mock_info(ENGINE, &[]);
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        withdraw_msg(800, Some("vamm1")),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Generic error: insufficient insurance coverage"
+    );
+}
+
+#[test]
+fn test_shared_withdrawal_spills_into_other_sub_funds() {
+    let mut deps = mock_sub_funds(SpilloverPolicy::Shared);
+
+    let info = mock_info(ENGINE, &[]);
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        info,
+        withdraw_msg(900, Some("vamm1")),
+    )
+    .unwrap();
+    assert_eq!(parse_event(&res, "amount"), "900");
+    assert_eq!(parse_event(&res, "sub_fund_debit"), "500");
+    assert_eq!(parse_event(&res, "shortfall"), "0");
+
+    assert_eq!(sub_fund(&deps, "vamm1"), Uint128::zero());
+    assert_eq!(sub_fund(&deps, "vamm2"), Uint128::from(100u128));
+
+    // withdrawals not made for a vamm can use the whole fund whatever the policy
+    let mut deps = mock_sub_funds(SpilloverPolicy::Isolated);
+
+    let info = mock_info(ENGINE, &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, withdraw_msg(1_000, None)).unwrap();
+    assert_eq!(parse_event(&res, "sub_fund_debit"), "600");
+    assert_eq!(parse_event(&res, "shortfall"), "0");
+
+    assert_eq!(sub_fund(&deps, "vamm1"), Uint128::zero());
+    assert_eq!(sub_fund(&deps, "vamm2"), Uint128::zero());
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+    let info = mock_info(ENGINE, &[]);
+    let err = execute(deps.as_mut(), mock_env(), info, withdraw_msg(1, None)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Generic error: insufficient insurance coverage"
+    );
+}
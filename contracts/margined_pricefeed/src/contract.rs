@@ -1,16 +1,21 @@
 use crate::error::ContractError;
 use crate::{
-    handle::{append_multiple_price, append_price, update_owner},
+    handle::{
+        add_reporter, append_multiple_price, append_price, pull_price, remove_reporter,
+        submit_price, update_config, update_owner, update_price_guard, update_reporter_config,
+    },
     query::{
         query_config, query_get_previous_price, query_get_price, query_get_twap_price, query_owner,
+        query_pending_round, query_price_at, query_price_guard, query_price_history,
+        query_reporters, query_round_data,
     },
-    state::{store_config, Config},
+    state::{migrate_price_data, read_config, store_config, Config},
 };
 use cw2::set_contract_version;
 use cw_controllers::Admin;
 
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, Uint128,
 };
 use margined_perp::margined_pricefeed::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
@@ -20,17 +25,24 @@ const CONTRACT_NAME: &str = "crates.io:margined-pricefeed";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Owner admin
 pub const OWNER: Admin = Admin::new("owner");
+/// Number of rounds kept per key unless configured otherwise
+pub const DEFAULT_RETENTION_ROUNDS: u64 = 10_000u64;
+/// Reporter deviations are ratios with six decimal places
+pub const RATIO_DECIMALS: Uint128 = Uint128::new(1_000_000u128);
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-    let config = Config {};
+    let config = Config {
+        oracle_hub_contract: deps.api.addr_validate(&msg.oracle_hub_contract)?,
+        retention_rounds: DEFAULT_RETENTION_ROUNDS,
+    };
 
     store_config(deps.storage, &config)?;
 
@@ -42,7 +54,7 @@ pub fn instantiate(
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
@@ -51,22 +63,44 @@ pub fn execute(
             key,
             price,
             timestamp,
-        } => append_price(deps, info, key, price, timestamp),
+        } => append_price(deps, env, info, key, price, timestamp),
         ExecuteMsg::AppendMultiplePrice {
             key,
             prices,
             timestamps,
-        } => append_multiple_price(deps, info, key, prices, timestamps),
+        } => append_multiple_price(deps, env, info, key, prices, timestamps),
         ExecuteMsg::UpdateOwner { owner } => update_owner(deps, info, owner),
+        ExecuteMsg::UpdateConfig {
+            oracle_hub_contract,
+            retention_rounds,
+        } => update_config(deps, info, oracle_hub_contract, retention_rounds),
+        ExecuteMsg::PullPrice { key } => pull_price(deps, env, key),
+        ExecuteMsg::SubmitPrice {
+            key,
+            price,
+            timestamp,
+        } => submit_price(deps, env, info, key, price, timestamp),
+        ExecuteMsg::AddReporter { key, reporter } => add_reporter(deps, info, key, reporter),
+        ExecuteMsg::RemoveReporter { key, reporter } => remove_reporter(deps, info, key, reporter),
+        ExecuteMsg::UpdateReporterConfig {
+            key,
+            quorum,
+            max_deviation,
+        } => update_reporter_config(deps, info, key, quorum, max_deviation),
+        ExecuteMsg::UpdatePriceGuard {
+            key,
+            max_age,
+            max_deviation,
+        } => update_price_guard(deps, info, key, max_age, max_deviation),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    let res = match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::GetOwner {} => to_binary(&query_owner(deps)?),
-        QueryMsg::GetPrice { key } => to_binary(&query_get_price(deps, key)?),
+        QueryMsg::GetPrice { key } => to_binary(&query_get_price(deps, env, key)?),
         QueryMsg::GetPreviousPrice {
             key,
             num_round_back,
@@ -74,11 +108,57 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetTwapPrice { key, interval } => {
             to_binary(&query_get_twap_price(deps, env, key, interval)?)
         }
-    }
+        QueryMsg::GetReporters { key } => to_binary(&query_reporters(deps, key)?),
+        QueryMsg::GetPendingRound { key } => to_binary(&query_pending_round(deps, key)?),
+        QueryMsg::GetPriceGuard { key } => to_binary(&query_price_guard(deps, key)?),
+        QueryMsg::GetRoundData { key, round_id } => {
+            to_binary(&query_round_data(deps, key, round_id)?)
+        }
+        QueryMsg::GetPriceAt { key, timestamp } => {
+            to_binary(&query_price_at(deps, key, timestamp)?)
+        }
+        QueryMsg::GetPriceHistory {
+            key,
+            start_after,
+            limit,
+        } => to_binary(&query_price_history(deps, key, start_after, limit)?),
+    }?;
+
+    Ok(res)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // configs stored before the oracle hub integration have no hub address
+    if let Some(oracle_hub_contract) = msg.oracle_hub_contract {
+        let config = Config {
+            oracle_hub_contract: deps.api.addr_validate(&oracle_hub_contract)?,
+            retention_rounds: read_config(deps.storage)
+                .map(|config| config.retention_rounds)
+                .unwrap_or_default(),
+        };
+
+        store_config(deps.storage, &config)?;
+    }
+
+    let mut config = read_config(deps.storage)?;
+    config.retention_rounds = match msg.retention_rounds {
+        Some(0) => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Retention rounds must be greater than zero",
+            )))
+        }
+        Some(retention_rounds) => retention_rounds,
+        // configs stored before retention was configurable keep the default
+        None if config.retention_rounds == 0 => DEFAULT_RETENTION_ROUNDS,
+        None => config.retention_rounds,
+    };
+    store_config(deps.storage, &config)?;
+
+    // prices stored as a single vector per key are split into rounds
+    migrate_price_data(deps.storage)?;
+
     Ok(Response::new())
 }
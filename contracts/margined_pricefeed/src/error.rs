@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{DivideByZeroError, OverflowError, StdError};
 use cw_controllers::AdminError;
 use thiserror::Error;
 
@@ -10,9 +10,24 @@ pub enum ContractError {
     #[error("{0}")]
     Admin(#[from] AdminError),
 
+    #[error("{0}")]
+    Overflow(#[from] OverflowError),
+
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
     #[error("Unable to retrieve price data for key: {0}")]
     NoPriceData(String),
+
+    #[error("Price for key {key} is stale, last updated at {timestamp}")]
+    StalePrice { key: String, timestamp: u64 },
+
+    #[error("Timestamp for key {key} is in the future")]
+    FutureTimestamp { key: String },
+
+    #[error("Timestamp for key {key} is not newer than the latest round")]
+    OutdatedTimestamp { key: String },
 }
@@ -1,6 +1,20 @@
-use cosmwasm_std::{DepsMut, MessageInfo, Response, StdError, Uint128};
+use cosmwasm_std::{
+    Attribute, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage, Timestamp,
+    Uint128,
+};
+use margined_common::validate::validate_ratio;
+use margined_perp::margined_pricefeed::Submission;
 
-use crate::{contract::OWNER, error::ContractError, state::store_price_data};
+use crate::{
+    contract::{OWNER, RATIO_DECIMALS},
+    error::ContractError,
+    oracle::query_oracle_hub_price,
+    state::{
+        read_config, read_latest_round, read_pending_round, read_price_guard, read_reporter_config,
+        read_round_info, remove_pending_round, store_config, store_pending_round, store_price_data,
+        store_price_guard, store_reporter_config, PendingRound,
+    },
+};
 
 pub fn update_owner(
     deps: DepsMut,
@@ -13,11 +27,76 @@ pub fn update_owner(
     Ok(OWNER.execute_update_admin(deps, info, Some(valid_owner))?)
 }
 
-/// this is a mock function that enables storage of data
-/// by the contract owner will be replaced by integration
-/// with on-chain price oracles in the future.
+pub fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    oracle_hub_contract: Option<String>,
+    retention_rounds: Option<u64>,
+) -> Result<Response, ContractError> {
+    // check permission
+    OWNER.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut config = read_config(deps.storage)?;
+
+    if let Some(oracle_hub_contract) = oracle_hub_contract {
+        config.oracle_hub_contract = deps.api.addr_validate(&oracle_hub_contract)?;
+    }
+
+    // rounds outside the window are pruned as new prices come in
+    if let Some(retention_rounds) = retention_rounds {
+        if retention_rounds == 0 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Retention rounds must be greater than zero",
+            )));
+        }
+        config.retention_rounds = retention_rounds;
+    }
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_config"))
+}
+
+/// pulls the latest price from the oracle hub and stores it as a new
+/// round, anyone can call this to keep the price history up to date.
+pub fn pull_price(deps: DepsMut, env: Env, key: String) -> Result<Response, ContractError> {
+    let config = read_config(deps.storage)?;
+
+    let response = query_oracle_hub_price(&deps.querier, &config.oracle_hub_contract, key.clone())
+        .map_err(|_| ContractError::NoPriceData(key.clone()))?;
+
+    if response.price.is_zero() {
+        return Err(ContractError::NoPriceData(key));
+    }
+
+    // only rounds newer than the latest one are stored
+    let latest = read_latest_round(deps.storage, key.clone())?;
+    if response.last_updated <= latest.timestamp.seconds() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Oracle hub price is not newer than the latest round",
+        )));
+    }
+
+    let clamped_price = store_guarded_price_data(
+        deps.storage,
+        &env,
+        key.clone(),
+        response.price,
+        response.last_updated,
+    )?;
+
+    Ok(Response::default()
+        .add_attribute("action", "pull_price")
+        .add_attribute("key", key)
+        .add_attribute("price", response.price.to_string())
+        .add_attributes(clamped_price_attribute(clamped_price)))
+}
+
+/// enables storage of data by the contract owner, this is
+/// the fallback for when the oracle hub has no price for a key.
 pub fn append_price(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     key: String,
     price: Uint128,
@@ -26,16 +105,18 @@ pub fn append_price(
     // check permission
     OWNER.assert_admin(deps.as_ref(), &info.sender)?;
 
-    store_price_data(deps.storage, key, price, timestamp)?;
+    let clamped_price = store_guarded_price_data(deps.storage, &env, key, price, timestamp)?;
 
-    Ok(Response::default().add_attribute("action", "append_price"))
+    Ok(Response::default()
+        .add_attribute("action", "append_price")
+        .add_attributes(clamped_price_attribute(clamped_price)))
 }
 
-/// this is a mock function that enables storage of data
-/// by the contract owner will be replaced by integration
-/// with on-chain price oracles in the future.
+/// enables storage of data by the contract owner, this is
+/// the fallback for when the oracle hub has no price for a key.
 pub fn append_multiple_price(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     key: String,
     prices: Vec<Uint128>,
@@ -51,9 +132,326 @@ pub fn append_multiple_price(
         )));
     }
 
+    let mut response = Response::default().add_attribute("action", "append_multiple_price");
+
     for index in 0..prices.len() {
-        store_price_data(deps.storage, key.clone(), prices[index], timestamps[index])?;
+        let clamped_price = store_guarded_price_data(
+            deps.storage,
+            &env,
+            key.clone(),
+            prices[index],
+            timestamps[index],
+        )?;
+        response = response.add_attributes(clamped_price_attribute(clamped_price));
+    }
+
+    Ok(response)
+}
+
+pub fn add_reporter(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: String,
+    reporter: String,
+) -> Result<Response, ContractError> {
+    // check permission
+    OWNER.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let reporter = deps.api.addr_validate(&reporter)?;
+    let mut reporter_config = read_reporter_config(deps.storage, key.clone())?;
+
+    if reporter_config.reporters.contains(&reporter) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Reporter already exists",
+        )));
+    }
+
+    reporter_config.reporters.push(reporter.clone());
+
+    // the first reporter of a key can finalise rounds alone
+    if reporter_config.quorum == 0 {
+        reporter_config.quorum = 1;
+    }
+
+    store_reporter_config(deps.storage, key.clone(), &reporter_config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "add_reporter")
+        .add_attribute("key", key)
+        .add_attribute("reporter", reporter))
+}
+
+pub fn remove_reporter(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: String,
+    reporter: String,
+) -> Result<Response, ContractError> {
+    // check permission
+    OWNER.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let reporter = deps.api.addr_validate(&reporter)?;
+    let mut reporter_config = read_reporter_config(deps.storage, key.clone())?;
+
+    let index = reporter_config
+        .reporters
+        .iter()
+        .position(|x| x == &reporter)
+        .ok_or_else(|| StdError::generic_err("Reporter not found"))?;
+    reporter_config.reporters.remove(index);
+
+    if reporter_config.quorum > reporter_config.reporters.len() as u64 {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Quorum cannot exceed the number of reporters",
+        )));
     }
 
-    Ok(Response::default().add_attribute("action", "append_multiple_price"))
+    store_reporter_config(deps.storage, key.clone(), &reporter_config)?;
+
+    // drop the removed reporter's submission from the pending round
+    let mut pending_round = read_pending_round(deps.storage, key.clone())?;
+    pending_round.submissions.retain(|x| x.reporter != reporter);
+    store_pending_round(deps.storage, key.clone(), &pending_round)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "remove_reporter")
+        .add_attribute("key", key)
+        .add_attribute("reporter", reporter))
+}
+
+pub fn update_reporter_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: String,
+    quorum: Option<u64>,
+    max_deviation: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    // check permission
+    OWNER.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut reporter_config = read_reporter_config(deps.storage, key.clone())?;
+
+    if let Some(quorum) = quorum {
+        if quorum == 0 || quorum > reporter_config.reporters.len() as u64 {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Quorum must be between one and the number of reporters",
+            )));
+        }
+        reporter_config.quorum = quorum;
+    }
+
+    if let Some(max_deviation) = max_deviation {
+        validate_ratio(max_deviation, RATIO_DECIMALS)?;
+        reporter_config.max_deviation = max_deviation;
+    }
+
+    store_reporter_config(deps.storage, key.clone(), &reporter_config)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "update_reporter_config")
+        .add_attribute("key", key))
+}
+
+pub fn update_price_guard(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: String,
+    max_age: Option<u64>,
+    max_deviation: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    // check permission
+    OWNER.assert_admin(deps.as_ref(), &info.sender)?;
+
+    let mut price_guard = read_price_guard(deps.storage, key.clone())?;
+
+    if let Some(max_age) = max_age {
+        price_guard.max_age = max_age;
+    }
+
+    if let Some(max_deviation) = max_deviation {
+        validate_ratio(max_deviation, RATIO_DECIMALS)?;
+        price_guard.max_deviation = max_deviation;
+    }
+
+    store_price_guard(deps.storage, key.clone(), &price_guard)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "update_price_guard")
+        .add_attribute("key", key))
+}
+
+/// records a reporter's price for the current round, once the quorum is
+/// reached the median of the submissions is stored as the round's price.
+pub fn submit_price(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    key: String,
+    price: Uint128,
+    timestamp: u64,
+) -> Result<Response, ContractError> {
+    let reporter_config = read_reporter_config(deps.storage, key.clone())?;
+
+    if !reporter_config.reporters.contains(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if price.is_zero() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Price cannot be zero",
+        )));
+    }
+
+    require_valid_timestamp(deps.storage, &env, key.clone(), timestamp)?;
+
+    // submissions collected for a round that has since been stored are stale
+    let round_id = read_round_info(deps.storage, key.clone())?.latest_round_id + 1;
+    let mut pending_round = read_pending_round(deps.storage, key.clone())?;
+    if pending_round.round_id != round_id {
+        pending_round = PendingRound {
+            round_id,
+            submissions: vec![],
+        };
+    }
+
+    if pending_round
+        .submissions
+        .iter()
+        .any(|x| x.reporter == info.sender)
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Reporter has already submitted for this round",
+        )));
+    }
+
+    pending_round.submissions.push(Submission {
+        reporter: info.sender,
+        price,
+        timestamp: Timestamp::from_seconds(timestamp),
+    });
+
+    let mut response = Response::default()
+        .add_attribute("action", "submit_price")
+        .add_attribute("key", key.clone())
+        .add_attribute("round_id", round_id.to_string());
+
+    if (pending_round.submissions.len() as u64) < reporter_config.quorum {
+        store_pending_round(deps.storage, key, &pending_round)?;
+
+        return Ok(response);
+    }
+
+    let median = calculate_median(pending_round.submissions.iter().map(|x| x.price).collect())?;
+    let timestamp = pending_round
+        .submissions
+        .iter()
+        .map(|x| x.timestamp.seconds())
+        .max()
+        .unwrap_or_default();
+
+    let clamped_price =
+        store_guarded_price_data(deps.storage, &env, key.clone(), median, timestamp)?;
+    remove_pending_round(deps.storage, key);
+
+    response = response
+        .add_attribute("median_price", median.to_string())
+        .add_attributes(clamped_price_attribute(clamped_price));
+
+    // flag the reporters that strayed too far from the median
+    for submission in pending_round.submissions {
+        if exceeds_max_deviation(submission.price, median, reporter_config.max_deviation)? {
+            response = response.add_attribute("deviated_reporter", submission.reporter);
+        }
+    }
+
+    Ok(response)
+}
+
+/// stores a new round, a price moving too far from the previous round is
+/// clamped to the maximum deviation and returned so that it can be flagged.
+/// later rounds keep moving the price, so a large move is never blocked.
+fn store_guarded_price_data(
+    storage: &mut dyn Storage,
+    env: &Env,
+    key: String,
+    price: Uint128,
+    timestamp: u64,
+) -> Result<Option<Uint128>, ContractError> {
+    require_valid_timestamp(storage, env, key.clone(), timestamp)?;
+
+    let price_guard = read_price_guard(storage, key.clone())?;
+    let latest = read_latest_round(storage, key.clone())?;
+
+    let mut clamped_price = None;
+    if exceeds_max_deviation(price, latest.price, price_guard.max_deviation)? {
+        let max_delta = latest
+            .price
+            .checked_mul(price_guard.max_deviation)?
+            .checked_div(RATIO_DECIMALS)?;
+
+        clamped_price = Some(if price > latest.price {
+            latest.price.checked_add(max_delta)?
+        } else {
+            latest.price.saturating_sub(max_delta)
+        });
+    }
+
+    store_price_data(storage, key, clamped_price.unwrap_or(price), timestamp)?;
+
+    Ok(clamped_price)
+}
+
+fn clamped_price_attribute(clamped_price: Option<Uint128>) -> Vec<Attribute> {
+    clamped_price
+        .map(|price| vec![Attribute::new("clamped_price", price.to_string())])
+        .unwrap_or_default()
+}
+
+/// rounds can't be dated in the future or before the latest round
+fn require_valid_timestamp(
+    storage: &dyn Storage,
+    env: &Env,
+    key: String,
+    timestamp: u64,
+) -> Result<(), ContractError> {
+    if timestamp > env.block.time.seconds() {
+        return Err(ContractError::FutureTimestamp { key });
+    }
+
+    if timestamp <= read_latest_round(storage, key.clone())?.timestamp.seconds() {
+        return Err(ContractError::OutdatedTimestamp { key });
+    }
+
+    Ok(())
+}
+
+fn calculate_median(mut prices: Vec<Uint128>) -> StdResult<Uint128> {
+    prices.sort();
+
+    let mid = prices.len() / 2;
+    if prices.len() % 2 == 1 {
+        return Ok(prices[mid]);
+    }
+
+    Ok(prices[mid - 1]
+        .checked_add(prices[mid])?
+        .checked_div(Uint128::from(2u128))?)
+}
+
+fn exceeds_max_deviation(
+    price: Uint128,
+    median: Uint128,
+    max_deviation: Uint128,
+) -> StdResult<bool> {
+    if max_deviation.is_zero() || median.is_zero() {
+        return Ok(false);
+    }
+
+    let delta = if price > median {
+        price - median
+    } else {
+        median - price
+    };
+
+    Ok(delta.checked_mul(RATIO_DECIMALS)?.checked_div(median)? > max_deviation)
 }
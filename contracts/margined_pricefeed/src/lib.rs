@@ -1,6 +1,7 @@
 pub mod contract;
 mod error;
 mod handle;
+mod oracle;
 mod query;
 mod state;
 
@@ -0,0 +1,11 @@
+use cosmwasm_std::{Addr, QuerierWrapper, StdResult};
+use margined_perp::margined_pricefeed::{OracleHubPriceResponse, OracleHubQueryMsg};
+
+/// Queries the latest price the oracle hub holds for the key
+pub fn query_oracle_hub_price(
+    querier: &QuerierWrapper,
+    oracle_hub: &Addr,
+    key: String,
+) -> StdResult<OracleHubPriceResponse> {
+    querier.query_wasm_smart(oracle_hub, &OracleHubQueryMsg::Price { key })
+}
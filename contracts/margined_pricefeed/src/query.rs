@@ -1,11 +1,22 @@
 use cosmwasm_std::{Deps, Env, StdError, StdResult, Uint128};
-use margined_perp::margined_pricefeed::{ConfigResponse, OwnerResponse};
-
-use crate::{contract::OWNER, state::read_price_data};
+use margined_perp::margined_pricefeed::{
+    ConfigResponse, OwnerResponse, PendingRoundResponse, PriceData, PriceGuardResponse,
+    ReportersResponse,
+};
+
+use crate::{
+    contract::OWNER,
+    error::ContractError,
+    oracle::query_oracle_hub_price,
+    state::{
+        read_config, read_latest_round, read_pending_round, read_price_guard, read_reporter_config,
+        read_round, read_round_info, DEFAULT_LIMIT, MAX_LIMIT,
+    },
+};
 
 /// Queries contract Config
-pub fn query_config(_deps: Deps) -> StdResult<ConfigResponse> {
-    Ok(ConfigResponse {})
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    read_config(deps.storage)
 }
 
 /// Queries contract owner from the admin
@@ -17,15 +28,105 @@ pub fn query_owner(deps: Deps) -> StdResult<OwnerResponse> {
     }
 }
 
-/// Queries latest price for pair stored with key
-pub fn query_get_price(deps: Deps, key: String) -> StdResult<Uint128> {
-    let prices = read_price_data(deps.storage, key)?;
+/// Queries the reporters allowed to submit prices for the key
+pub fn query_reporters(deps: Deps, key: String) -> StdResult<ReportersResponse> {
+    read_reporter_config(deps.storage, key)
+}
+
+/// Queries the submissions collected for the key's next round
+pub fn query_pending_round(deps: Deps, key: String) -> StdResult<PendingRoundResponse> {
+    read_pending_round(deps.storage, key)
+}
+
+/// Queries the staleness and deviation guard of the key
+pub fn query_price_guard(deps: Deps, key: String) -> StdResult<PriceGuardResponse> {
+    read_price_guard(deps.storage, key)
+}
+
+/// Queries latest price for pair, the oracle hub is preferred unless it has
+/// no price for the key or the latest stored round is newer
+pub fn query_get_price(deps: Deps, env: Env, key: String) -> Result<Uint128, ContractError> {
+    let config = read_config(deps.storage)?;
+    let latest_round = read_latest_round(deps.storage, key.clone())?;
+    let latest = (latest_round.price, latest_round.timestamp.seconds());
+
+    let (price, timestamp) =
+        match query_oracle_hub_price(&deps.querier, &config.oracle_hub_contract, key.clone()) {
+            Ok(response) if !response.price.is_zero() && response.last_updated >= latest.1 => {
+                (response.price, response.last_updated)
+            }
+            _ => latest,
+        };
+
+    assert_fresh(deps, &env, key, timestamp)?;
+
+    Ok(price)
+}
+
+/// Queries a retained round of the key
+pub fn query_round_data(deps: Deps, key: String, round_id: u64) -> StdResult<PriceData> {
+    let round_info = read_round_info(deps.storage, key.clone())?;
+
+    if round_id == 0 || round_id < round_info.oldest_round_id {
+        return Err(StdError::generic_err("Round not found"));
+    }
+
+    read_round(deps.storage, key, round_id)?.ok_or_else(|| StdError::generic_err("Round not found"))
+}
 
-    if let Some(price) = prices.last() {
-        return Ok(price.price);
+/// Queries the round in effect at the timestamp, i.e. the last round
+/// stored at or before it, rounds are assumed to be in time order
+pub fn query_price_at(deps: Deps, key: String, timestamp: u64) -> StdResult<PriceData> {
+    let round_info = read_round_info(deps.storage, key.clone())?;
+
+    let mut price_data: Option<PriceData> = None;
+    let mut low = round_info.oldest_round_id.max(1u64);
+    let mut high = round_info.latest_round_id;
+
+    // binary search for the last round at or before the timestamp
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let round = read_round(deps.storage, key.clone(), mid)?
+            .ok_or_else(|| StdError::generic_err("Round not found"))?;
+
+        if round.timestamp.seconds() <= timestamp {
+            price_data = Some(round);
+            low = mid + 1;
+        } else {
+            high = mid - 1;
+        }
     }
 
-    Err(StdError::generic_err("No price found"))
+    price_data.ok_or_else(|| StdError::generic_err("No round at or before the timestamp"))
+}
+
+/// Queries the retained rounds of the key from oldest to latest
+pub fn query_price_history(
+    deps: Deps,
+    key: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<PriceData>> {
+    let round_info = read_round_info(deps.storage, key.clone())?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as u64;
+
+    let start = match start_after {
+        Some(round_id) => round_id + 1,
+        None => 1u64,
+    }
+    .max(round_info.oldest_round_id);
+    let end = round_info
+        .latest_round_id
+        .min(start.saturating_add(limit).saturating_sub(1));
+
+    let mut rounds = vec![];
+    for round_id in start..=end {
+        if let Some(round) = read_round(deps.storage, key.clone(), round_id)? {
+            rounds.push(round);
+        }
+    }
+
+    Ok(rounds)
 }
 
 /// Queries previous price for pair stored with key
@@ -34,12 +135,15 @@ pub fn query_get_previous_price(
     key: String,
     num_round_back: u64,
 ) -> StdResult<Uint128> {
-    let prices = read_price_data(deps.storage, key)?;
-    // prices.sort_by(|a, b| a.round_id.cmp(&b.round_id));
-
-    // check ind to get last previous price ind by num_round_back
-    if let Some(ind) = prices.len().checked_sub((num_round_back + 1) as usize) {
-        return Ok(prices[ind].price);
+    let round_info = read_round_info(deps.storage, key.clone())?;
+
+    if let Some(round_id) = round_info.latest_round_id.checked_sub(num_round_back) {
+        // pruned rounds are no longer available
+        if round_id >= round_info.oldest_round_id {
+            if let Some(price_data) = read_round(deps.storage, key, round_id)? {
+                return Ok(price_data.price);
+            }
+        }
     }
 
     Err(StdError::generic_err("Not enough history"))
@@ -51,33 +155,32 @@ pub fn query_get_twap_price(
     env: Env,
     key: String,
     interval: u64,
-) -> StdResult<Uint128> {
+) -> Result<Uint128, ContractError> {
     if interval == 0 {
-        return Err(StdError::generic_err("Interval can't be zero"));
+        return Err(StdError::generic_err("Interval can't be zero").into());
     }
 
     let base_timestamp = match env.block.time.seconds().checked_sub(interval) {
         Some(val) => Uint128::from(val),
         None => {
-            return Err(StdError::generic_err(
-                "Interval can't be greater than block time",
-            ))
+            return Err(StdError::generic_err("Interval can't be greater than block time").into())
         }
     };
 
-    let prices = read_price_data(deps.storage, key)?;
+    let round_info = read_round_info(deps.storage, key.clone())?;
 
     // get the current data
-    let mut latest_round_ind = prices.len() - 1;
-    let mut latest_round = &prices[latest_round_ind];
+    let latest_round = read_latest_round(deps.storage, key.clone())?;
     let mut timestamp = Uint128::from(latest_round.timestamp.seconds());
 
     if latest_round.round_id == 0u64 {
-        return Err(StdError::generic_err("Insufficient history"));
+        return Err(StdError::generic_err("Insufficient history").into());
     }
 
+    assert_fresh(deps, &env, key.clone(), latest_round.timestamp.seconds())?;
+
     // if latest updated timestamp is earlier than target timestamp, return the latest price.
-    if timestamp < base_timestamp || latest_round.round_id == 1u64 {
+    if timestamp < base_timestamp || latest_round.round_id == round_info.oldest_round_id {
         return Ok(latest_round.price);
     }
 
@@ -85,39 +188,50 @@ pub fn query_get_twap_price(
         Uint128::from(env.block.time.seconds()).checked_sub(Uint128::from(timestamp))?;
     let mut weighted_price = latest_round.price.checked_mul(cumulative_time)?;
 
-    loop {
-        // no more item
-        if latest_round_ind == 0 {
-            break;
-        }
-
-        if latest_round.round_id == 1u64 {
-            let twap = weighted_price.checked_div(cumulative_time)?;
-            return Ok(twap);
-        }
-
-        latest_round_ind -= 1;
-        latest_round = &prices[latest_round_ind];
-        let latest_timestamp = Uint128::from(latest_round.timestamp.seconds());
+    // walk back through the retained rounds
+    for round_id in (round_info.oldest_round_id..latest_round.round_id).rev() {
+        let round = match read_round(deps.storage, key.clone(), round_id)? {
+            Some(round) => round,
+            None => break,
+        };
+        let round_timestamp = Uint128::from(round.timestamp.seconds());
 
         // time to break
-        if latest_timestamp <= base_timestamp {
+        if round_timestamp <= base_timestamp {
             let delta_timestamp = timestamp.checked_sub(base_timestamp)?;
             weighted_price =
-                weighted_price.checked_add(latest_round.price.checked_mul(delta_timestamp)?)?;
+                weighted_price.checked_add(round.price.checked_mul(delta_timestamp)?)?;
 
-            break;
+            let twap = weighted_price.checked_div(Uint128::from(interval))?;
+            return Ok(twap);
         }
 
-        let delta_timestamp = timestamp.checked_sub(latest_timestamp)?;
-        weighted_price =
-            weighted_price.checked_add(latest_round.price.checked_mul(delta_timestamp)?)?;
+        let delta_timestamp = timestamp.checked_sub(round_timestamp)?;
+        weighted_price = weighted_price.checked_add(round.price.checked_mul(delta_timestamp)?)?;
 
         cumulative_time = cumulative_time.checked_add(delta_timestamp)?;
-        timestamp = latest_timestamp;
+        timestamp = round_timestamp;
+    }
+
+    // the history ran out before the interval was covered
+    if cumulative_time.is_zero() {
+        return Ok(latest_round.price);
     }
 
-    let twap = weighted_price.checked_div(Uint128::from(interval))?;
+    let twap = weighted_price.checked_div(cumulative_time)?;
 
     Ok(twap)
 }
+
+/// Errors if the key has a max age and the price is older than it
+fn assert_fresh(deps: Deps, env: &Env, key: String, timestamp: u64) -> Result<(), ContractError> {
+    let price_guard = read_price_guard(deps.storage, key.clone())?;
+
+    if price_guard.max_age != 0
+        && timestamp.saturating_add(price_guard.max_age) < env.block.time.seconds()
+    {
+        return Err(ContractError::StalePrice { key, timestamp });
+    }
+
+    Ok(())
+}
@@ -1,40 +1,231 @@
-use cosmwasm_std::{from_slice, to_vec, StdResult, Storage, Timestamp, Uint128};
-use margined_perp::margined_pricefeed::{ConfigResponse, PriceData};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec, Order, StdError, StdResult, Storage, Timestamp, Uint128};
+use cosmwasm_storage::{Bucket, ReadonlyBucket};
+use margined_perp::margined_pricefeed::{
+    ConfigResponse, PendingRoundResponse, PriceData, PriceGuardResponse, ReportersResponse,
+};
+
+// settings for pagination
+pub const MAX_LIMIT: u32 = 100;
+pub const DEFAULT_LIMIT: u32 = 10;
 
 pub static KEY_CONFIG: &[u8] = b"config";
 
-pub const PRICES: &[u8] = b"prices";
+pub const PRICES: &[u8] = b"prices"; // rounds stored as a single vector before pruning
+pub static PREFIX_ROUNDS: &[u8] = b"rounds";
+pub static PREFIX_ROUND_INFO: &[u8] = b"round_info";
+pub const REPORTERS: &[u8] = b"reporters";
+pub const PENDING_ROUNDS: &[u8] = b"pending_rounds";
+pub const PRICE_GUARDS: &[u8] = b"price_guards";
 
 pub type Config = ConfigResponse;
+pub type ReporterConfig = ReportersResponse;
+pub type PendingRound = PendingRoundResponse;
+pub type PriceGuard = PriceGuardResponse;
+
+/// maximum number of rounds pruned by a single price update
+const MAX_PRUNED_ROUNDS: u64 = 3;
+
+/// The range of rounds retained for a key
+#[cw_serde]
+#[derive(Default)]
+pub struct RoundInfo {
+    pub latest_round_id: u64,
+    pub oldest_round_id: u64,
+}
 
 pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()> {
     Ok(storage.set(KEY_CONFIG, &to_vec(config)?))
 }
 
+pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
+    match storage.get(KEY_CONFIG) {
+        Some(data) => from_slice(&data),
+        None => Err(StdError::generic_err("Config not found")),
+    }
+}
+
 pub fn store_price_data(
     storage: &mut dyn Storage,
     key: String,
     price: Uint128,
     timestamp: u64,
 ) -> StdResult<()> {
-    // load the existing data
-    let mut prices = read_price_data(storage, key.clone())?;
+    let retention_rounds = read_config(storage)?.retention_rounds;
+    let mut round_info = read_round_info(storage, key.clone())?;
+
+    round_info.latest_round_id += 1;
+    if round_info.oldest_round_id == 0 {
+        round_info.oldest_round_id = round_info.latest_round_id;
+    }
 
     let price_data = PriceData {
-        round_id: prices.len() as u64,
+        round_id: round_info.latest_round_id,
         price,
         timestamp: Timestamp::from_seconds(timestamp),
     };
 
-    prices.push(price_data);
+    Bucket::multilevel(storage, &[PREFIX_ROUNDS, key.as_bytes()])
+        .save(&round_info.latest_round_id.to_be_bytes(), &price_data)?;
+
+    prune_rounds(storage, key.clone(), &mut round_info, retention_rounds);
+
+    Bucket::new(storage, PREFIX_ROUND_INFO).save(key.as_bytes(), &round_info)
+}
+
+/// drops the rounds that fell out of the retention window, a few at a time
+/// so that lowering the retention does not make a single update expensive
+fn prune_rounds(
+    storage: &mut dyn Storage,
+    key: String,
+    round_info: &mut RoundInfo,
+    retention_rounds: u64,
+) {
+    if retention_rounds == 0 {
+        return;
+    }
+
+    let mut rounds: Bucket<PriceData> =
+        Bucket::multilevel(storage, &[PREFIX_ROUNDS, key.as_bytes()]);
+
+    let mut pruned = 0u64;
+    while pruned < MAX_PRUNED_ROUNDS
+        && round_info.latest_round_id - round_info.oldest_round_id >= retention_rounds
+    {
+        rounds.remove(&round_info.oldest_round_id.to_be_bytes());
+        round_info.oldest_round_id += 1;
+        pruned += 1;
+    }
+}
+
+pub fn read_round_info(storage: &dyn Storage, key: String) -> StdResult<RoundInfo> {
+    Ok(ReadonlyBucket::new(storage, PREFIX_ROUND_INFO)
+        .may_load(key.as_bytes())?
+        .unwrap_or_default())
+}
+
+/// Reads a round, round zero is the empty round every key starts with
+pub fn read_round(
+    storage: &dyn Storage,
+    key: String,
+    round_id: u64,
+) -> StdResult<Option<PriceData>> {
+    if round_id == 0 {
+        return Ok(Some(PriceData::default()));
+    }
+
+    ReadonlyBucket::multilevel(storage, &[PREFIX_ROUNDS, key.as_bytes()])
+        .may_load(&round_id.to_be_bytes())
+}
+
+pub fn read_latest_round(storage: &dyn Storage, key: String) -> StdResult<PriceData> {
+    let round_info = read_round_info(storage, key.clone())?;
+
+    read_round(storage, key, round_info.latest_round_id)?
+        .ok_or_else(|| StdError::generic_err("Round not found"))
+}
+
+/// moves the prices stored as a single vector per key into individual
+/// rounds, only the rounds inside the retention window are kept
+pub fn migrate_price_data(storage: &mut dyn Storage) -> StdResult<()> {
+    let retention_rounds = read_config(storage)?.retention_rounds;
+
+    let mut end = PRICES.to_vec();
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+
+    let legacy: Vec<(Vec<u8>, Vec<u8>)> = storage
+        .range(Some(PRICES), Some(end.as_slice()), Order::Ascending)
+        .collect();
+
+    for (storage_key, data) in legacy {
+        let key = String::from_utf8(storage_key[PRICES.len()..].to_vec())
+            .map_err(|_| StdError::generic_err("Invalid price key"))?;
+        let prices: Vec<PriceData> = from_slice(&data)?;
+
+        let latest_round_id = prices.last().map(|x| x.round_id).unwrap_or_default();
+        let oldest_round_id = if retention_rounds == 0 {
+            1u64
+        } else {
+            latest_round_id
+                .saturating_sub(retention_rounds - 1)
+                .max(1u64)
+        };
+
+        let mut rounds = Bucket::multilevel(storage, &[PREFIX_ROUNDS, key.as_bytes()]);
+        for price_data in prices.iter().filter(|x| x.round_id >= oldest_round_id) {
+            rounds.save(&price_data.round_id.to_be_bytes(), price_data)?;
+        }
 
-    Ok(storage.set(&[PRICES, key.as_bytes()].concat(), &to_vec(&prices)?))
+        if latest_round_id != 0 {
+            let round_info = RoundInfo {
+                latest_round_id,
+                oldest_round_id,
+            };
+            Bucket::new(storage, PREFIX_ROUND_INFO).save(key.as_bytes(), &round_info)?;
+        }
+
+        storage.remove(&storage_key);
+    }
+
+    Ok(())
+}
+
+pub fn store_reporter_config(
+    storage: &mut dyn Storage,
+    key: String,
+    reporter_config: &ReporterConfig,
+) -> StdResult<()> {
+    Ok(storage.set(
+        &[REPORTERS, key.as_bytes()].concat(),
+        &to_vec(reporter_config)?,
+    ))
+}
+
+pub fn read_reporter_config(storage: &dyn Storage, key: String) -> StdResult<ReporterConfig> {
+    match storage.get(&[REPORTERS, key.as_bytes()].concat()) {
+        None => Ok(ReporterConfig::default()),
+        Some(data) => from_slice(&data),
+    }
+}
+
+pub fn store_pending_round(
+    storage: &mut dyn Storage,
+    key: String,
+    pending_round: &PendingRound,
+) -> StdResult<()> {
+    Ok(storage.set(
+        &[PENDING_ROUNDS, key.as_bytes()].concat(),
+        &to_vec(pending_round)?,
+    ))
+}
+
+pub fn remove_pending_round(storage: &mut dyn Storage, key: String) {
+    storage.remove(&[PENDING_ROUNDS, key.as_bytes()].concat())
+}
+
+pub fn read_pending_round(storage: &dyn Storage, key: String) -> StdResult<PendingRound> {
+    match storage.get(&[PENDING_ROUNDS, key.as_bytes()].concat()) {
+        None => Ok(PendingRound::default()),
+        Some(data) => from_slice(&data),
+    }
+}
+
+pub fn store_price_guard(
+    storage: &mut dyn Storage,
+    key: String,
+    price_guard: &PriceGuard,
+) -> StdResult<()> {
+    Ok(storage.set(
+        &[PRICE_GUARDS, key.as_bytes()].concat(),
+        &to_vec(price_guard)?,
+    ))
 }
 
-pub fn read_price_data(storage: &dyn Storage, key: String) -> StdResult<Vec<PriceData>> {
-    // let prices = ReadonlyBucket::new(storage, PRICES).may_load(key.as_bytes())?;
-    match storage.get(&[PRICES, key.as_bytes()].concat()) {
-        None => Ok(vec![PriceData::default()]),
+pub fn read_price_guard(storage: &dyn Storage, key: String) -> StdResult<PriceGuard> {
+    match storage.get(&[PRICE_GUARDS, key.as_bytes()].concat()) {
+        None => Ok(PriceGuard::default()),
         Some(data) => from_slice(&data),
     }
 }
@@ -0,0 +1,157 @@
+use crate::contract::{execute, instantiate, query};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_binary, Timestamp, Uint128};
+use margined_perp::margined_pricefeed::{ExecuteMsg, InstantiateMsg, PriceData, QueryMsg};
+
+#[test]
+fn test_get_round_data_and_price_at() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::AppendMultiplePrice {
+        key: "ETHUSD".to_string(),
+        prices: vec![
+            Uint128::from(500_000_000u128),
+            Uint128::from(600_000_000u128),
+            Uint128::from(700_000_000u128),
+            Uint128::from(600_000_000u128),
+            Uint128::from(670_000_000u128),
+        ],
+        timestamps: vec![1_000_000, 1_000_010, 1_000_020, 1_000_030, 1_000_040],
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetRoundData {
+            key: "ETHUSD".to_string(),
+            round_id: 2u64,
+        },
+    )
+    .unwrap();
+    let round: PriceData = from_binary(&res).unwrap();
+    assert_eq!(
+        round,
+        PriceData {
+            round_id: 2u64,
+            price: Uint128::from(600_000_000u128),
+            timestamp: Timestamp::from_seconds(1_000_010),
+        }
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetRoundData {
+            key: "ETHUSD".to_string(),
+            round_id: 6u64,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Generic error: Round not found"
+    );
+
+    // the round stored at 1_000_020 is in effect until the next one
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPriceAt {
+            key: "ETHUSD".to_string(),
+            timestamp: 1_000_025,
+        },
+    )
+    .unwrap();
+    let round: PriceData = from_binary(&res).unwrap();
+    assert_eq!(round.round_id, 3u64);
+    assert_eq!(round.price, Uint128::from(700_000_000u128));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPriceAt {
+            key: "ETHUSD".to_string(),
+            timestamp: 2_000_000,
+        },
+    )
+    .unwrap();
+    let round: PriceData = from_binary(&res).unwrap();
+    assert_eq!(round.round_id, 5u64);
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPriceAt {
+            key: "ETHUSD".to_string(),
+            timestamp: 999_999,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Generic error: No round at or before the timestamp"
+    );
+}
+
+#[test]
+fn test_get_price_history() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::AppendMultiplePrice {
+        key: "ETHUSD".to_string(),
+        prices: vec![
+            Uint128::from(500_000_000u128),
+            Uint128::from(600_000_000u128),
+            Uint128::from(700_000_000u128),
+        ],
+        timestamps: vec![1_000_000, 1_000_010, 1_000_020],
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPriceHistory {
+            key: "ETHUSD".to_string(),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    let rounds: Vec<PriceData> = from_binary(&res).unwrap();
+    assert_eq!(
+        rounds.iter().map(|x| x.round_id).collect::<Vec<u64>>(),
+        vec![1u64, 2u64, 3u64]
+    );
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPriceHistory {
+            key: "ETHUSD".to_string(),
+            start_after: Some(1u64),
+            limit: Some(1u32),
+        },
+    )
+    .unwrap();
+    let rounds: Vec<PriceData> = from_binary(&res).unwrap();
+    assert_eq!(
+        rounds,
+        vec![PriceData {
+            round_id: 2u64,
+            price: Uint128::from(600_000_000u128),
+            timestamp: Timestamp::from_seconds(1_000_010),
+        }]
+    );
+}
@@ -1 +1,5 @@
+mod history_tests;
+mod oracle_hub_tests;
+mod reporter_tests;
+mod retention_tests;
 mod tests;
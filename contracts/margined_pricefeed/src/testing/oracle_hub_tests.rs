@@ -0,0 +1,163 @@
+use cosmwasm_std::Addr;
+use margined_perp::margined_pricefeed::InstantiateMsg;
+use margined_utils::{
+    contracts::helpers::PricefeedController,
+    create_entry_points_testing,
+    cw_multi_test::{App, Executor},
+    testing::to_decimals,
+};
+use mock_oracle_hub::contract::{
+    ExecuteMsg as OracleHubExecuteMsg, InstantiateMsg as OracleHubInstantiateMsg,
+};
+
+// instantiates the mock oracle hub and a pricefeed pulling from it
+fn setup() -> (App, Addr, Addr, PricefeedController) {
+    let mut router = App::default();
+    let owner = Addr::unchecked("owner");
+
+    let oracle_hub_id = router.store_code(Box::new(create_entry_points_testing!(mock_oracle_hub)));
+    let oracle_hub = router
+        .instantiate_contract(
+            oracle_hub_id,
+            owner.clone(),
+            &OracleHubInstantiateMsg {},
+            &[],
+            "oracle_hub",
+            None,
+        )
+        .unwrap();
+
+    let pricefeed_id = router.store_code(Box::new(create_entry_points_testing!(crate)));
+    let pricefeed_addr = router
+        .instantiate_contract(
+            pricefeed_id,
+            owner.clone(),
+            &InstantiateMsg {
+                oracle_hub_contract: oracle_hub.to_string(),
+            },
+            &[],
+            "pricefeed",
+            None,
+        )
+        .unwrap();
+
+    (
+        router,
+        owner,
+        oracle_hub,
+        PricefeedController(pricefeed_addr),
+    )
+}
+
+#[test]
+fn test_get_price_prefers_oracle_hub() {
+    let (mut router, owner, oracle_hub, pricefeed) = setup();
+    let now = router.block_info().time.seconds();
+
+    let config = pricefeed.config(&router.wrap()).unwrap();
+    assert_eq!(config.oracle_hub_contract, oracle_hub);
+
+    // the hub has no price so the owner pushed price is used
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(10u64), now - 100)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let price = pricefeed
+        .get_price(&router.wrap(), "ETH".to_string())
+        .unwrap();
+    assert_eq!(price, to_decimals(10u64));
+
+    router
+        .execute_contract(
+            owner.clone(),
+            oracle_hub,
+            &OracleHubExecuteMsg::SetPrice {
+                key: "ETH".to_string(),
+                price: to_decimals(12u64),
+                last_updated: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    let price = pricefeed
+        .get_price(&router.wrap(), "ETH".to_string())
+        .unwrap();
+    assert_eq!(price, to_decimals(12u64));
+
+    // a newer owner pushed price takes over from a stale hub
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(11u64), now + 100)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let price = pricefeed
+        .get_price(&router.wrap(), "ETH".to_string())
+        .unwrap();
+    assert_eq!(price, to_decimals(11u64));
+}
+
+#[test]
+fn test_pull_price_from_oracle_hub() {
+    let (mut router, owner, oracle_hub, pricefeed) = setup();
+    let alice = Addr::unchecked("alice");
+
+    // the hub has no price for the key
+    let msg = pricefeed.pull_price("ETH".to_string()).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Unable to retrieve price data for key: ETH"
+    );
+
+    router
+        .execute_contract(
+            owner.clone(),
+            oracle_hub.clone(),
+            &OracleHubExecuteMsg::SetPrice {
+                key: "ETH".to_string(),
+                price: to_decimals(12u64),
+                last_updated: None,
+            },
+            &[],
+        )
+        .unwrap();
+
+    // anyone can pull the price into the history
+    let msg = pricefeed.pull_price("ETH".to_string()).unwrap();
+    router.execute(alice.clone(), msg).unwrap();
+
+    let price = pricefeed
+        .get_previous_price(&router.wrap(), "ETH".to_string(), 0)
+        .unwrap();
+    assert_eq!(price, to_decimals(12u64));
+
+    let msg = pricefeed.pull_price("ETH".to_string()).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Oracle hub price is not newer than the latest round"
+    );
+
+    // only the owner can change the oracle hub
+    let msg = pricefeed
+        .update_config(Some("new_oracle_hub".to_string()), None)
+        .unwrap();
+    let err = router.execute(alice, msg.clone()).unwrap_err();
+    assert_eq!(err.source().unwrap().to_string(), "Caller is not admin");
+
+    router.execute(owner, msg).unwrap();
+
+    let config = pricefeed.config(&router.wrap()).unwrap();
+    assert_eq!(
+        config.oracle_hub_contract,
+        Addr::unchecked("new_oracle_hub")
+    );
+
+    // the old round is still served when the hub cannot be reached
+    let price = pricefeed
+        .get_price(&router.wrap(), "ETH".to_string())
+        .unwrap();
+    assert_eq!(price, to_decimals(12u64));
+}
@@ -0,0 +1,285 @@
+use crate::contract::{execute, instantiate, query};
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_binary, Addr, Uint128};
+use margined_perp::margined_pricefeed::{
+    ExecuteMsg, InstantiateMsg, PendingRoundResponse, QueryMsg, ReportersResponse,
+};
+
+#[test]
+fn test_manage_reporters() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // only the owner manages reporters
+    let msg = ExecuteMsg::AddReporter {
+        key: "ETHUSD".to_string(),
+        reporter: "reporter0000".to_string(),
+    };
+    let info = mock_info("not_owner", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(result.to_string(), "Caller is not admin");
+
+    for reporter in ["reporter0000", "reporter0001", "reporter0002"] {
+        let msg = ExecuteMsg::AddReporter {
+            key: "ETHUSD".to_string(),
+            reporter: reporter.to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    let msg = ExecuteMsg::AddReporter {
+        key: "ETHUSD".to_string(),
+        reporter: "reporter0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(result.to_string(), "Generic error: Reporter already exists");
+
+    // quorum cannot exceed the reporter set
+    let msg = ExecuteMsg::UpdateReporterConfig {
+        key: "ETHUSD".to_string(),
+        quorum: Some(4u64),
+        max_deviation: None,
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Generic error: Quorum must be between one and the number of reporters"
+    );
+
+    let msg = ExecuteMsg::UpdateReporterConfig {
+        key: "ETHUSD".to_string(),
+        quorum: Some(2u64),
+        max_deviation: Some(Uint128::from(50_000u128)), // 5%
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::RemoveReporter {
+        key: "ETHUSD".to_string(),
+        reporter: "reporter0002".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetReporters {
+            key: "ETHUSD".to_string(),
+        },
+    )
+    .unwrap();
+    let reporters: ReportersResponse = from_binary(&res).unwrap();
+    assert_eq!(
+        reporters,
+        ReportersResponse {
+            reporters: vec![
+                Addr::unchecked("reporter0000"),
+                Addr::unchecked("reporter0001"),
+            ],
+            quorum: 2u64,
+            max_deviation: Uint128::from(50_000u128),
+        }
+    );
+
+    // removing another reporter would make the quorum unreachable
+    let msg = ExecuteMsg::RemoveReporter {
+        key: "ETHUSD".to_string(),
+        reporter: "reporter0001".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Generic error: Quorum cannot exceed the number of reporters"
+    );
+}
+
+#[test]
+fn test_submit_price_stores_median() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    for reporter in ["reporter0000", "reporter0001", "reporter0002"] {
+        let msg = ExecuteMsg::AddReporter {
+            key: "ETHUSD".to_string(),
+            reporter: reporter.to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    let msg = ExecuteMsg::UpdateReporterConfig {
+        key: "ETHUSD".to_string(),
+        quorum: Some(3u64),
+        max_deviation: Some(Uint128::from(50_000u128)), // 5%
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // even the owner has to be a reporter
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(100_000_000u128),
+        timestamp: 1_000_000,
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(result.to_string(), "Unauthorized");
+
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(100_000_000u128),
+        timestamp: 1_000_000,
+    };
+    let info = mock_info("reporter0000", &[]);
+    execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Generic error: Reporter has already submitted for this round"
+    );
+
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(102_000_000u128),
+        timestamp: 1_000_001,
+    };
+    let info = mock_info("reporter0001", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPendingRound {
+            key: "ETHUSD".to_string(),
+        },
+    )
+    .unwrap();
+    let pending_round: PendingRoundResponse = from_binary(&res).unwrap();
+    assert_eq!(pending_round.round_id, 1u64);
+    assert_eq!(pending_round.submissions.len(), 2usize);
+
+    // the quorum is reached and the outlier is flagged
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(120_000_000u128),
+        timestamp: 1_000_002,
+    };
+    let info = mock_info("reporter0002", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let median = res
+        .attributes
+        .iter()
+        .find(|attr| attr.key == "median_price")
+        .unwrap();
+    assert_eq!(median.value, "102000000");
+
+    let deviated: Vec<String> = res
+        .attributes
+        .iter()
+        .filter(|attr| attr.key == "deviated_reporter")
+        .map(|attr| attr.value.clone())
+        .collect();
+    assert_eq!(deviated, vec!["reporter0002".to_string()]);
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 0,
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(102_000_000u128));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPendingRound {
+            key: "ETHUSD".to_string(),
+        },
+    )
+    .unwrap();
+    let pending_round: PendingRoundResponse = from_binary(&res).unwrap();
+    assert!(pending_round.submissions.is_empty());
+}
+
+#[test]
+fn test_submit_price_rejects_invalid_timestamps() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::AddReporter {
+        key: "ETHUSD".to_string(),
+        reporter: "reporter0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let now = mock_env().block.time.seconds();
+
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(100_000_000u128),
+        timestamp: now + 1,
+    };
+    let info = mock_info("reporter0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Timestamp for key ETHUSD is in the future"
+    );
+
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(100_000_000u128),
+        timestamp: now,
+    };
+    let info = mock_info("reporter0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // the next round has to be newer than the one just stored
+    let msg = ExecuteMsg::SubmitPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(101_000_000u128),
+        timestamp: now,
+    };
+    let info = mock_info("reporter0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Timestamp for key ETHUSD is not newer than the latest round"
+    );
+
+    let msg = ExecuteMsg::AppendPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(101_000_000u128),
+        timestamp: now - 1,
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Timestamp for key ETHUSD is not newer than the latest round"
+    );
+}
@@ -0,0 +1,162 @@
+use crate::contract::{execute, instantiate, migrate, query};
+use crate::state::PRICES;
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{from_binary, to_vec, Timestamp, Uint128};
+use margined_perp::margined_pricefeed::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, PriceData, QueryMsg,
+};
+
+#[test]
+fn test_rounds_outside_retention_are_pruned() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::UpdateConfig {
+        oracle_hub_contract: None,
+        retention_rounds: Some(0u64),
+    };
+    let info = mock_info("addr0000", &[]);
+    let result = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+    assert_eq!(
+        result.to_string(),
+        "Generic error: Retention rounds must be greater than zero"
+    );
+
+    let msg = ExecuteMsg::UpdateConfig {
+        oracle_hub_contract: None,
+        retention_rounds: Some(3u64),
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::AppendMultiplePrice {
+        key: "ETHUSD".to_string(),
+        prices: vec![
+            Uint128::from(500_000_000u128),
+            Uint128::from(600_000_000u128),
+            Uint128::from(700_000_000u128),
+            Uint128::from(600_000_000u128),
+            Uint128::from(670_000_000u128),
+        ],
+        timestamps: vec![1_000_000, 1_000_001, 1_000_002, 1_000_003, 1_000_004],
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // only the last three rounds are kept
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 2u64,
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(700_000_000u128));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 3u64,
+        },
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Generic error: Not enough history"
+    );
+}
+
+#[test]
+fn test_migrate_legacy_price_data() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // prices used to be stored as a single vector per key
+    let mut prices = vec![PriceData::default()];
+    for (round_id, price) in [500_000_000u128, 600_000_000, 700_000_000, 800_000_000]
+        .into_iter()
+        .enumerate()
+    {
+        prices.push(PriceData {
+            round_id: round_id as u64 + 1,
+            price: Uint128::from(price),
+            timestamp: Timestamp::from_seconds(1_000_000 + round_id as u64),
+        });
+    }
+    let storage_key = [PRICES, "ETHUSD".as_bytes()].concat();
+    deps.storage.set(&storage_key, &to_vec(&prices).unwrap());
+
+    let msg = MigrateMsg {
+        oracle_hub_contract: None,
+        retention_rounds: Some(2u64),
+    };
+    migrate(deps.as_mut(), mock_env(), msg).unwrap();
+
+    assert_eq!(deps.storage.get(&storage_key), None);
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPrice {
+            key: "ETHUSD".to_string(),
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(800_000_000u128));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 1u64,
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(700_000_000u128));
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 2u64,
+        },
+    );
+    assert!(res.is_err());
+
+    // new rounds continue from the migrated history
+    let msg = ExecuteMsg::AppendPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(900_000_000u128),
+        timestamp: 1_000_010,
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPreviousPrice {
+            key: "ETHUSD".to_string(),
+            num_round_back: 1u64,
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(800_000_000u128));
+}
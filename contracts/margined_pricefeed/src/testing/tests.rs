@@ -16,7 +16,13 @@ fn test_instantiation() {
 
     let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
     let config: ConfigResponse = from_binary(&res).unwrap();
-    assert_eq!(config, ConfigResponse {});
+    assert_eq!(
+        config,
+        ConfigResponse {
+            oracle_hub_contract: Addr::unchecked("oracle_hub0000"),
+            retention_rounds: 10_000u64,
+        }
+    );
 }
 
 #[test]
@@ -482,3 +488,64 @@ fn test_get_twap_error_zero_interval() {
     );
     assert!(res.is_err());
 }
+
+#[test]
+fn test_deviating_price_is_clamped() {
+    let mut deps = mock_dependencies();
+    let msg = InstantiateMsg {
+        oracle_hub_contract: "oracle_hub0000".to_string(),
+    };
+    let info = mock_info("addr0000", &[]);
+    instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::UpdatePriceGuard {
+        key: "ETHUSD".to_string(),
+        max_age: None,
+        max_deviation: Some(Uint128::from(100_000u128)), // 10%
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let msg = ExecuteMsg::AppendPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(100_000_000u128),
+        timestamp: 1_000_000,
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    // a 50% move is clamped to 10% and flagged rather than rejected
+    let msg = ExecuteMsg::AppendPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(150_000_000u128),
+        timestamp: 1_000_001,
+    };
+    let info = mock_info("addr0000", &[]);
+    let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    let clamped = res
+        .attributes
+        .iter()
+        .find(|attr| attr.key == "clamped_price")
+        .unwrap();
+    assert_eq!(clamped.value, "110000000");
+
+    // following rounds keep moving towards the new price
+    let msg = ExecuteMsg::AppendPrice {
+        key: "ETHUSD".to_string(),
+        price: Uint128::from(150_000_000u128),
+        timestamp: 1_000_002,
+    };
+    let info = mock_info("addr0000", &[]);
+    execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+    let res = query(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::GetPrice {
+            key: "ETHUSD".to_string(),
+        },
+    )
+    .unwrap();
+    let price: Uint128 = from_binary(&res).unwrap();
+    assert_eq!(price, Uint128::from(121_000_000u128));
+}
@@ -18,11 +18,16 @@ use crate::{
     utils::{TwapCalcOption, TwapInputAsset},
 };
 use crate::{
-    handle::{set_open, settle_funding, swap_input, swap_output, update_config, update_owner},
+    handle::{
+        adjust_k, repeg, set_open, settle_funding, shutdown, swap_input, swap_output,
+        update_config, update_funding_config, update_owner, update_rolling_fluctuation_limit,
+        update_spread_config,
+    },
     query::{
-        query_calc_fee, query_config, query_input_amount, query_input_price,
-        query_is_over_fluctuation_limit, query_is_over_spread_limit, query_output_amount,
-        query_output_price, query_owner, query_spot_price, query_state, query_twap_price,
+        query_calc_fee, query_config, query_funding_history, query_funding_index,
+        query_funding_rate, query_input_amount, query_input_price, query_is_over_fluctuation_limit,
+        query_is_over_spread_limit, query_is_reduce_only, query_output_amount, query_output_price,
+        query_owner, query_settlement_price, query_spot_price, query_state, query_twap_price,
     },
     state::{store_config, store_reserve_snapshot, store_state, Config, ReserveSnapshot, State},
 };
@@ -39,6 +44,9 @@ pub const ONE_HOUR_IN_SECONDS: u64 = 60 * 60;
 pub const ONE_DAY_IN_SECONDS: u64 = 24 * 60 * 60;
 pub const ONE_WEEK_IN_SECONDS: u64 = 7 * 24 * 60 * 60;
 
+/// Most reserve snapshots walked back when checking the rolling fluctuation limit
+pub const MAX_ROLLING_SNAPSHOTS: u64 = 100;
+
 const FIFTEEN_MINUTES: u64 = 15 * 60;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -74,6 +82,14 @@ pub fn instantiate(
         decimals,
         spot_price_twap_interval: ONE_HOUR_IN_SECONDS,
         funding_period: msg.funding_period,
+        max_funding_rate: Uint128::zero(),
+        funding_interest_rate: Uint128::zero(),
+        funding_dampening_ratio: Uint128::zero(),
+        continuous_funding: false,
+        dynamic_spread: false,
+        max_spread_ratio: Uint128::zero(),
+        rolling_fluctuation_limit_ratio: Uint128::zero(),
+        rolling_fluctuation_window: 0u64,
     };
 
     // set and update margin engine
@@ -152,6 +168,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             quote_asset_amount,
             can_go_over_fluctuation,
             base_asset_limit,
+            reduces_position,
         } => swap_input(
             deps,
             env,
@@ -161,6 +178,7 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             quote_asset_amount,
             base_asset_limit,
             can_go_over_fluctuation,
+            reduces_position,
         ),
         ExecuteMsg::SwapOutput {
             direction,
@@ -176,8 +194,38 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
             base_asset_amount,
             quote_asset_limit,
         ),
+        ExecuteMsg::UpdateFundingConfig {
+            max_funding_rate,
+            funding_interest_rate,
+            funding_dampening_ratio,
+            continuous_funding,
+        } => update_funding_config(
+            deps,
+            env,
+            info,
+            max_funding_rate,
+            funding_interest_rate,
+            funding_dampening_ratio,
+            continuous_funding,
+        ),
+        ExecuteMsg::UpdateSpreadConfig {
+            dynamic_spread,
+            max_spread_ratio,
+        } => update_spread_config(deps, info, dynamic_spread, max_spread_ratio),
+        ExecuteMsg::UpdateRollingFluctuationLimit {
+            rolling_fluctuation_limit_ratio,
+            rolling_fluctuation_window,
+        } => update_rolling_fluctuation_limit(
+            deps,
+            info,
+            rolling_fluctuation_limit_ratio,
+            rolling_fluctuation_window,
+        ),
         ExecuteMsg::SettleFunding {} => settle_funding(deps, env, info),
+        ExecuteMsg::Repeg { target_price } => repeg(deps, env, info, target_price),
+        ExecuteMsg::AdjustK { scale } => adjust_k(deps, env, info, scale),
         ExecuteMsg::SetOpen { open } => set_open(deps, env, info, open),
+        ExecuteMsg::Shutdown {} => shutdown(deps, env, info),
     }
 }
 
@@ -235,9 +283,10 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
                 interval,
             )?)
         }
-        QueryMsg::CalcFee { quote_asset_amount } => {
-            to_binary(&query_calc_fee(deps, quote_asset_amount)?)
-        }
+        QueryMsg::CalcFee {
+            quote_asset_amount,
+            direction,
+        } => to_binary(&query_calc_fee(deps, quote_asset_amount, direction)?),
         QueryMsg::SpotPrice {} => to_binary(&query_spot_price(deps)?),
         QueryMsg::TwapPrice { interval } => to_binary(&query_twap_price(
             deps,
@@ -247,6 +296,8 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             None,
         )?),
         QueryMsg::IsOverSpreadLimit {} => to_binary(&query_is_over_spread_limit(deps)?),
+        QueryMsg::IsReduceOnly {} => to_binary(&query_is_reduce_only(deps, env)?),
+        QueryMsg::SettlementPrice {} => to_binary(&query_settlement_price(deps)?),
         QueryMsg::IsOverFluctuationLimit {
             direction,
             base_asset_amount,
@@ -256,6 +307,11 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             direction,
             base_asset_amount,
         )?),
+        QueryMsg::FundingRate {} => to_binary(&query_funding_rate(deps)?),
+        QueryMsg::FundingIndex {} => to_binary(&query_funding_index(deps, env)?),
+        QueryMsg::FundingHistory { start_after, limit } => {
+            to_binary(&query_funding_history(deps, start_after, limit)?)
+        }
     }
 }
 
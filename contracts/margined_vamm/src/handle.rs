@@ -3,7 +3,7 @@ use cosmwasm_std::{
 };
 
 use margined_common::{integer::Integer, validate::validate_ratio};
-use margined_perp::margined_vamm::Direction;
+use margined_perp::margined_vamm::{Direction, FundingRecord};
 use margined_utils::contracts::helpers::PricefeedController;
 
 use crate::{
@@ -11,10 +11,15 @@ use crate::{
         ONE_DAY_IN_SECONDS, ONE_HOUR_IN_SECONDS, ONE_MINUTE_IN_SECONDS, ONE_WEEK_IN_SECONDS, OWNER,
     },
     query::query_twap_price,
-    state::{read_config, read_state, store_config, store_state, Config},
+    state::{
+        read_config, read_funding_index, read_funding_record_counter, read_settlement_price,
+        read_state, store_config, store_funding_index, store_funding_record,
+        store_reduce_only_until, store_settlement_price, store_state, Config, State,
+    },
     utils::{
-        add_reserve_snapshot, check_is_over_block_fluctuation_limit, require_margin_engine,
-        require_open, TwapCalcOption,
+        add_reserve_snapshot, check_is_over_block_fluctuation_limit,
+        check_is_over_rolling_fluctuation_limit, require_margin_engine,
+        require_not_increasing_exposure, require_open, TwapCalcOption,
     },
 };
 
@@ -96,6 +101,135 @@ pub fn update_config(
     Ok(Response::default().add_attribute("action", "update_config"))
 }
 
+/// updates the cap, interest and dampening applied to the funding rate of each period
+/// and switches between discrete and continuous funding
+pub fn update_funding_config(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    max_funding_rate: Option<Uint128>,
+    funding_interest_rate: Option<Uint128>,
+    funding_dampening_ratio: Option<Uint128>,
+    continuous_funding: Option<bool>,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    // funding accrued so far follows the previous parameters
+    accrue_funding(deps.branch(), &env)?;
+
+    let mut config: Config = read_config(deps.storage)?;
+
+    if let Some(max_funding_rate) = max_funding_rate {
+        validate_ratio(max_funding_rate, config.decimals)?;
+        config.max_funding_rate = max_funding_rate;
+    }
+
+    if let Some(funding_interest_rate) = funding_interest_rate {
+        validate_ratio(funding_interest_rate, config.decimals)?;
+        config.funding_interest_rate = funding_interest_rate;
+    }
+
+    if let Some(funding_dampening_ratio) = funding_dampening_ratio {
+        validate_ratio(funding_dampening_ratio, config.decimals)?;
+        config.funding_dampening_ratio = funding_dampening_ratio;
+    }
+
+    // the interest alone cannot be over the cap
+    if !config.max_funding_rate.is_zero() && config.funding_interest_rate > config.max_funding_rate
+    {
+        return Err(StdError::generic_err(
+            "funding interest rate cannot exceed the max funding rate",
+        ));
+    }
+
+    if let Some(continuous_funding) = continuous_funding {
+        config.continuous_funding = continuous_funding;
+    }
+
+    store_config(deps.storage, &config)?;
+
+    accrue_funding(deps, &env)?;
+
+    Ok(Response::default().add_attribute("action", "update_funding_config"))
+}
+
+pub fn update_spread_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    dynamic_spread: Option<bool>,
+    max_spread_ratio: Option<Uint128>,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut config: Config = read_config(deps.storage)?;
+
+    if let Some(dynamic_spread) = dynamic_spread {
+        config.dynamic_spread = dynamic_spread;
+    }
+
+    if let Some(max_spread_ratio) = max_spread_ratio {
+        validate_ratio(max_spread_ratio, config.decimals)?;
+        config.max_spread_ratio = max_spread_ratio;
+    }
+
+    // the dynamic spread is unbounded without a cap
+    if config.dynamic_spread && config.max_spread_ratio.is_zero() {
+        return Err(StdError::generic_err(
+            "max spread ratio must be set to enable the dynamic spread",
+        ));
+    }
+
+    // the cap cannot undercut the flat spread
+    if !config.max_spread_ratio.is_zero() && config.max_spread_ratio < config.spread_ratio {
+        return Err(StdError::generic_err(
+            "max spread ratio cannot be below the spread ratio",
+        ));
+    }
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_spread_config"))
+}
+
+pub fn update_rolling_fluctuation_limit(
+    deps: DepsMut,
+    info: MessageInfo,
+    rolling_fluctuation_limit_ratio: Option<Uint128>,
+    rolling_fluctuation_window: Option<u64>,
+) -> StdResult<Response> {
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let mut config: Config = read_config(deps.storage)?;
+
+    if let Some(rolling_fluctuation_limit_ratio) = rolling_fluctuation_limit_ratio {
+        validate_ratio(rolling_fluctuation_limit_ratio, config.decimals)?;
+        config.rolling_fluctuation_limit_ratio = rolling_fluctuation_limit_ratio;
+    }
+
+    if let Some(rolling_fluctuation_window) = rolling_fluctuation_window {
+        config.rolling_fluctuation_window = rolling_fluctuation_window;
+    }
+
+    if !config.rolling_fluctuation_limit_ratio.is_zero() && config.rolling_fluctuation_window == 0 {
+        return Err(StdError::generic_err(
+            "rolling fluctuation window cannot be zero",
+        ));
+    }
+
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::default().add_attribute("action", "update_rolling_fluctuation_limit"))
+}
+
 pub fn update_owner(deps: DepsMut, info: MessageInfo, owner: String) -> StdResult<Response> {
     // validate the address
     let valid_owner = deps.api.addr_validate(&owner)?;
@@ -116,6 +250,11 @@ pub fn set_open(deps: DepsMut, env: Env, info: MessageInfo, open: bool) -> StdRe
         return Err(StdError::generic_err("unauthorized"));
     }
 
+    // a vamm that has been shut down only settles positions
+    if read_settlement_price(deps.storage)?.is_some() {
+        return Err(StdError::generic_err("amm is shut down"));
+    }
+
     state.open = open;
 
     // if state.open is true then we update the next funding time
@@ -129,9 +268,54 @@ pub fn set_open(deps: DepsMut, env: Env, info: MessageInfo, open: bool) -> StdRe
     Ok(Response::new().add_attribute("action", "set_open"))
 }
 
+/// closes the vamm for good and records the price positions are settled at, the oracle price is
+/// used unless it is unavailable in which case it falls back to the twap of the vamm
+pub fn shutdown(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<Response> {
+    let config = read_config(deps.storage)?;
+    let mut state = read_state(deps.storage)?;
+
+    // check permission
+    if !OWNER.is_admin(deps.as_ref(), &info.sender)? && info.sender != config.insurance_fund {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    if read_settlement_price(deps.storage)?.is_some() {
+        return Err(StdError::generic_err("amm is already shut down"));
+    }
+
+    let oracle_price = PricefeedController(config.pricefeed)
+        .get_price(&deps.querier, config.base_asset)
+        .unwrap_or_default();
+
+    let (settlement_price, source) = if !oracle_price.is_zero() {
+        (oracle_price, "oracle")
+    } else {
+        let twap_price = query_twap_price(
+            deps.as_ref(),
+            env,
+            config.spot_price_twap_interval,
+            TwapCalcOption::Reserve,
+            None,
+        )?;
+
+        (twap_price, "twap")
+    };
+
+    state.open = false;
+
+    store_state(deps.storage, &state)?;
+    store_settlement_price(deps.storage, settlement_price)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "shutdown"),
+        ("settlement_price", &settlement_price.to_string()),
+        ("settlement_price_source", source),
+    ]))
+}
+
 // Function should only be called by the margin engine
 pub fn swap_input(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     direction: Direction,
@@ -139,6 +323,7 @@ pub fn swap_input(
     quote_asset_amount: Uint128,
     base_asset_limit: Uint128,
     can_go_over_fluctuation: bool,
+    reduces_position: bool,
 ) -> StdResult<Response> {
     let state = read_state(deps.storage)?;
 
@@ -176,6 +361,12 @@ pub fn swap_input(
         Uint128::zero()
     };
 
+    require_not_increasing_exposure(deps.storage, &env, reduces_position)?;
+
+    if config.continuous_funding {
+        accrue_funding(deps.branch(), &env)?;
+    }
+
     let response = update_reserve(
         deps.storage,
         env,
@@ -197,7 +388,7 @@ pub fn swap_input(
 
 // Function should only be called by the margin engine
 pub fn swap_output(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     direction: Direction,
@@ -249,6 +440,10 @@ pub fn swap_output(
         Uint128::zero()
     };
 
+    if config.continuous_funding {
+        accrue_funding(deps.branch(), &env)?;
+    }
+
     let response = update_reserve(
         deps.storage,
         env,
@@ -274,6 +469,12 @@ pub fn settle_funding(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<R
     let config = read_config(deps.storage)?;
     require_margin_engine(info.sender, config.margin_engine)?;
 
+    if config.continuous_funding {
+        return Err(StdError::generic_err(
+            "settle funding is disabled in continuous funding mode",
+        ));
+    }
+
     if env.block.time.seconds() < state.next_funding_time {
         return Err(StdError::generic_err("settle funding called too early"));
     }
@@ -296,17 +497,13 @@ pub fn settle_funding(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<R
         None,
     )?;
 
-    let premium =
-        Integer::new_positive(index_price).checked_sub(Integer::new_positive(underlying_price))?;
+    let FundingCalc {
+        premium_fraction,
+        raw_funding_rate,
+        funding_rate,
+    } = calc_premium_fraction(&config, index_price, underlying_price)?;
 
-    let premium_fraction = premium
-        .checked_mul(Integer::new_positive(config.funding_period))?
-        .checked_div(Integer::new_positive(ONE_DAY_IN_SECONDS))?;
-
-    // update funding rate = premiumFraction / twapIndexPrice
-    state.funding_rate = premium_fraction
-        .checked_mul(Integer::new_positive(config.decimals))?
-        .checked_div(Integer::new_positive(underlying_price))?;
+    state.funding_rate = funding_rate;
 
     // in order to prevent multiple funding settlement during very short time after network congestion
     let block_time_seconds = env.block.time.seconds();
@@ -321,14 +518,299 @@ pub fn settle_funding(deps: DepsMut, env: Env, info: MessageInfo) -> StdResult<R
 
     store_state(deps.storage, &state)?;
 
+    // keep a record of the settlement so the funding of each period can be audited
+    let funding_id = read_funding_record_counter(deps.storage)? + 1;
+    store_funding_record(
+        deps.storage,
+        &FundingRecord {
+            funding_id,
+            timestamp: block_time_seconds,
+            premium_fraction,
+            funding_rate: state.funding_rate,
+            raw_funding_rate,
+            underlying_price,
+            index_price,
+        },
+    )?;
+
     Ok(Response::new().add_attributes(vec![
         ("action", "settle_funding"),
+        ("funding_id", &funding_id.to_string()),
         ("premium_fraction", &premium_fraction.to_string()),
+        ("raw_funding_rate", &raw_funding_rate.to_string()),
+        ("funding_rate", &state.funding_rate.to_string()),
         ("underlying_price", &underlying_price.to_string()),
         ("index_price", &index_price.to_string()),
     ]))
 }
 
+/// moves the quote asset reserve so the spot price is the target price, only the margin engine
+/// can repeg as the cost is charged to the insurance fund
+pub fn repeg(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    target_price: Uint128,
+) -> StdResult<Response> {
+    let state = read_state(deps.storage)?;
+    let config = read_config(deps.storage)?;
+    require_margin_engine(info.sender, config.margin_engine)?;
+
+    if target_price.is_zero() {
+        return Err(StdError::generic_err("Target price cannot be zero"));
+    }
+
+    let quote_asset_reserve = state
+        .base_asset_reserve
+        .checked_mul(target_price)?
+        .checked_div(config.decimals)?;
+
+    adjust_reserves(
+        deps,
+        env,
+        "repeg",
+        quote_asset_reserve,
+        state.base_asset_reserve,
+    )
+}
+
+/// scales both reserves, changing the depth of the curve but not the spot price, only the
+/// margin engine can adjust k as the cost is charged to the insurance fund
+pub fn adjust_k(deps: DepsMut, env: Env, info: MessageInfo, scale: Uint128) -> StdResult<Response> {
+    let state = read_state(deps.storage)?;
+    let config = read_config(deps.storage)?;
+    require_margin_engine(info.sender, config.margin_engine)?;
+
+    if scale.is_zero() {
+        return Err(StdError::generic_err("Scale cannot be zero"));
+    }
+
+    let quote_asset_reserve = state
+        .quote_asset_reserve
+        .checked_mul(scale)?
+        .checked_div(config.decimals)?;
+    let base_asset_reserve = state
+        .base_asset_reserve
+        .checked_mul(scale)?
+        .checked_div(config.decimals)?;
+
+    adjust_reserves(
+        deps,
+        env,
+        "adjust_k",
+        quote_asset_reserve,
+        base_asset_reserve,
+    )
+}
+
+/// replaces the reserves and returns the cost of the change for the open interest, positive
+/// when traders gain and the insurance fund has to pay
+fn adjust_reserves(
+    deps: DepsMut,
+    env: Env,
+    action: &str,
+    quote_asset_reserve: Uint128,
+    base_asset_reserve: Uint128,
+) -> StdResult<Response> {
+    let mut state = read_state(deps.storage)?;
+
+    if quote_asset_reserve.is_zero() || base_asset_reserve.is_zero() {
+        return Err(StdError::generic_err("Reserves cannot be zero"));
+    }
+
+    let adjustment_cost = calc_adjustment_cost(
+        deps.as_ref(),
+        &state,
+        quote_asset_reserve,
+        base_asset_reserve,
+    )?;
+
+    state.quote_asset_reserve = quote_asset_reserve;
+    state.base_asset_reserve = base_asset_reserve;
+
+    store_state(deps.storage, &state)?;
+
+    add_reserve_snapshot(
+        deps.storage,
+        env.clone(),
+        state.quote_asset_reserve,
+        state.base_asset_reserve,
+    )?;
+
+    // the premium accrued so far follows the previous price
+    if read_config(deps.storage)?.continuous_funding {
+        accrue_funding(deps, &env)?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", action),
+        (
+            "quote_asset_reserve",
+            &state.quote_asset_reserve.to_string(),
+        ),
+        ("base_asset_reserve", &state.base_asset_reserve.to_string()),
+        ("adjustment_cost", &adjustment_cost.to_string()),
+    ]))
+}
+
+/// compares what closing the whole open interest is worth before and after the new reserves,
+/// longs receive the quote of closing and shorts pay it
+fn calc_adjustment_cost(
+    deps: Deps,
+    state: &State,
+    quote_asset_reserve: Uint128,
+    base_asset_reserve: Uint128,
+) -> StdResult<Integer> {
+    let position_size = state.total_position_size;
+    if position_size.is_zero() {
+        return Ok(Integer::zero());
+    }
+
+    let direction = if position_size.is_positive() {
+        Direction::AddToAmm
+    } else {
+        Direction::RemoveFromAmm
+    };
+
+    let notional_before = get_output_price_with_reserves(
+        deps,
+        &direction,
+        position_size.value,
+        state.quote_asset_reserve,
+        state.base_asset_reserve,
+    )?;
+    let notional_after = get_output_price_with_reserves(
+        deps,
+        &direction,
+        position_size.value,
+        quote_asset_reserve,
+        base_asset_reserve,
+    )?;
+
+    let cost = Integer::new_positive(notional_after) - Integer::new_positive(notional_before);
+
+    if position_size.is_positive() {
+        Ok(cost)
+    } else {
+        Ok(cost.invert_sign())
+    }
+}
+
+/// accrues the continuous funding index and the funding paid by the open interest up to the
+/// block time, then resets the premium fraction applied until the next update from the amm and
+/// oracle twaps. Must run before the reserves move so the elapsed time follows the open interest
+/// it was accrued on
+pub fn accrue_funding(deps: DepsMut, env: &Env) -> StdResult<()> {
+    let config = read_config(deps.storage)?;
+    let state = read_state(deps.storage)?;
+    let mut funding_index = read_funding_index(deps.storage)?;
+
+    let block_time = env.block.time.seconds();
+
+    funding_index.cumulative_funding_payment = funding_index.accrued_funding_payment(
+        config.funding_period,
+        block_time,
+        state.total_position_size,
+        config.decimals,
+    )?;
+    funding_index.cumulative_premium_fraction =
+        funding_index.accrued(config.funding_period, block_time)?;
+    funding_index.last_updated = block_time;
+
+    funding_index.premium_fraction = if config.continuous_funding {
+        let underlying_price = PricefeedController(config.pricefeed.clone()).twap_price(
+            &deps.querier,
+            config.base_asset.clone(),
+            config.spot_price_twap_interval,
+        );
+
+        // a stale oracle pauses the accrual until it recovers rather than reverting the swap
+        match underlying_price {
+            Ok(underlying_price) => {
+                let index_price = query_twap_price(
+                    deps.as_ref(),
+                    env.clone(),
+                    config.spot_price_twap_interval,
+                    TwapCalcOption::Reserve,
+                    None,
+                )?;
+
+                calc_premium_fraction(&config, index_price, underlying_price)?.premium_fraction
+            }
+            Err(_) => Integer::zero(),
+        }
+    } else {
+        Integer::zero()
+    };
+
+    store_funding_index(deps.storage, &funding_index)
+}
+
+struct FundingCalc {
+    premium_fraction: Integer,
+    raw_funding_rate: Integer,
+    funding_rate: Integer,
+}
+
+/// calculates the premium fraction of a funding period from the amm and oracle prices
+fn calc_premium_fraction(
+    config: &Config,
+    index_price: Uint128,
+    underlying_price: Uint128,
+) -> StdResult<FundingCalc> {
+    let premium =
+        Integer::new_positive(index_price).checked_sub(Integer::new_positive(underlying_price))?;
+
+    let raw_premium_fraction = premium
+        .checked_mul(Integer::new_positive(config.funding_period))?
+        .checked_div(Integer::new_positive(ONE_DAY_IN_SECONDS))?;
+
+    // raw funding rate = premiumFraction / twapIndexPrice
+    let raw_funding_rate = raw_premium_fraction
+        .checked_mul(Integer::new_positive(config.decimals))?
+        .checked_div(Integer::new_positive(underlying_price))?;
+
+    let funding_rate = calc_funding_rate(config, raw_funding_rate)?;
+
+    // the premium fraction charged follows the adjusted rate
+    let premium_fraction = if funding_rate == raw_funding_rate {
+        raw_premium_fraction
+    } else {
+        funding_rate
+            .checked_mul(Integer::new_positive(underlying_price))?
+            .checked_div(Integer::new_positive(config.decimals))?
+    };
+
+    Ok(FundingCalc {
+        premium_fraction,
+        raw_funding_rate,
+        funding_rate,
+    })
+}
+
+/// dampens the raw funding rate, adds the interest and caps the result
+fn calc_funding_rate(config: &Config, raw_funding_rate: Integer) -> StdResult<Integer> {
+    let decimals = Integer::new_positive(config.decimals);
+
+    let funding_rate = raw_funding_rate
+        .checked_mul(Integer::new_positive(
+            config
+                .decimals
+                .checked_sub(config.funding_dampening_ratio)?,
+        ))?
+        .checked_div(decimals)?
+        .checked_add(Integer::new_positive(config.funding_interest_rate))?;
+
+    if config.max_funding_rate.is_zero() {
+        return Ok(funding_rate);
+    }
+
+    Ok(funding_rate.clamp(
+        Integer::new_negative(config.max_funding_rate),
+        Integer::new_positive(config.max_funding_rate),
+    ))
+}
+
 pub fn get_input_price_with_reserves(
     deps: Deps,
     direction: &Direction,
@@ -456,7 +938,24 @@ pub fn update_reserve(
         state.base_asset_reserve,
     )?;
 
-    Ok(Response::new().add_attributes(vec![
+    let mut response = Response::new();
+
+    // the vamm stays reduce only until the move drops out of the rolling window
+    if check_is_over_rolling_fluctuation_limit(storage, &env)? {
+        let config = read_config(storage)?;
+        let reduce_only_until = env
+            .block
+            .time
+            .seconds()
+            .checked_add(config.rolling_fluctuation_window)
+            .ok_or_else(|| StdError::generic_err("reduce only timestamp overflow"))?;
+
+        store_reduce_only_until(storage, reduce_only_until)?;
+
+        response = response.add_attribute("reduce_only_until", reduce_only_until.to_string());
+    }
+
+    Ok(response.add_attributes(vec![
         (
             "quote_asset_reserve",
             &state.quote_asset_reserve.to_string(),
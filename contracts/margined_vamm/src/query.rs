@@ -1,14 +1,19 @@
 use cosmwasm_std::{Deps, Env, StdError, StdResult, Uint128};
 use margined_common::integer::Integer;
 use margined_perp::margined_vamm::{
-    CalcFeeResponse, ConfigResponse, Direction, OwnerResponse, StateResponse,
+    CalcFeeResponse, ConfigResponse, Direction, FundingIndexResponse, FundingRateResponse,
+    FundingRecord, OwnerResponse, StateResponse,
 };
 use margined_utils::contracts::helpers::PricefeedController;
 
 use crate::{
     contract::OWNER,
     handle::{get_input_price_with_reserves, get_output_price_with_reserves},
-    state::{read_config, read_reserve_snapshot_counter, read_state},
+    state::{
+        read_config, read_funding_index, read_funding_record, read_funding_record_counter,
+        read_reduce_only_until, read_reserve_snapshot_counter, read_settlement_price, read_state,
+        DEFAULT_LIMIT, MAX_LIMIT,
+    },
     utils::{
         calc_twap, price_boundaries_of_last_block, TwapCalcOption, TwapInputAsset,
         TwapPriceCalcParams,
@@ -152,7 +157,11 @@ pub fn query_twap_price(
 }
 
 /// Returns the total (i.e. toll + spread) fees for an amount
-pub fn query_calc_fee(deps: Deps, quote_asset_amount: Uint128) -> StdResult<CalcFeeResponse> {
+pub fn query_calc_fee(
+    deps: Deps,
+    quote_asset_amount: Uint128,
+    direction: Direction,
+) -> StdResult<CalcFeeResponse> {
     let mut res = CalcFeeResponse {
         toll_fee: Uint128::zero(),
         spread_fee: Uint128::zero(),
@@ -161,17 +170,98 @@ pub fn query_calc_fee(deps: Deps, quote_asset_amount: Uint128) -> StdResult<Calc
     if quote_asset_amount != Uint128::zero() {
         let config = read_config(deps.storage)?;
 
+        let spread_ratio = if config.dynamic_spread {
+            calc_dynamic_spread_ratio(deps, &config, quote_asset_amount, &direction)?
+        } else {
+            config.spread_ratio
+        };
+
         res.toll_fee = quote_asset_amount
             .checked_mul(config.toll_ratio)?
             .checked_div(config.decimals)?;
         res.spread_fee = quote_asset_amount
-            .checked_mul(config.spread_ratio)?
+            .checked_mul(spread_ratio)?
             .checked_div(config.decimals)?;
     }
 
     Ok(res)
 }
 
+/// Returns the spread ratio for a trade, the flat spread is increased by the price impact and,
+/// if the trade pushes the vamm away from the oracle, by the divergence after the trade.
+/// Falls back to the flat spread if the oracle price is unavailable
+fn calc_dynamic_spread_ratio(
+    deps: Deps,
+    config: &ConfigResponse,
+    quote_asset_amount: Uint128,
+    direction: &Direction,
+) -> StdResult<Uint128> {
+    let state = read_state(deps.storage)?;
+
+    // the flat spread applies while the oracle can't be read, so trading isn't halted by it
+    let oracle_price = match PricefeedController(config.pricefeed.clone())
+        .get_price(&deps.querier, config.base_asset.clone())
+    {
+        Ok(oracle_price) if !oracle_price.is_zero() => oracle_price,
+        _ => return Ok(config.spread_ratio),
+    };
+
+    let base_asset_amount = get_input_price_with_reserves(
+        deps,
+        direction,
+        quote_asset_amount,
+        state.quote_asset_reserve,
+        state.base_asset_reserve,
+    )?;
+
+    let (quote_asset_after, base_asset_after) = match direction {
+        Direction::AddToAmm => (
+            state.quote_asset_reserve.checked_add(quote_asset_amount)?,
+            state.base_asset_reserve.checked_sub(base_asset_amount)?,
+        ),
+        Direction::RemoveFromAmm => (
+            state.quote_asset_reserve.checked_sub(quote_asset_amount)?,
+            state.base_asset_reserve.checked_add(base_asset_amount)?,
+        ),
+    };
+
+    let price_before = query_spot_price(deps)?;
+    let price_after = quote_asset_after
+        .checked_mul(config.decimals)?
+        .checked_div(base_asset_after)?;
+
+    let price_impact = price_after
+        .abs_diff(price_before)
+        .checked_mul(config.decimals)?
+        .checked_div(price_before)?;
+
+    // longs push the price up and shorts push it down
+    let is_moving_away = match direction {
+        Direction::AddToAmm => price_after > oracle_price,
+        Direction::RemoveFromAmm => price_after < oracle_price,
+    };
+
+    let divergence = if is_moving_away {
+        price_after
+            .abs_diff(oracle_price)
+            .checked_mul(config.decimals)?
+            .checked_div(oracle_price)?
+    } else {
+        Uint128::zero()
+    };
+
+    let spread_ratio = config
+        .spread_ratio
+        .checked_add(price_impact)?
+        .checked_add(divergence)?;
+
+    if !config.max_spread_ratio.is_zero() {
+        return Ok(spread_ratio.min(config.max_spread_ratio));
+    }
+
+    Ok(spread_ratio)
+}
+
 /// Returns bool to show is spread limit has been exceeded
 pub fn query_is_over_spread_limit(deps: Deps) -> StdResult<bool> {
     let config: ConfigResponse = read_config(deps.storage)?;
@@ -198,6 +288,16 @@ pub fn query_is_over_spread_limit(deps: Deps) -> StdResult<bool> {
     Ok(current_spread_ratio.abs() >= max_oracle_spread_ratio)
 }
 
+/// Returns bool to show if the rolling fluctuation limit has put the vamm in reduce only mode
+pub fn query_is_reduce_only(deps: Deps, env: Env) -> StdResult<bool> {
+    Ok(env.block.time.seconds() < read_reduce_only_until(deps.storage)?)
+}
+
+/// Queries the price positions are settled at, none until the vamm has been shut down
+pub fn query_settlement_price(deps: Deps) -> StdResult<Option<Uint128>> {
+    read_settlement_price(deps.storage)
+}
+
 /// Returns bool to show is fluctuation limit has been exceeded
 pub fn query_is_over_fluctuation_limit(
     deps: Deps,
@@ -233,3 +333,55 @@ pub fn query_is_over_fluctuation_limit(
 
     Ok(price > upper_limit || price < lower_limit)
 }
+
+/// Queries the funding rate of the last settlement alongside the rate before adjustments
+pub fn query_funding_rate(deps: Deps) -> StdResult<FundingRateResponse> {
+    let state = read_state(deps.storage)?;
+
+    let raw_funding_rate = match read_funding_record_counter(deps.storage)? {
+        0 => state.funding_rate,
+        funding_id => read_funding_record(deps.storage, funding_id)?.raw_funding_rate,
+    };
+
+    Ok(FundingRateResponse {
+        funding_rate: state.funding_rate,
+        raw_funding_rate,
+        next_funding_time: state.next_funding_time,
+    })
+}
+
+/// Queries the continuous funding index, accrued up to the current block
+pub fn query_funding_index(deps: Deps, env: Env) -> StdResult<FundingIndexResponse> {
+    let config = read_config(deps.storage)?;
+    let state = read_state(deps.storage)?;
+    let funding_index = read_funding_index(deps.storage)?;
+
+    Ok(FundingIndexResponse {
+        continuous_funding: config.continuous_funding,
+        cumulative_premium_fraction: funding_index
+            .accrued(config.funding_period, env.block.time.seconds())?,
+        premium_fraction: funding_index.premium_fraction,
+        cumulative_funding_payment: funding_index.accrued_funding_payment(
+            config.funding_period,
+            env.block.time.seconds(),
+            state.total_position_size,
+            config.decimals,
+        )?,
+        last_updated: funding_index.last_updated,
+    })
+}
+
+/// Queries the funding settlements in ascending order
+pub fn query_funding_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<FundingRecord>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as u64;
+    let start = start_after.unwrap_or_default() + 1;
+    let end = read_funding_record_counter(deps.storage)?.min(start + limit - 1);
+
+    (start..=end)
+        .map(|funding_id| read_funding_record(deps.storage, funding_id))
+        .collect()
+}
@@ -1,12 +1,22 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{from_slice, to_vec, StdError, StdResult, Storage, Timestamp, Uint128};
+use margined_common::integer::Integer;
 
-use margined_perp::margined_vamm::{ConfigResponse, StateResponse};
+use margined_perp::margined_vamm::{ConfigResponse, FundingRecord, StateResponse};
 
 pub static KEY_CONFIG: &[u8] = b"config";
 pub static KEY_STATE: &[u8] = b"state";
 pub static KEY_RESERVE_SNAPSHOT: &[u8] = b"reserve_snapshot";
 pub static KEY_RESERVE_SNAPSHOT_COUNTER: &[u8] = b"reserve_snapshot_counter";
+pub static KEY_FUNDING_RECORD: &[u8] = b"funding_record";
+pub static KEY_FUNDING_RECORD_COUNTER: &[u8] = b"funding_record_counter";
+pub static KEY_FUNDING_INDEX: &[u8] = b"funding_index";
+pub static KEY_REDUCE_ONLY_UNTIL: &[u8] = b"reduce_only_until";
+pub static KEY_SETTLEMENT_PRICE: &[u8] = b"settlement_price";
+
+// settings for pagination
+pub const MAX_LIMIT: u32 = 100;
+pub const DEFAULT_LIMIT: u32 = 10;
 
 // Has the same fields
 pub type State = StateResponse;
@@ -85,3 +95,114 @@ pub fn increment_reserve_snapshot_counter(storage: &mut dyn Storage) -> StdResul
 
     Ok(storage.set(KEY_RESERVE_SNAPSHOT_COUNTER, &to_vec(&val)?))
 }
+
+pub fn read_funding_record(storage: &dyn Storage, funding_id: u64) -> StdResult<FundingRecord> {
+    match storage.get(&[KEY_FUNDING_RECORD, &funding_id.to_be_bytes()].concat()) {
+        Some(data) => from_slice(&data),
+        None => Err(StdError::generic_err("Funding record not found")),
+    }
+}
+
+/// Stores the record of a funding settlement and moves the counter to its id
+pub fn store_funding_record(
+    storage: &mut dyn Storage,
+    funding_record: &FundingRecord,
+) -> StdResult<()> {
+    storage.set(
+        KEY_FUNDING_RECORD_COUNTER,
+        &to_vec(&funding_record.funding_id)?,
+    );
+
+    Ok(storage.set(
+        &[KEY_FUNDING_RECORD, &funding_record.funding_id.to_be_bytes()].concat(),
+        &to_vec(funding_record)?,
+    ))
+}
+
+pub fn read_funding_record_counter(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(match storage.get(KEY_FUNDING_RECORD_COUNTER) {
+        Some(data) => from_slice(&data)?,
+        None => 0,
+    })
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct FundingIndex {
+    pub cumulative_premium_fraction: Integer,
+    pub premium_fraction: Integer, // per funding period, applied until the next update
+    #[serde(default)]
+    pub cumulative_funding_payment: Integer, // paid by the open interest, positive when longs pay
+    pub last_updated: u64,
+}
+
+impl FundingIndex {
+    /// returns the cumulative premium fraction accrued up to the timestamp
+    pub fn accrued(&self, funding_period: u64, timestamp: u64) -> StdResult<Integer> {
+        let elapsed = timestamp.saturating_sub(self.last_updated);
+
+        let accrued = self
+            .premium_fraction
+            .checked_mul(Integer::new_positive(elapsed))?
+            .checked_div(Integer::new_positive(funding_period))?;
+
+        Ok(self.cumulative_premium_fraction.checked_add(accrued)?)
+    }
+
+    /// returns the cumulative funding paid by the open interest up to the timestamp
+    pub fn accrued_funding_payment(
+        &self,
+        funding_period: u64,
+        timestamp: u64,
+        total_position_size: Integer,
+        decimals: Uint128,
+    ) -> StdResult<Integer> {
+        let accrued = self
+            .accrued(funding_period, timestamp)?
+            .checked_sub(self.cumulative_premium_fraction)?;
+
+        let funding_payment = accrued
+            .checked_mul(total_position_size)?
+            .checked_div(Integer::new_positive(decimals))?;
+
+        Ok(self
+            .cumulative_funding_payment
+            .checked_add(funding_payment)?)
+    }
+}
+
+pub fn store_funding_index(
+    storage: &mut dyn Storage,
+    funding_index: &FundingIndex,
+) -> StdResult<()> {
+    Ok(storage.set(KEY_FUNDING_INDEX, &to_vec(funding_index)?))
+}
+
+pub fn read_funding_index(storage: &dyn Storage) -> StdResult<FundingIndex> {
+    match storage.get(KEY_FUNDING_INDEX) {
+        Some(data) => from_slice(&data),
+        None => Ok(FundingIndex::default()),
+    }
+}
+
+pub fn store_reduce_only_until(storage: &mut dyn Storage, timestamp: u64) -> StdResult<()> {
+    Ok(storage.set(KEY_REDUCE_ONLY_UNTIL, &to_vec(&timestamp)?))
+}
+
+pub fn read_reduce_only_until(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(match storage.get(KEY_REDUCE_ONLY_UNTIL) {
+        Some(data) => from_slice(&data)?,
+        None => 0,
+    })
+}
+
+pub fn store_settlement_price(storage: &mut dyn Storage, price: Uint128) -> StdResult<()> {
+    Ok(storage.set(KEY_SETTLEMENT_PRICE, &to_vec(&price)?))
+}
+
+pub fn read_settlement_price(storage: &dyn Storage) -> StdResult<Option<Uint128>> {
+    match storage.get(KEY_SETTLEMENT_PRICE) {
+        Some(data) => Ok(Some(from_slice(&data)?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,168 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_vamm::Direction;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, VammScenario},
+};
+
+use crate::testing::new_vammscenario;
+
+#[test]
+fn test_rolling_fluctuation_limit_makes_vamm_reduce_only() {
+    let VammScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        ..
+    } = new_vammscenario();
+
+    let msg = vamm.set_fluctuation_limit_ratio(Uint128::zero()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_rolling_fluctuation_limit(Some(Uint128::from(100_000_000u128)), None)
+        .unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: rolling fluctuation window cannot be zero"
+    );
+
+    // max 10% move over the last 10 minutes
+    let msg = vamm
+        .update_rolling_fluctuation_limit(Some(Uint128::from(100_000_000u128)), Some(600u64))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // each block stays within the limit but the price walks up, 1020 : 98.04 = 10.4
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(5u64);
+        block.height += 1;
+    });
+
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(20u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    assert!(!res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .any(|attr| attr.key == "reduce_only_until"));
+
+    let is_reduce_only = vamm.is_reduce_only(&router.wrap()).unwrap();
+    assert!(!is_reduce_only);
+
+    // 1050 : 95.24 = 11.025, 10.25% over the window
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(5u64);
+        block.height += 1;
+    });
+
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(30u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    let reduce_only_until = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "reduce_only_until")
+        .unwrap()
+        .value
+        .clone();
+    let expected_until = router.block_info().time.plus_seconds(600u64).seconds();
+    assert_eq!(reduce_only_until, expected_until.to_string());
+
+    let is_reduce_only = vamm.is_reduce_only(&router.wrap()).unwrap();
+    assert!(is_reduce_only);
+
+    // increasing the open interest is rejected
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(10u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amm is reduce only"
+    );
+
+    // a new position on the other side is rejected too, even though it nets the open interest
+    let msg = vamm
+        .swap_input(
+            Direction::RemoveFromAmm,
+            0,
+            to_decimals(10u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amm is reduce only"
+    );
+
+    // reducing a position is still allowed
+    let msg = vamm
+        .swap_input(
+            Direction::RemoveFromAmm,
+            0,
+            to_decimals(10u64),
+            Uint128::zero(),
+            false,
+            true,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // once the window has cleared the vamm accepts new exposure again
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(601u64);
+        block.height += 1;
+    });
+
+    let is_reduce_only = vamm.is_reduce_only(&router.wrap()).unwrap();
+    assert!(!is_reduce_only);
+
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(10u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+}
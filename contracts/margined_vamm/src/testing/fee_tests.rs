@@ -1,11 +1,11 @@
 use cosmwasm_std::{StdError, Uint128};
-use margined_perp::margined_vamm::CalcFeeResponse;
+use margined_perp::margined_vamm::{CalcFeeResponse, Direction};
 use margined_utils::{
     cw_multi_test::Executor,
-    testing::{to_decimals, SimpleScenario},
+    testing::{to_decimals, SimpleScenario, VammScenario},
 };
 
-use crate::testing::new_simple_scenario;
+use crate::testing::{new_simple_scenario, new_vammscenario};
 
 #[test]
 fn test_calc_fee() {
@@ -24,7 +24,9 @@ fn test_calc_fee() {
         .unwrap();
     router.execute(owner.clone(), msg).unwrap();
 
-    let result = vamm.calc_fee(&router.wrap(), to_decimals(10)).unwrap();
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(10), Direction::AddToAmm)
+        .unwrap();
 
     assert_eq!(
         result,
@@ -58,7 +60,9 @@ fn test_set_diff_fee_ratio() {
         )
         .unwrap();
     router.execute(owner.clone(), msg).unwrap();
-    let result = vamm.calc_fee(&router.wrap(), to_decimals(100)).unwrap();
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
 
     assert_eq!(
         result,
@@ -93,7 +97,9 @@ fn test_set_fee_ratio_zero() {
         .unwrap();
     router.execute(owner.clone(), msg).unwrap();
 
-    let result = vamm.calc_fee(&router.wrap(), to_decimals(100)).unwrap();
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
     assert_eq!(
         result,
         CalcFeeResponse {
@@ -107,7 +113,9 @@ fn test_set_fee_ratio_zero() {
 fn test_calc_fee_input_zero() {
     let SimpleScenario { router, vamm, .. } = new_simple_scenario();
 
-    let result = vamm.calc_fee(&router.wrap(), to_decimals(0)).unwrap();
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(0), Direction::AddToAmm)
+        .unwrap();
     assert_eq!(
         result,
         CalcFeeResponse {
@@ -147,3 +155,129 @@ fn test_update_not_owner() {
         err.downcast().unwrap()
     );
 }
+
+#[test]
+fn test_dynamic_spread_scales_with_divergence_and_price_impact() {
+    let VammScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(10u64), 1_000_000_000)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm.update_spread_config(Some(true), None).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    let msg = vamm
+        .update_spread_config(None, Some(Uint128::from(5_000_000u128)))
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: max spread ratio cannot be below the spread ratio"
+    );
+
+    let msg = vamm.update_spread_config(Some(true), None).unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: max spread ratio must be set to enable the dynamic spread"
+    );
+
+    let msg = vamm
+        .update_spread_config(Some(true), Some(to_decimals(1u64)))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // the long moves the price to 12.1, 21% impact and 21% away from the oracle
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
+    assert_eq!(
+        result,
+        CalcFeeResponse {
+            toll_fee: to_decimals(1),
+            spread_fee: Uint128::from(42_999_999_800u128),
+        }
+    );
+
+    // the short moves the price to 8.1, 19% impact and 19% away from the oracle
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::RemoveFromAmm)
+        .unwrap();
+    assert_eq!(
+        result,
+        CalcFeeResponse {
+            toll_fee: to_decimals(1),
+            spread_fee: to_decimals(39),
+        }
+    );
+
+    // moving towards the oracle only pays for the price impact
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(15u64), 1_000_000_001)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
+    assert_eq!(result.spread_fee, Uint128::from(21_999_999_900u128));
+
+    // the cap bounds the spread ratio to 30%
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(10u64), 1_000_000_002)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_spread_config(None, Some(Uint128::from(300_000_000u128)))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
+    assert_eq!(result.spread_fee, to_decimals(30));
+
+    // without the dynamic spread the flat ratio applies
+    let msg = vamm.update_spread_config(Some(false), None).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
+    assert_eq!(result.spread_fee, to_decimals(1));
+}
+
+#[test]
+fn test_dynamic_spread_falls_back_to_the_flat_spread_without_an_oracle_price() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        ..
+    } = new_vammscenario();
+
+    let msg = vamm
+        .update_spread_config(Some(true), Some(Uint128::from(300_000_000u128)))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // no price has been appended to the pricefeed
+    let result = vamm
+        .calc_fee(&router.wrap(), to_decimals(100), Direction::AddToAmm)
+        .unwrap();
+    assert_eq!(result.spread_fee, to_decimals(1));
+}
@@ -54,6 +54,7 @@ fn test_swap_input_price_goes_up_within_fluctuation_limit() {
         quote_asset_amount: to_decimals(24),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -75,6 +76,7 @@ fn test_swap_input_price_goes_down_within_fluctuation_limit() {
         quote_asset_amount: to_decimals(25),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -95,6 +97,7 @@ fn test_swap_input_price_goes_down_then_up_and_down_within_fluctuation_limit() {
         quote_asset_amount: to_decimals(25),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -107,6 +110,7 @@ fn test_swap_input_price_goes_down_then_up_and_down_within_fluctuation_limit() {
         quote_asset_amount: to_decimals(49),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -119,6 +123,7 @@ fn test_swap_input_price_goes_down_then_up_and_down_within_fluctuation_limit() {
         quote_asset_amount: to_decimals(49),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -140,6 +145,7 @@ fn test_swap_input_price_goes_can_go_over_fluctuation_limit_once() {
         quote_asset_amount: to_decimals(25),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: true,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -198,6 +204,7 @@ fn test_force_error_swap_input_price_down_over_limit() {
         quote_asset_amount: to_decimals(26),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -221,6 +228,7 @@ fn test_force_error_swap_input_can_go_over_limit_but_fails_second_time() {
         quote_asset_amount: to_decimals(25),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: true,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -232,6 +240,7 @@ fn test_force_error_swap_input_can_go_over_limit_but_fails_second_time() {
         quote_asset_amount: to_decimals(1),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: true,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -255,6 +264,7 @@ fn test_force_error_swap_input_short_can_go_over_limit_but_fails_second_time() {
         quote_asset_amount: to_decimals(30),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: true,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -266,6 +276,7 @@ fn test_force_error_swap_input_short_can_go_over_limit_but_fails_second_time() {
         quote_asset_amount: to_decimals(1),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: true,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -385,6 +396,7 @@ fn test_force_error_swap_many_times() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -396,6 +408,7 @@ fn test_force_error_swap_many_times() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -407,6 +420,7 @@ fn test_force_error_swap_many_times() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -425,6 +439,7 @@ fn test_force_error_compare_price_fluctuation_with_previous_blocks() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -441,6 +456,7 @@ fn test_force_error_compare_price_fluctuation_with_previous_blocks() {
         quote_asset_amount: to_decimals(26),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -457,6 +473,7 @@ fn test_force_error_compare_price_fluctuation_with_previous_blocks() {
         quote_asset_amount: to_decimals(30),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -473,6 +490,7 @@ fn test_force_error_compare_price_fluctuation_with_previous_blocks() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -484,6 +502,7 @@ fn test_force_error_compare_price_fluctuation_with_previous_blocks() {
         quote_asset_amount: to_decimals(20),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -507,6 +526,7 @@ fn test_force_error_value_of_fluctuation_is_same_even_no_trading_for_multiple_bl
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -523,6 +543,7 @@ fn test_force_error_value_of_fluctuation_is_same_even_no_trading_for_multiple_bl
         quote_asset_amount: to_decimals(25),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -546,6 +567,7 @@ fn test_force_error_value_of_fluctuation_is_same_even_no_trading_for_multiple_bl
         quote_asset_amount: to_decimals(10),
         base_asset_limit: to_decimals(0u64),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -1,7 +1,12 @@
 use cosmwasm_std::{StdError, Uint128};
-use margined_utils::{cw_multi_test::Executor, testing::VammScenario};
+use margined_common::integer::Integer;
+use margined_perp::margined_vamm::Direction;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, VammScenario},
+};
 
-use crate::testing::new_vammscenario;
+use crate::testing::{new_vammscenario, new_vammscenario_with_pricefeed};
 
 #[test]
 fn test_settle_funding_delay_before_buffer_period_ends() {
@@ -138,3 +143,395 @@ fn test_cant_settle_funding_multiple_times_at_once_even_settle_funding_delay() {
         err.downcast().unwrap()
     );
 }
+
+#[test]
+fn test_settle_funding_refuses_stale_price() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario_with_pricefeed();
+
+    let price = Uint128::from(500_000_000u128);
+    let timestamp = router.block_info().time.seconds();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), price, timestamp)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = pricefeed
+        .update_price_guard("ETH".to_string(), Some(1_800u64), None)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    // the last price is an hour old
+    let msg = vamm.settle_funding().unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert!(err.source().unwrap().to_string().contains(&format!(
+        "Price for key ETH is stale, last updated at {}",
+        timestamp
+    )));
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), price, router.block_info().time.seconds())
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm.settle_funding().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+}
+
+#[test]
+fn test_settle_funding_records_history() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario();
+
+    let price = Uint128::from(500_000_000u128);
+    let timestamp = 1_000_000_000;
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), price, timestamp)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let history = vamm.funding_history(&router.wrap(), None, None).unwrap();
+    assert!(history.is_empty());
+
+    for _ in 0..2 {
+        router.update_block(|block| {
+            block.time = block.time.plus_seconds(3_600u64);
+            block.height += 1;
+        });
+
+        let msg = vamm.settle_funding().unwrap();
+        router.execute(owner.clone(), msg).unwrap();
+    }
+
+    let history = vamm.funding_history(&router.wrap(), None, None).unwrap();
+    assert_eq!(history.len(), 2usize);
+    assert_eq!(history[0].funding_id, 1u64);
+    assert_eq!(history[1].funding_id, 2u64);
+    assert_eq!(history[1].timestamp, router.block_info().time.seconds());
+
+    // (10 - 0.5) * 1 hour / 1 day
+    assert_eq!(
+        history[0].premium_fraction,
+        Integer::new_positive(395_833_333u128)
+    );
+    assert_eq!(history[0].underlying_price, price);
+    assert_eq!(history[0].index_price, Uint128::from(10_000_000_000u128));
+
+    let state = vamm.state(&router.wrap()).unwrap();
+    assert_eq!(history[1].funding_rate, state.funding_rate);
+
+    let history = vamm
+        .funding_history(&router.wrap(), Some(1u64), Some(10u32))
+        .unwrap();
+    assert_eq!(history.len(), 1usize);
+    assert_eq!(history[0].funding_id, 2u64);
+}
+
+#[test]
+fn test_settle_funding_caps_and_dampens_funding_rate() {
+    let VammScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario();
+
+    let price = Uint128::from(500_000_000u128);
+    let timestamp = 1_000_000_000;
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), price, timestamp)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_funding_config(Some(Uint128::from(100_000_000u128)), None, None, None)
+        .unwrap();
+    let err = router.execute(alice.clone(), msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+    router.execute(owner.clone(), msg).unwrap();
+
+    // interest cannot be over the cap
+    let msg = vamm
+        .update_funding_config(None, Some(Uint128::from(200_000_000u128)), None, None)
+        .unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: funding interest rate cannot exceed the max funding rate"
+    );
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let msg = vamm.settle_funding().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // raw rate is (10 - 0.5) / 24 / 0.5, capped at 0.1
+    let funding_rate = vamm.funding_rate(&router.wrap()).unwrap();
+    assert_eq!(
+        funding_rate.raw_funding_rate,
+        Integer::new_positive(791_666_666u128)
+    );
+    assert_eq!(
+        funding_rate.funding_rate,
+        Integer::new_positive(100_000_000u128)
+    );
+
+    // premium fraction follows the capped rate, 0.1 * 0.5
+    let history = vamm.funding_history(&router.wrap(), None, None).unwrap();
+    assert_eq!(
+        history[0].premium_fraction,
+        Integer::new_positive(50_000_000u128)
+    );
+    assert_eq!(history[0].raw_funding_rate, funding_rate.raw_funding_rate);
+
+    // half of the premium is charged, plus 0.01 interest
+    let msg = vamm
+        .update_funding_config(
+            Some(Uint128::zero()),
+            Some(Uint128::from(10_000_000u128)),
+            Some(Uint128::from(500_000_000u128)),
+            None,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let config = vamm.config(&router.wrap()).unwrap();
+    assert_eq!(config.max_funding_rate, Uint128::zero());
+    assert_eq!(config.funding_interest_rate, Uint128::from(10_000_000u128));
+    assert_eq!(
+        config.funding_dampening_ratio,
+        Uint128::from(500_000_000u128)
+    );
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let msg = vamm.settle_funding().unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let state = vamm.state(&router.wrap()).unwrap();
+    assert_eq!(state.funding_rate, Integer::new_positive(405_833_333u128));
+
+    let history = vamm
+        .funding_history(&router.wrap(), Some(1u64), None)
+        .unwrap();
+    assert_eq!(
+        history[0].premium_fraction,
+        Integer::new_positive(202_916_666u128)
+    );
+}
+
+#[test]
+fn test_continuous_funding_accrues_when_touched() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(8u64), 1_000_000_000)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_funding_config(None, None, None, Some(true))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // (10 - 8) * 1 hour / 1 day
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert!(funding_index.continuous_funding);
+    assert_eq!(
+        funding_index.premium_fraction,
+        Integer::new_positive(83_333_333u128)
+    );
+    assert_eq!(funding_index.cumulative_premium_fraction, Integer::zero());
+
+    // half a period has accrued half of the premium fraction
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(1_800u64);
+        block.height += 1;
+    });
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(
+        funding_index.cumulative_premium_fraction,
+        Integer::new_positive(41_666_666u128)
+    );
+
+    let msg = vamm.settle_funding().unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: settle funding is disabled in continuous funding mode"
+    );
+
+    // a swap stores the accrued index, the premium follows the amm twap so the new price only
+    // moves it once it has been in place
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(100u64),
+            Uint128::zero(),
+            true,
+            false,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(
+        funding_index.cumulative_premium_fraction,
+        Integer::new_positive(41_666_666u128)
+    );
+    assert_eq!(
+        funding_index.last_updated,
+        router.block_info().time.seconds()
+    );
+    assert_eq!(
+        funding_index.premium_fraction,
+        Integer::new_positive(83_333_333u128)
+    );
+    assert_eq!(funding_index.cumulative_funding_payment, Integer::zero());
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let msg = vamm.update_funding_config(None, None, None, None).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // the long open interest paid the premium accrued while it was open
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(
+        funding_index.cumulative_premium_fraction,
+        Integer::new_positive(124_999_999u128)
+    );
+    assert!(funding_index.premium_fraction > Integer::new_positive(83_333_333u128));
+    assert!(funding_index.cumulative_funding_payment > Integer::zero());
+
+    // switching back freezes the index
+    let msg = vamm
+        .update_funding_config(None, None, None, Some(false))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let frozen_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert!(!frozen_index.continuous_funding);
+    assert_eq!(frozen_index.premium_fraction, Integer::zero());
+    assert_eq!(
+        frozen_index.cumulative_premium_fraction,
+        Integer::new_positive(124_999_999u128)
+    );
+    assert_eq!(
+        frozen_index.cumulative_funding_payment,
+        funding_index.cumulative_funding_payment
+    );
+}
+
+#[test]
+fn test_continuous_funding_pauses_on_stale_oracle() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario_with_pricefeed();
+
+    let msg = pricefeed
+        .append_price(
+            "ETH".to_string(),
+            to_decimals(8u64),
+            router.block_info().time.seconds(),
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = pricefeed
+        .update_price_guard("ETH".to_string(), Some(1_800u64), None)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm
+        .update_funding_config(None, None, None, Some(true))
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert!(funding_index.premium_fraction > Integer::zero());
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    // the oracle is stale, the swap goes through and the accrual stops until it recovers
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(100u64),
+            Uint128::zero(),
+            true,
+            false,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let stale_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(
+        stale_index.cumulative_premium_fraction,
+        funding_index.premium_fraction
+    );
+    assert_eq!(stale_index.premium_fraction, Integer::zero());
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3_600u64);
+        block.height += 1;
+    });
+
+    let funding_index = vamm.funding_index(&router.wrap()).unwrap();
+    assert_eq!(
+        funding_index.cumulative_premium_fraction,
+        stale_index.cumulative_premium_fraction
+    );
+}
@@ -1,8 +1,11 @@
+mod circuit_breaker_tests;
 mod fee_tests;
 mod fluctuation_tests;
 mod funding_tests;
 mod get_price_tests;
+mod repeg_tests;
 mod set_open_tests;
+mod settlement_tests;
 mod spread_limit_tests;
 mod swap_input_output_tests;
 mod swap_tests;
@@ -33,3 +36,11 @@ pub fn new_vammscenario() -> VammScenario {
         Box::new(create_entry_points_testing!(mock_pricefeed)),
     )
 }
+
+pub fn new_vammscenario_with_pricefeed() -> VammScenario {
+    VammScenario::new(
+        Box::new(create_entry_points_testing!(cw20_base)),
+        Box::new(create_entry_points_testing!(crate)),
+        Box::new(create_entry_points_testing!(margined_pricefeed)),
+    )
+}
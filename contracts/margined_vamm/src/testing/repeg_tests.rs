@@ -0,0 +1,75 @@
+use cosmwasm_std::Uint128;
+use margined_common::integer::Integer;
+use margined_perp::margined_vamm::Direction;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{adjustment_cost, to_decimals, VammScenario},
+};
+
+use crate::testing::new_vammscenario;
+
+#[test]
+fn test_repeg_and_adjust_k_charge_open_interest() {
+    let VammScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        ..
+    } = new_vammscenario();
+
+    // without open interest nothing is owed
+    let msg = vamm.adjust_k(to_decimals(2u64)).unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    assert_eq!(adjustment_cost(&res), Integer::zero());
+
+    let msg = vamm.adjust_k(Uint128::from(500_000_000u128)).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let state = vamm.state(&router.wrap()).unwrap();
+    assert_eq!(state.quote_asset_reserve, to_decimals(1_000u64));
+    assert_eq!(state.base_asset_reserve, to_decimals(100u64));
+
+    // traders go long and push the price to 12.1
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(100u64),
+            Uint128::zero(),
+            true,
+            false,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let msg = vamm.repeg(to_decimals(10u64)).unwrap();
+    let err = router.execute(alice.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: sender not margin engine"
+    );
+
+    let msg = vamm.repeg(Uint128::zero()).unwrap();
+    let err = router.execute(owner.clone(), msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: Target price cannot be zero"
+    );
+
+    // pulling the price down makes the longs lose
+    let msg = vamm.repeg(to_decimals(10u64)).unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    assert!(adjustment_cost(&res).is_negative());
+
+    let spot_price = vamm.spot_price(&router.wrap()).unwrap();
+    assert_eq!(spot_price, to_decimals(10u64));
+
+    // a deeper curve moves the price less when the longs close, so they gain
+    let msg = vamm.adjust_k(to_decimals(2u64)).unwrap();
+    let res = router.execute(owner.clone(), msg).unwrap();
+    assert!(adjustment_cost(&res).is_positive());
+
+    let spot_price = vamm.spot_price(&router.wrap()).unwrap();
+    assert_eq!(spot_price, to_decimals(10u64));
+}
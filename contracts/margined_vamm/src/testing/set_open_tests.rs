@@ -161,6 +161,7 @@ fn test_set_open_cant_do_anything_when_its_beginning() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
@@ -226,6 +227,7 @@ fn test_set_open_cant_do_anything_when_closed() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
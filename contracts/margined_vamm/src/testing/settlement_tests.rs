@@ -0,0 +1,121 @@
+use cosmwasm_std::Uint128;
+use margined_perp::margined_vamm::Direction;
+use margined_utils::{
+    cw_multi_test::Executor,
+    testing::{to_decimals, VammScenario},
+};
+
+use crate::testing::new_vammscenario;
+
+#[test]
+fn test_shutdown_records_the_oracle_price() {
+    let VammScenario {
+        mut router,
+        owner,
+        alice,
+        vamm,
+        pricefeed,
+        ..
+    } = new_vammscenario();
+
+    let msg = pricefeed
+        .append_price("ETH".to_string(), to_decimals(11u64), 1_000_000_000)
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    let settlement_price = vamm.settlement_price(&router.wrap()).unwrap();
+    assert_eq!(settlement_price, None);
+
+    let msg = vamm.shutdown().unwrap();
+    let err = router.execute(alice, msg.clone()).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: unauthorized"
+    );
+
+    router.execute(owner.clone(), msg.clone()).unwrap();
+
+    let settlement_price = vamm.settlement_price(&router.wrap()).unwrap();
+    assert_eq!(settlement_price, Some(to_decimals(11u64)));
+
+    let state = vamm.state(&router.wrap()).unwrap();
+    assert!(!state.open);
+
+    // the vamm can't trade or be reopened once shut down
+    let swap_msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(10u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    let err = router.execute(owner.clone(), swap_msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amm is closed"
+    );
+
+    let open_msg = vamm.set_open(true).unwrap();
+    let err = router.execute(owner.clone(), open_msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amm is shut down"
+    );
+
+    let err = router.execute(owner, msg).unwrap_err();
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "Generic error: amm is already shut down"
+    );
+}
+
+#[test]
+fn test_shutdown_falls_back_to_the_twap_without_an_oracle_price() {
+    let VammScenario {
+        mut router,
+        owner,
+        vamm,
+        ..
+    } = new_vammscenario();
+
+    let msg = vamm.set_fluctuation_limit_ratio(Uint128::zero()).unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    // 1250 : 80 = 15.625
+    let msg = vamm
+        .swap_input(
+            Direction::AddToAmm,
+            0,
+            to_decimals(250u64),
+            Uint128::zero(),
+            false,
+            false,
+        )
+        .unwrap();
+    router.execute(owner.clone(), msg).unwrap();
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(900u64);
+        block.height += 1;
+    });
+
+    let config = vamm.config(&router.wrap()).unwrap();
+    let twap_price = vamm
+        .twap_price(&router.wrap(), config.spot_price_twap_interval)
+        .unwrap();
+
+    let msg = vamm.shutdown().unwrap();
+    let res = router.execute(owner, msg).unwrap();
+    assert!(res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .any(|attr| attr.key == "settlement_price_source" && attr.value == "twap"));
+
+    let settlement_price = vamm.settlement_price(&router.wrap()).unwrap();
+    assert_eq!(settlement_price, Some(twap_price));
+    assert_ne!(twap_price, to_decimals(10u64));
+}
@@ -54,6 +54,7 @@ fn test_use_getoutputprice_use_to_swapinput_long() {
         quote_asset_amount: required_quote_asset,
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -135,6 +136,7 @@ fn test_use_getoutputprice_use_to_swapinput_short() {
         quote_asset_amount: required_quote_asset,
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -365,6 +367,7 @@ fn test_swap_input_twice_short_long() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -377,6 +380,7 @@ fn test_swap_input_twice_short_long() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -433,6 +437,7 @@ fn test_swap_input_twice_long_short() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -445,6 +450,7 @@ fn test_swap_input_twice_long_short() {
         quote_asset_amount: to_decimals(10),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -47,6 +47,14 @@ fn test_instantiation() {
             pricefeed: Addr::unchecked("oracle".to_string()),
             funding_period: 3_600u64,
             spot_price_twap_interval: ONE_HOUR_IN_SECONDS,
+            max_funding_rate: Uint128::zero(),
+            funding_interest_rate: Uint128::zero(),
+            funding_dampening_ratio: Uint128::zero(),
+            continuous_funding: false,
+            dynamic_spread: false,
+            max_spread_ratio: Uint128::zero(),
+            rolling_fluctuation_limit_ratio: Uint128::zero(),
+            rolling_fluctuation_window: 0u64,
         }
     );
 
@@ -342,6 +350,14 @@ fn test_update_config() {
             pricefeed: Addr::unchecked("oracle".to_string()),
             funding_period: 3_600u64,
             spot_price_twap_interval: ONE_MINUTE_IN_SECONDS,
+            max_funding_rate: Uint128::zero(),
+            funding_interest_rate: Uint128::zero(),
+            funding_dampening_ratio: Uint128::zero(),
+            continuous_funding: false,
+            dynamic_spread: false,
+            max_spread_ratio: Uint128::zero(),
+            rolling_fluctuation_limit_ratio: Uint128::zero(),
+            rolling_fluctuation_window: 0u64,
         }
     );
 }
@@ -423,6 +439,7 @@ fn test_swap_input_zero_amount() {
         quote_asset_amount: Uint128::zero(),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
@@ -534,6 +551,7 @@ fn test_swap_input_long() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
@@ -590,6 +608,7 @@ fn test_swap_input_short() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -756,6 +775,7 @@ fn test_swap_input_short_long() {
         quote_asset_amount: to_decimals(480),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -782,6 +802,7 @@ fn test_swap_input_short_long() {
         quote_asset_amount: to_decimals(960),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -837,6 +858,7 @@ fn test_swap_input_short_long_long() {
         quote_asset_amount: to_decimals(200),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -862,6 +884,7 @@ fn test_swap_input_short_long_long() {
         quote_asset_amount: to_decimals(100),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -887,6 +910,7 @@ fn test_swap_input_short_long_long() {
         quote_asset_amount: to_decimals(200),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -943,6 +967,7 @@ fn test_swap_input_short_long_short() {
         quote_asset_amount: to_decimals(200),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -968,6 +993,7 @@ fn test_swap_input_short_long_short() {
         quote_asset_amount: to_decimals(450),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -993,6 +1019,7 @@ fn test_swap_input_short_long_short() {
         quote_asset_amount: to_decimals(250),
         base_asset_limit: Uint128::zero(),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
 
@@ -1247,6 +1274,7 @@ fn test_force_error_swapinput_long_but_less_than_min_base_amount() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: Uint128::from(37_500_000_000u128),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
@@ -1294,6 +1322,7 @@ fn test_force_error_swapinput_short_but_more_than_min_base_amount() {
         quote_asset_amount: to_decimals(600),
         base_asset_limit: to_decimals(150),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         position_id: 0u64
     };
     let info = mock_info("addr0000", &[]);
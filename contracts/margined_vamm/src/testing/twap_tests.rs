@@ -46,6 +46,7 @@ fn setup() -> TestingEnv {
                 direction: Direction::RemoveFromAmm,
                 quote_asset_amount: to_decimals(100),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -57,6 +58,7 @@ fn setup() -> TestingEnv {
                 direction: Direction::AddToAmm,
                 quote_asset_amount: to_decimals(50),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -94,6 +96,7 @@ fn test_no_change_in_snapshot() {
         direction: Direction::RemoveFromAmm,
         quote_asset_amount: to_decimals(100),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         base_asset_limit: Uint128::zero(),
         position_id: 0u64
     };
@@ -134,6 +137,7 @@ fn test_interval_less_than_latest_snapshots() {
         direction: Direction::RemoveFromAmm,
         quote_asset_amount: to_decimals(100),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         base_asset_limit: Uint128::zero(),
         position_id: 0u64
     };
@@ -179,6 +183,7 @@ fn test_input_twap_get_twap_price() {
                 direction: Direction::RemoveFromAmm,
                 quote_asset_amount: to_decimals(100),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -190,6 +195,7 @@ fn test_input_twap_get_twap_price() {
                 direction: Direction::AddToAmm,
                 quote_asset_amount: to_decimals(50),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -225,6 +231,7 @@ fn test_input_twap_if_snapshot_is_now_no_effect() {
                 direction: Direction::RemoveFromAmm,
                 quote_asset_amount: to_decimals(100),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -236,6 +243,7 @@ fn test_input_twap_if_snapshot_is_now_no_effect() {
                 direction: Direction::AddToAmm,
                 quote_asset_amount: to_decimals(50),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -252,6 +260,7 @@ fn test_input_twap_if_snapshot_is_now_no_effect() {
         direction: Direction::RemoveFromAmm,
         quote_asset_amount: to_decimals(100),
         can_go_over_fluctuation: false,
+        reduces_position: false,
         base_asset_limit: Uint128::zero(),
         position_id: 0u64
     };
@@ -316,6 +325,7 @@ fn test_output_twap_get_twap_price() {
                 direction: Direction::RemoveFromAmm,
                 quote_asset_amount: to_decimals(100),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
@@ -327,6 +337,7 @@ fn test_output_twap_get_twap_price() {
                 direction: Direction::AddToAmm,
                 quote_asset_amount: to_decimals(50),
                 can_go_over_fluctuation: false,
+                reduces_position: false,
                 base_asset_limit: Uint128::zero(),
                 position_id: 0u64
             };
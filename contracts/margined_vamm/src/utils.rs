@@ -2,10 +2,11 @@ use cosmwasm_std::{Addr, Deps, Env, Response, StdError, StdResult, Storage, Uint
 use margined_perp::margined_vamm::Direction;
 
 use crate::{
+    contract::MAX_ROLLING_SNAPSHOTS,
     handle::{get_input_price_with_reserves, get_output_price_with_reserves},
     state::{
-        read_config, read_reserve_snapshot, read_reserve_snapshot_counter, read_state,
-        store_reserve_snapshot, update_current_reserve_snapshot,
+        read_config, read_reduce_only_until, read_reserve_snapshot, read_reserve_snapshot_counter,
+        read_state, store_reserve_snapshot, update_current_reserve_snapshot,
     },
 };
 
@@ -99,6 +100,66 @@ pub fn check_is_over_block_fluctuation_limit(
     Ok(Response::new())
 }
 
+/// Returns true if the price has moved more than the rolling limit since the start of the window,
+/// at most `MAX_ROLLING_SNAPSHOTS` are walked back so a window covering more of them is shortened
+pub fn check_is_over_rolling_fluctuation_limit(
+    storage: &dyn Storage,
+    env: &Env,
+) -> StdResult<bool> {
+    let config = read_config(storage)?;
+
+    if config.rolling_fluctuation_limit_ratio.is_zero() {
+        return Ok(false);
+    }
+
+    let base_timestamp = env
+        .block
+        .time
+        .seconds()
+        .saturating_sub(config.rolling_fluctuation_window);
+
+    let mut index = read_reserve_snapshot_counter(storage)?;
+    let mut snapshot = read_reserve_snapshot(storage, index)?;
+
+    let current_price = snapshot
+        .quote_asset_reserve
+        .checked_mul(config.decimals)?
+        .checked_div(snapshot.base_asset_reserve)?;
+
+    // walk back to the last snapshot taken at or before the start of the window
+    let oldest_index = index.saturating_sub(MAX_ROLLING_SNAPSHOTS).max(1);
+    while index > oldest_index && snapshot.timestamp.seconds() > base_timestamp {
+        index -= 1;
+        snapshot = read_reserve_snapshot(storage, index)?;
+    }
+
+    let window_price = snapshot
+        .quote_asset_reserve
+        .checked_mul(config.decimals)?
+        .checked_div(snapshot.base_asset_reserve)?;
+
+    let price_move_ratio = current_price
+        .abs_diff(window_price)
+        .checked_mul(config.decimals)?
+        .checked_div(window_price)?;
+
+    Ok(price_move_ratio > config.rolling_fluctuation_limit_ratio)
+}
+
+/// Rejects swaps that don't reduce or close a position while the vamm is reduce only, the net
+/// open interest can't tell a new position from the close of one on the other side
+pub fn require_not_increasing_exposure(
+    storage: &dyn Storage,
+    env: &Env,
+    reduces_position: bool,
+) -> StdResult<()> {
+    if !reduces_position && env.block.time.seconds() < read_reduce_only_until(storage)? {
+        return Err(StdError::generic_err("amm is reduce only"));
+    }
+
+    Ok(())
+}
+
 pub fn price_boundaries_of_last_block(
     storage: &dyn Storage,
     env: Env,
@@ -0,0 +1,96 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{from_slice, to_vec};
+
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
+    Uint128,
+};
+use margined_perp::margined_pricefeed::{OracleHubPriceResponse, OracleHubQueryMsg};
+
+pub static KEY_PRICES: &[u8] = b"prices";
+
+#[cw_serde]
+pub struct InstantiateMsg {}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    SetPrice {
+        key: String,
+        price: Uint128,
+        last_updated: Option<u64>,
+    },
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    _deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    _msg: InstantiateMsg,
+) -> StdResult<Response> {
+    Ok(Response::default())
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    msg: ExecuteMsg,
+) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::SetPrice {
+            key,
+            price,
+            last_updated,
+        } => set_price(deps, env, key, price, last_updated),
+    }
+}
+
+/// anyone can set the price, defaults the update time to the block time
+#[cfg(not(tarpaulin_include))]
+pub fn set_price(
+    deps: DepsMut,
+    env: Env,
+    key: String,
+    price: Uint128,
+    last_updated: Option<u64>,
+) -> StdResult<Response> {
+    let response = OracleHubPriceResponse {
+        price,
+        last_updated: last_updated.unwrap_or_else(|| env.block.time.seconds()),
+    };
+
+    store_price(deps.storage, key, &response)?;
+
+    Ok(Response::default())
+}
+
+#[cfg(not(tarpaulin_include))]
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: OracleHubQueryMsg) -> StdResult<Binary> {
+    match msg {
+        OracleHubQueryMsg::Price { key } => to_binary(&read_price(deps.storage, key)?),
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn store_price(
+    storage: &mut dyn Storage,
+    key: String,
+    price: &OracleHubPriceResponse,
+) -> StdResult<()> {
+    Ok(storage.set(&[KEY_PRICES, key.as_bytes()].concat(), &to_vec(price)?))
+}
+
+#[cfg(not(tarpaulin_include))]
+pub fn read_price(storage: &dyn Storage, key: String) -> StdResult<OracleHubPriceResponse> {
+    match storage.get(&[KEY_PRICES, key.as_bytes()].concat()) {
+        Some(data) => from_slice(&data),
+        None => Err(StdError::generic_err("Price not found")),
+    }
+}
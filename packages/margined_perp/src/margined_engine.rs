@@ -1,7 +1,10 @@
 use crate::margined_vamm::Direction;
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Addr, SubMsg, Uint128};
-use margined_common::{asset::AssetInfo, integer::Integer};
+use margined_common::{
+    asset::{Asset, AssetInfo},
+    integer::Integer,
+};
 
 #[cw_serde]
 #[derive(Copy)]
@@ -19,6 +22,19 @@ impl Side {
     }
 }
 
+#[cw_serde]
+#[derive(Copy)]
+pub enum MarginMode {
+    Isolated, // margin is only accounted against its own position
+    Cross,    // margin is shared with all the trader's cross positions
+}
+
+impl Default for MarginMode {
+    fn default() -> MarginMode {
+        MarginMode::Isolated
+    }
+}
+
 #[cw_serde]
 pub enum PnlCalcOption {
     SpotPrice,
@@ -56,6 +72,8 @@ pub enum ExecuteMsg {
         partial_liquidation_ratio: Option<Uint128>,
         tp_sl_spread: Option<Uint128>,
         liquidation_fee: Option<Uint128>,
+        pricefeed: Option<String>,
+        execution_fee: Option<Uint128>,
     },
     UpdatePauser {
         pauser: String,
@@ -66,6 +84,20 @@ pub enum ExecuteMsg {
     RemoveWhitelist {
         address: String,
     },
+    RegisterCollateral {
+        asset: String,
+        risk_factor: Uint128,
+        price_key: String,
+    },
+    DeregisterCollateral {
+        asset: String,
+    },
+    UpdateFeeRouting {
+        fee_routing: FeeRouting,
+    },
+    UpdateFeeTiers {
+        fee_tiers: Vec<FeeTier>,
+    },
     OpenPosition {
         vamm: String,
         side: Side,
@@ -74,6 +106,26 @@ pub enum ExecuteMsg {
         take_profit: Uint128,
         stop_loss: Option<Uint128>,
         base_asset_limit: Uint128,
+        margin_mode: Option<MarginMode>,
+    },
+    PlaceLimitOrder {
+        vamm: String,
+        side: Side,
+        margin_amount: Uint128,
+        leverage: Uint128,
+        limit_price: Uint128,
+        take_profit: Uint128,
+        stop_loss: Option<Uint128>,
+        expiry: Option<u64>,
+        margin_mode: Option<MarginMode>,
+    },
+    CancelLimitOrder {
+        vamm: String,
+        order_id: u64,
+    },
+    ExecuteLimitOrders {
+        vamm: String,
+        limit: Option<u32>,
     },
     UpdateTpSl {
         vamm: String,
@@ -86,19 +138,58 @@ pub enum ExecuteMsg {
         position_id: u64,
         quote_asset_limit: Uint128,
     },
+    SettlePosition {
+        vamm: String,
+        position_id: u64,
+    },
     TriggerTpSl {
         vamm: String,
         position_id: u64,
         quote_asset_limit: Uint128,
     },
+    TriggerTpSlMany {
+        vamm: String,
+        position_ids: Vec<u64>,
+        quote_asset_limits: Option<Vec<Uint128>>,
+    },
     Liquidate {
         vamm: String,
         position_id: u64,
         quote_asset_limit: Uint128,
     },
+    LiquidateMany {
+        vamm: String,
+        position_ids: Vec<u64>,
+        quote_asset_limits: Option<Vec<Uint128>>,
+    },
+    // positions of a batch are processed one call each, only callable by the engine itself
+    TriggerTpSlBatchPosition {
+        vamm: String,
+        position_id: u64,
+        quote_asset_limit: Uint128,
+        keeper: String,
+    },
+    LiquidateBatchPosition {
+        vamm: String,
+        position_id: u64,
+        quote_asset_limit: Uint128,
+        liquidator: String,
+    },
+    FillBatchLimitOrder {
+        vamm: String,
+        order_id: u64,
+    },
     PayFunding {
         vamm: String,
     },
+    RepegVamm {
+        vamm: String,
+        target_price: Uint128,
+    },
+    AdjustVammK {
+        vamm: String,
+        scale: Uint128,
+    },
     DepositMargin {
         vamm: String,
         position_id: u64,
@@ -109,9 +200,24 @@ pub enum ExecuteMsg {
         position_id: u64,
         amount: Uint128,
     },
+    DepositCollateral {
+        vamm: String,
+        position_id: u64,
+        asset: String,
+        amount: Uint128,
+    },
+    WithdrawCollateral {
+        vamm: String,
+        position_id: u64,
+        amount: Uint128,
+    },
     SetPause {
         pause: bool,
     },
+    SetReduceOnly {
+        vamm: String,
+        reduce_only: bool,
+    },
 }
 
 #[cw_serde]
@@ -122,12 +228,20 @@ pub struct MigrateMsg {}
 pub enum QueryMsg {
     #[returns(ConfigResponse)]
     Config {},
+    #[returns(FeeRouting)]
+    FeeRouting {},
+    #[returns(Vec<FeeTier>)]
+    FeeTiers {},
+    #[returns(TraderFeeTierResponse)]
+    TraderFeeTier { trader: String },
     #[returns(StateResponse)]
     State {},
     #[returns(PauserResponse)]
     GetPauser {},
     #[returns(bool)]
     IsWhitelisted { address: String },
+    #[returns(bool)]
+    IsReduceOnly { vamm: String },
     #[returns(cw_controllers::HooksResponse)]
     GetWhitelist {},
     #[returns(Position)]
@@ -148,6 +262,19 @@ pub enum QueryMsg {
         limit: Option<u32>,
         order_by: Option<i32>,
     },
+    #[returns(LiquidatablePositionsResponse)]
+    LiquidatablePositions {
+        vamm: String,
+        calc_option: Option<PnlCalcOption>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(Vec<Position>)]
+    TriggerablePositions {
+        vamm: String,
+        side: Side,
+        limit: Option<u32>,
+    },
     #[returns(TickResponse)]
     Tick {
         vamm: String,
@@ -162,6 +289,30 @@ pub enum QueryMsg {
         limit: Option<u32>,
         order_by: Option<i32>,
     },
+    #[returns(LimitOrder)]
+    LimitOrder { vamm: String, order_id: u64 },
+    #[returns(Vec<LimitOrder>)]
+    LimitOrders {
+        vamm: String,
+        trader: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order_by: Option<i32>,
+    },
+    #[returns(LimitOrderTickResponse)]
+    LimitOrderTick {
+        vamm: String,
+        side: Side,
+        limit_price: Uint128,
+    },
+    #[returns(LimitOrderTicksResponse)]
+    LimitOrderTicks {
+        vamm: String,
+        side: Side,
+        start_after: Option<Uint128>,
+        limit: Option<u32>,
+        order_by: Option<i32>,
+    },
     #[returns(PositionUnrealizedPnlResponse)]
     UnrealizedPnl {
         vamm: String,
@@ -180,12 +331,29 @@ pub enum QueryMsg {
     },
     #[returns(Integer)]
     FreeCollateral { vamm: String, position_id: u64 },
+    #[returns(Integer)]
+    AccountMarginRatio { trader: String },
+    #[returns(Integer)]
+    AccountFreeCollateral { trader: String },
     #[returns(Uint128)]
     BalanceWithFundingPayment { position_id: u64 },
     #[returns(Position)]
     PositionWithFundingPayment { vamm: String, position_id: u64 },
+    #[returns(Vec<FundingPayment>)]
+    PositionFundingPayments {
+        vamm: String,
+        position_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     #[returns(LastPositionIdResponse)]
     LastPositionId {},
+    #[returns(Collateral)]
+    Collateral { asset: String },
+    #[returns(Vec<Collateral>)]
+    AllCollateral {},
+    #[returns(Uint128)]
+    CollateralValue { vamm: String, position_id: u64 },
 }
 
 #[cw_serde]
@@ -200,12 +368,52 @@ pub struct ConfigResponse {
     pub partial_liquidation_ratio: Uint128,
     pub tp_sl_spread: Uint128,
     pub liquidation_fee: Uint128,
+    pub pricefeed: Option<Addr>, // used to value non quote asset collateral
+    #[serde(default)]
+    pub execution_fee: Uint128, // share of the closed notional paid to TP/SL keepers
+}
+
+#[cw_serde]
+pub struct FeeRecipient {
+    pub recipient: String,
+    pub weight: u64, // basis points of the fee
+}
+
+// the weights of every fee type sum to 10000 basis points
+#[cw_serde]
+pub struct FeeRouting {
+    pub spread_fee: Vec<FeeRecipient>,
+    pub toll_fee: Vec<FeeRecipient>,
+    pub liquidation_fee: Vec<FeeRecipient>, // protocol share of the liquidation penalty
+}
+
+// tiers are ordered by ascending volume, the highest tier reached applies
+#[cw_serde]
+pub struct FeeTier {
+    pub min_volume: Uint128, // rolling 30 day notional volume of the trader
+    pub discount: Uint128,   // ratio of the spread and toll fees waived
+}
+
+#[cw_serde]
+pub struct TraderFeeTierResponse {
+    pub volume: Uint128,
+    pub tier: Option<u32>,
+    pub discount: Uint128,
+}
+
+#[cw_serde]
+pub struct Collateral {
+    pub asset: AssetInfo,
+    pub decimals: Uint128,
+    pub risk_factor: Uint128, // 0 < risk_factor <= 1, haircut applied to the collateral value
+    pub price_key: String,    // key of the collateral price in the pricefeed
 }
 
 #[cw_serde]
 pub struct StateResponse {
     pub open_interest_notional: Uint128,
     pub bad_debt: Uint128,
+    pub uncovered_bad_debt: Uint128,
 }
 
 #[cw_serde]
@@ -229,6 +437,33 @@ pub struct TicksResponse {
     pub ticks: Vec<TickResponse>,
 }
 
+#[cw_serde]
+pub struct LimitOrderTickResponse {
+    pub limit_price: Uint128,
+    pub total_orders: u64,
+}
+
+#[cw_serde]
+pub struct LimitOrderTicksResponse {
+    pub ticks: Vec<LimitOrderTickResponse>,
+}
+
+#[cw_serde]
+pub struct LimitOrder {
+    pub order_id: u64,
+    pub vamm: Addr,
+    pub trader: Addr,
+    pub side: Side,
+    pub margin_amount: Uint128,
+    pub leverage: Uint128,
+    pub limit_price: Uint128,
+    pub take_profit: Uint128,
+    pub stop_loss: Option<Uint128>,
+    pub margin_mode: MarginMode,
+    pub fee_amount: Uint128, // fees escrowed alongside the margin
+    pub expiry: Option<u64>, // block time in seconds after which the order can't be filled
+}
+
 #[cw_serde]
 pub struct Position {
     pub position_id: u64,
@@ -244,7 +479,12 @@ pub struct Position {
     pub take_profit: Uint128,
     pub stop_loss: Option<Uint128>,
     pub last_updated_premium_fraction: Integer,
+    #[serde(default)]
+    pub last_updated_funding_id: u64, // id of the latest vamm funding record already paid
     pub block_time: u64,
+    #[serde(default)]
+    pub margin_mode: MarginMode,
+    pub collateral: Option<Asset>, // non quote asset collateral backing the position
 }
 
 impl Default for Position {
@@ -263,11 +503,26 @@ impl Default for Position {
             take_profit: Uint128::zero(),
             stop_loss: Some(Uint128::zero()),
             last_updated_premium_fraction: Integer::zero(),
+            last_updated_funding_id: 0u64,
             block_time: 0u64,
+            margin_mode: MarginMode::Isolated,
+            collateral: None,
         }
     }
 }
 
+#[cw_serde]
+pub struct LiquidatablePosition {
+    pub position: Position,
+    pub margin_ratio: Integer,
+}
+
+#[cw_serde]
+pub struct LiquidatablePositionsResponse {
+    pub positions: Vec<LiquidatablePosition>,
+    pub last_position_id: Option<u64>, // last position scanned, to be used as the next start_after
+}
+
 #[cw_serde]
 pub struct SwapResponse {
     pub vamm: String,
@@ -286,12 +541,21 @@ pub struct PositionUnrealizedPnlResponse {
     pub unrealized_pnl: Integer,
 }
 
+#[cw_serde]
+pub struct FundingPayment {
+    pub funding_id: u64,
+    pub timestamp: u64,
+    pub premium_fraction: Integer,
+    pub funding_payment: Integer, // positive means the trader pays
+}
+
 #[cw_serde]
 pub struct RemainMarginResponse {
     pub funding_payment: Integer,
     pub margin: Uint128,
     pub bad_debt: Uint128,
     pub latest_premium_fraction: Integer,
+    pub latest_funding_id: u64,
 }
 
 #[cw_serde]
@@ -21,6 +21,22 @@ pub enum ExecuteMsg {
         amount: Uint128,
         recipient: String,
     },
+    UpdateStakingConfig {
+        staking_token: Option<String>,
+        epoch_duration: Option<u64>,
+        unbonding_period: Option<u64>,
+    },
+    Bond {
+        amount: Uint128,
+    },
+    Unbond {
+        amount: Uint128,
+    },
+    ClaimUnbonded {},
+    DistributeRewards {},
+    ClaimRewards {
+        token: Option<String>,
+    },
 }
 
 #[cw_serde]
@@ -36,13 +52,25 @@ pub enum QueryMsg {
     GetTokenLength {},
     #[returns(AllTokenResponse)]
     GetTokenList { limit: Option<u32> },
+    #[returns(DistributionResponse)]
+    Distribution {},
+    #[returns(StakerResponse)]
+    Staker { address: String },
 }
 
 #[cw_serde]
 pub struct MigrateMsg {}
 
 #[cw_serde]
-pub struct ConfigResponse {}
+#[derive(Default)]
+pub struct ConfigResponse {
+    #[serde(default)]
+    pub staking_token: Option<AssetInfo>, // token bonded by holders to earn the fees
+    #[serde(default)]
+    pub epoch_duration: u64, // minimum seconds between two distributions
+    #[serde(default)]
+    pub unbonding_period: u64, // seconds unbonded tokens are locked before they can be claimed
+}
 
 #[cw_serde]
 pub struct OwnerResponse {
@@ -63,3 +91,32 @@ pub struct AllTokenResponse {
 pub struct TokenLengthResponse {
     pub length: usize,
 }
+
+#[cw_serde]
+pub struct RewardIndexResponse {
+    pub token: AssetInfo,
+    pub index: Uint128, // rewards per bonded token, scaled by the reward index precision
+    pub unclaimed: Uint128, // distributed but not yet claimed
+}
+
+#[cw_serde]
+pub struct DistributionResponse {
+    pub total_bonded: Uint128,
+    pub last_distribution: u64,
+    pub next_distribution: u64,
+    pub reward_indexes: Vec<RewardIndexResponse>,
+}
+
+#[cw_serde]
+pub struct PendingRewardResponse {
+    pub token: AssetInfo,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct StakerResponse {
+    pub bonded: Uint128,
+    pub unbonding: Uint128,
+    pub unlock_time: u64,
+    pub pending_rewards: Vec<PendingRewardResponse>,
+}
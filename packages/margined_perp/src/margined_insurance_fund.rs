@@ -9,11 +9,38 @@ pub struct InstantiateMsg {
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    UpdateOwner { owner: String },
-    AddVamm { vamm: String },
-    RemoveVamm { vamm: String },
-    Withdraw { token: AssetInfo, amount: Uint128 },
+    UpdateOwner {
+        owner: String,
+    },
+    AddVamm {
+        vamm: String,
+    },
+    RemoveVamm {
+        vamm: String,
+    },
+    Withdraw {
+        token: AssetInfo,
+        amount: Uint128,
+        vamm: Option<String>,
+    },
+    FundVamm {
+        vamm: String,
+        amount: Uint128,
+    },
+    UpdateSpilloverPolicy {
+        spillover_policy: SpilloverPolicy,
+    },
     ShutdownVamms {},
+    UpdateVaultConfig {
+        unstake_cooldown: Option<u64>,
+    },
+    Stake {
+        amount: Uint128,
+    },
+    Unstake {
+        shares: Uint128,
+    },
+    ClaimUnstake {},
 }
 
 #[cw_serde]
@@ -31,6 +58,14 @@ pub enum QueryMsg {
     GetAllVammStatus { limit: Option<u32> },
     #[returns(VammStatusResponse)]
     GetVammStatus { vamm: String },
+    #[returns(VaultResponse)]
+    Vault {},
+    #[returns(StakerResponse)]
+    Staker { address: String },
+    #[returns(VammCoverageResponse)]
+    VammCoverage { vamm: String },
+    #[returns(AllVammCoverageResponse)]
+    AllVammCoverage { limit: Option<u32> },
 }
 
 #[cw_serde]
@@ -39,6 +74,25 @@ pub struct MigrateMsg {}
 #[cw_serde]
 pub struct ConfigResponse {
     pub engine: Addr,
+    #[serde(default)]
+    pub unstake_cooldown: u64, // seconds between requesting an unstake and claiming it
+    #[serde(default)]
+    pub spillover_policy: SpilloverPolicy,
+}
+
+/// Where a withdrawal for a vAMM is taken from once its own sub-fund is exhausted
+#[cw_serde]
+#[derive(Copy)]
+pub enum SpilloverPolicy {
+    Isolated, // only the sub-fund of the vAMM
+    Backstop, // the shared backstop
+    Shared,   // the shared backstop and then the sub-funds of the other vAMMs
+}
+
+impl Default for SpilloverPolicy {
+    fn default() -> SpilloverPolicy {
+        SpilloverPolicy::Backstop
+    }
 }
 
 #[cw_serde]
@@ -65,3 +119,32 @@ pub struct AllVammResponse {
 pub struct AllVammStatusResponse {
     pub vamm_list_status: Vec<(Addr, bool)>,
 }
+
+#[cw_serde]
+pub struct VaultResponse {
+    pub total_shares: Uint128,
+    pub total_assets: Uint128, // eligible collateral held by the insurance fund
+    pub protocol_shares: Uint128, // shares backing the funds held before the first stake
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct StakerResponse {
+    pub shares: Uint128, // includes the shares pending unstake
+    pub pending_shares: Uint128,
+    pub unlock_time: u64,
+    pub value: Uint128, // current value of the shares in eligible collateral
+}
+
+#[cw_serde]
+pub struct VammCoverageResponse {
+    pub vamm: Addr,
+    pub sub_fund: Uint128,
+    pub backstop: Uint128,
+    pub coverage: Uint128, // total available to the vAMM under the spillover policy
+}
+
+#[cw_serde]
+pub struct AllVammCoverageResponse {
+    pub vamm_coverage: Vec<VammCoverageResponse>,
+}
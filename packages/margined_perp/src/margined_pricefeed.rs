@@ -21,7 +21,10 @@ pub struct InstantiateMsg {
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    pub oracle_hub_contract: Option<String>,
+    pub retention_rounds: Option<u64>,
+}
 
 #[cw_serde]
 pub enum ExecuteMsg {
@@ -38,6 +41,36 @@ pub enum ExecuteMsg {
     UpdateOwner {
         owner: String,
     },
+    UpdateConfig {
+        oracle_hub_contract: Option<String>,
+        retention_rounds: Option<u64>,
+    },
+    PullPrice {
+        key: String,
+    },
+    SubmitPrice {
+        key: String,
+        price: Uint128,
+        timestamp: u64,
+    },
+    AddReporter {
+        key: String,
+        reporter: String,
+    },
+    RemoveReporter {
+        key: String,
+        reporter: String,
+    },
+    UpdateReporterConfig {
+        key: String,
+        quorum: Option<u64>,
+        max_deviation: Option<Uint128>,
+    },
+    UpdatePriceGuard {
+        key: String,
+        max_age: Option<u64>,
+        max_deviation: Option<Uint128>,
+    },
 }
 
 #[cw_serde]
@@ -53,12 +86,75 @@ pub enum QueryMsg {
     GetPreviousPrice { key: String, num_round_back: u64 },
     #[returns(Uint128)]
     GetTwapPrice { key: String, interval: u64 },
+    #[returns(ReportersResponse)]
+    GetReporters { key: String },
+    #[returns(PendingRoundResponse)]
+    GetPendingRound { key: String },
+    #[returns(PriceGuardResponse)]
+    GetPriceGuard { key: String },
+    #[returns(PriceData)]
+    GetRoundData { key: String, round_id: u64 },
+    #[returns(PriceData)]
+    GetPriceAt { key: String, timestamp: u64 },
+    #[returns(Vec<PriceData>)]
+    GetPriceHistory {
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
-pub struct ConfigResponse {}
+pub struct ConfigResponse {
+    pub oracle_hub_contract: Addr,
+    #[serde(default)]
+    pub retention_rounds: u64,
+}
 
 #[cw_serde]
 pub struct OwnerResponse {
     pub owner: Addr,
 }
+
+#[cw_serde]
+#[derive(Default)]
+pub struct ReportersResponse {
+    pub reporters: Vec<Addr>,
+    pub quorum: u64,
+    pub max_deviation: Uint128, // ratio with six decimal places, zero disables flagging
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct PriceGuardResponse {
+    pub max_age: u64,           // seconds, zero disables the staleness check
+    pub max_deviation: Uint128, // ratio with six decimal places between rounds, zero disables it
+}
+
+#[cw_serde]
+pub struct Submission {
+    pub reporter: Addr,
+    pub price: Uint128,
+    pub timestamp: Timestamp,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct PendingRoundResponse {
+    pub round_id: u64,
+    pub submissions: Vec<Submission>,
+}
+
+/// Query interface the pricefeed expects the oracle hub to implement
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleHubQueryMsg {
+    #[returns(OracleHubPriceResponse)]
+    Price { key: String },
+}
+
+#[cw_serde]
+pub struct OracleHubPriceResponse {
+    pub price: Uint128,
+    pub last_updated: u64,
+}
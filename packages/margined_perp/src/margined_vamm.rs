@@ -63,6 +63,8 @@ pub enum ExecuteMsg {
         quote_asset_amount: Uint128,
         base_asset_limit: Uint128,
         can_go_over_fluctuation: bool,
+        #[serde(default)]
+        reduces_position: bool, // closes or reduces a position, allowed while reduce only
     },
     SwapOutput {
         direction: Direction,
@@ -70,10 +72,31 @@ pub enum ExecuteMsg {
         base_asset_amount: Uint128,
         quote_asset_limit: Uint128,
     },
+    UpdateFundingConfig {
+        max_funding_rate: Option<Uint128>,
+        funding_interest_rate: Option<Uint128>,
+        funding_dampening_ratio: Option<Uint128>,
+        continuous_funding: Option<bool>,
+    },
+    UpdateSpreadConfig {
+        dynamic_spread: Option<bool>,
+        max_spread_ratio: Option<Uint128>,
+    },
+    UpdateRollingFluctuationLimit {
+        rolling_fluctuation_limit_ratio: Option<Uint128>,
+        rolling_fluctuation_window: Option<u64>,
+    },
     SettleFunding {},
+    Repeg {
+        target_price: Uint128,
+    },
+    AdjustK {
+        scale: Uint128,
+    },
     SetOpen {
         open: bool,
     },
+    Shutdown {},
 }
 
 #[cw_serde]
@@ -124,7 +147,10 @@ pub enum QueryMsg {
     #[returns(Uint128)]
     UnderlyingTwapPrice { interval: u64 },
     #[returns(CalcFeeResponse)]
-    CalcFee { quote_asset_amount: Uint128 },
+    CalcFee {
+        quote_asset_amount: Uint128,
+        direction: Direction,
+    },
     #[returns(bool)]
     IsOverSpreadLimit {},
     #[returns(bool)]
@@ -132,6 +158,19 @@ pub enum QueryMsg {
         direction: Direction,
         base_asset_amount: Uint128,
     },
+    #[returns(bool)]
+    IsReduceOnly {},
+    #[returns(Option<Uint128>)]
+    SettlementPrice {},
+    #[returns(FundingRateResponse)]
+    FundingRate {},
+    #[returns(FundingIndexResponse)]
+    FundingIndex {},
+    #[returns(Vec<FundingRecord>)]
+    FundingHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
@@ -149,6 +188,22 @@ pub struct ConfigResponse {
     pub decimals: Uint128,
     pub funding_period: u64,
     pub spot_price_twap_interval: u64,
+    #[serde(default)]
+    pub max_funding_rate: Uint128, // absolute cap of the funding rate per period, zero is uncapped
+    #[serde(default)]
+    pub funding_interest_rate: Uint128, // added to the funding rate of each period
+    #[serde(default)]
+    pub funding_dampening_ratio: Uint128, // share of the premium that is not charged
+    #[serde(default)]
+    pub continuous_funding: bool, // accrue funding whenever the vamm is touched instead of settling
+    #[serde(default)]
+    pub dynamic_spread: bool, // scale the spread with the oracle divergence and price impact
+    #[serde(default)]
+    pub max_spread_ratio: Uint128, // cap of the dynamic spread ratio, zero is uncapped
+    #[serde(default)]
+    pub rolling_fluctuation_limit_ratio: Uint128, // max price move over the rolling window, zero is disabled
+    #[serde(default)]
+    pub rolling_fluctuation_window: u64, // length of the rolling window in seconds
 }
 
 #[cw_serde]
@@ -166,6 +221,33 @@ pub struct StateResponse {
     pub next_funding_time: u64,
 }
 
+#[cw_serde]
+pub struct FundingRateResponse {
+    pub funding_rate: Integer,
+    pub raw_funding_rate: Integer,
+    pub next_funding_time: u64,
+}
+
+#[cw_serde]
+pub struct FundingIndexResponse {
+    pub continuous_funding: bool,
+    pub cumulative_premium_fraction: Integer, // accrued up to the current block
+    pub premium_fraction: Integer,            // per funding period, applied until the next update
+    pub cumulative_funding_payment: Integer,  // paid by the open interest, positive when longs pay
+    pub last_updated: u64,
+}
+
+#[cw_serde]
+pub struct FundingRecord {
+    pub funding_id: u64,
+    pub timestamp: u64,
+    pub premium_fraction: Integer,
+    pub funding_rate: Integer,
+    pub raw_funding_rate: Integer, // rate from the premium before dampening, interest and cap
+    pub underlying_price: Uint128, // twap of the pricefeed
+    pub index_price: Uint128,      // twap of the amm
+}
+
 #[cw_serde]
 pub struct CalcFeeResponse {
     pub toll_fee: Uint128,
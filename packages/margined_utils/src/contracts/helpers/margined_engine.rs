@@ -1,8 +1,10 @@
 use cosmwasm_schema::cw_serde;
 use cw_controllers::HooksResponse;
 use margined_perp::margined_engine::{
-    ConfigResponse, ExecuteMsg, PnlCalcOption, Position, PositionFilter,
-    PositionUnrealizedPnlResponse, QueryMsg, Side, StateResponse, TickResponse, TicksResponse,
+    Collateral, ConfigResponse, ExecuteMsg, FeeRouting, FeeTier, FundingPayment, LimitOrder,
+    LimitOrderTickResponse, LimitOrderTicksResponse, LiquidatablePositionsResponse, MarginMode,
+    PnlCalcOption, Position, PositionFilter, PositionUnrealizedPnlResponse, QueryMsg, Side,
+    StateResponse, TickResponse, TicksResponse, TraderFeeTierResponse,
 };
 
 use cosmwasm_std::{Addr, Coin, CosmosMsg, QuerierWrapper, StdResult, Uint128};
@@ -32,6 +34,8 @@ impl EngineController {
         partial_liquidation_ratio: Option<Uint128>,
         tp_sl_spread: Option<Uint128>,
         liquidation_fee: Option<Uint128>,
+        pricefeed: Option<String>,
+        execution_fee: Option<Uint128>,
     ) -> StdResult<CosmosMsg> {
         wasm_execute(
             &self.0,
@@ -44,6 +48,8 @@ impl EngineController {
                 partial_liquidation_ratio,
                 tp_sl_spread,
                 liquidation_fee,
+                pricefeed,
+                execution_fee,
             },
             vec![],
         )
@@ -61,6 +67,8 @@ impl EngineController {
                 partial_liquidation_ratio: None,
                 tp_sl_spread: None,
                 liquidation_fee: None,
+                pricefeed: None,
+                execution_fee: None,
             },
             vec![],
         )
@@ -81,6 +89,8 @@ impl EngineController {
                 partial_liquidation_ratio: None,
                 tp_sl_spread: None,
                 liquidation_fee: None,
+                pricefeed: None,
+                execution_fee: None,
             },
             vec![],
         )
@@ -98,6 +108,8 @@ impl EngineController {
                 partial_liquidation_ratio: None,
                 tp_sl_spread: None,
                 liquidation_fee: None,
+                pricefeed: None,
+                execution_fee: None,
             },
             vec![],
         )
@@ -116,6 +128,8 @@ impl EngineController {
             partial_liquidation_ratio: Some(partial_liquidation_ratio),
             tp_sl_spread: None,
             liquidation_fee: None,
+            pricefeed: None,
+            execution_fee: None,
         };
         wasm_execute(&self.0, &msg, vec![])
     }
@@ -130,6 +144,40 @@ impl EngineController {
             partial_liquidation_ratio: None,
             tp_sl_spread: None,
             liquidation_fee: Some(liquidation_fee),
+            pricefeed: None,
+            execution_fee: None,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn set_pricefeed(&self, pricefeed: String) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateConfig {
+            owner: None,
+            insurance_fund: None,
+            fee_pool: None,
+            initial_margin_ratio: None,
+            maintenance_margin_ratio: None,
+            partial_liquidation_ratio: None,
+            tp_sl_spread: None,
+            liquidation_fee: None,
+            pricefeed: Some(pricefeed),
+            execution_fee: None,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn set_execution_fee(&self, execution_fee: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateConfig {
+            owner: None,
+            insurance_fund: None,
+            fee_pool: None,
+            initial_margin_ratio: None,
+            maintenance_margin_ratio: None,
+            partial_liquidation_ratio: None,
+            tp_sl_spread: None,
+            liquidation_fee: None,
+            pricefeed: None,
+            execution_fee: Some(execution_fee),
         };
         wasm_execute(&self.0, &msg, vec![])
     }
@@ -139,6 +187,11 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn set_reduce_only(&self, vamm: String, reduce_only: bool) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::SetReduceOnly { vamm, reduce_only };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn open_position(
         &self,
         vamm: String,
@@ -158,6 +211,33 @@ impl EngineController {
             base_asset_limit,
             take_profit,
             stop_loss,
+            margin_mode: None,
+        };
+        wasm_execute(&self.0, &msg, funds)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_position_with_margin_mode(
+        &self,
+        vamm: String,
+        side: Side,
+        margin_amount: Uint128,
+        leverage: Uint128,
+        take_profit: Uint128,
+        stop_loss: Option<Uint128>,
+        base_asset_limit: Uint128,
+        margin_mode: MarginMode,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::OpenPosition {
+            vamm,
+            side,
+            margin_amount,
+            leverage,
+            base_asset_limit,
+            take_profit,
+            stop_loss,
+            margin_mode: Some(margin_mode),
         };
         wasm_execute(&self.0, &msg, funds)
     }
@@ -176,6 +256,11 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn settle_position(&self, vamm: String, position_id: u64) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::SettlePosition { vamm, position_id };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn liquidate(
         &self,
         vamm: String,
@@ -190,11 +275,56 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn liquidate_many(
+        &self,
+        vamm: String,
+        position_ids: Vec<u64>,
+        quote_asset_limits: Option<Vec<Uint128>>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::LiquidateMany {
+            vamm,
+            position_ids,
+            quote_asset_limits,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn liquidate_batch_position(
+        &self,
+        vamm: String,
+        position_id: u64,
+        quote_asset_limit: Uint128,
+        liquidator: String,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::LiquidateBatchPosition {
+            vamm,
+            position_id,
+            quote_asset_limit,
+            liquidator,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn fill_batch_limit_order(&self, vamm: String, order_id: u64) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::FillBatchLimitOrder { vamm, order_id };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn pay_funding(&self, vamm: String) -> StdResult<CosmosMsg> {
         let msg = ExecuteMsg::PayFunding { vamm };
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn repeg_vamm(&self, vamm: String, target_price: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::RepegVamm { vamm, target_price };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn adjust_vamm_k(&self, vamm: String, scale: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::AdjustVammK { vamm, scale };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn deposit_margin(
         &self,
         vamm: String,
@@ -224,6 +354,74 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn deposit_collateral(
+        &self,
+        vamm: String,
+        position_id: u64,
+        asset: String,
+        amount: Uint128,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::DepositCollateral {
+            vamm,
+            position_id,
+            asset,
+            amount,
+        };
+        wasm_execute(&self.0, &msg, funds)
+    }
+
+    pub fn withdraw_collateral(
+        &self,
+        vamm: String,
+        position_id: u64,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::WithdrawCollateral {
+            vamm,
+            position_id,
+            amount,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn place_limit_order(
+        &self,
+        vamm: String,
+        side: Side,
+        margin_amount: Uint128,
+        leverage: Uint128,
+        limit_price: Uint128,
+        take_profit: Uint128,
+        stop_loss: Option<Uint128>,
+        expiry: Option<u64>,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::PlaceLimitOrder {
+            vamm,
+            side,
+            margin_amount,
+            leverage,
+            limit_price,
+            take_profit,
+            stop_loss,
+            expiry,
+            margin_mode: None,
+        };
+        wasm_execute(&self.0, &msg, funds)
+    }
+
+    pub fn cancel_limit_order(&self, vamm: String, order_id: u64) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::CancelLimitOrder { vamm, order_id };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn execute_limit_orders(&self, vamm: String, limit: Option<u32>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::ExecuteLimitOrders { vamm, limit };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn update_tp_sl(
         &self,
         vamm: String,
@@ -254,6 +452,36 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn trigger_tp_sl_many(
+        &self,
+        vamm: String,
+        position_ids: Vec<u64>,
+        quote_asset_limits: Option<Vec<Uint128>>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::TriggerTpSlMany {
+            vamm,
+            position_ids,
+            quote_asset_limits,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn trigger_tp_sl_batch_position(
+        &self,
+        vamm: String,
+        position_id: u64,
+        quote_asset_limit: Uint128,
+        keeper: String,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::TriggerTpSlBatchPosition {
+            vamm,
+            position_id,
+            quote_asset_limit,
+            keeper,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn add_whitelist(&self, address: String) -> StdResult<CosmosMsg> {
         let msg = ExecuteMsg::AddWhitelist { address };
         wasm_execute(&self.0, &msg, vec![])
@@ -264,6 +492,35 @@ impl EngineController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn register_collateral(
+        &self,
+        asset: String,
+        risk_factor: Uint128,
+        price_key: String,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::RegisterCollateral {
+            asset,
+            risk_factor,
+            price_key,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn deregister_collateral(&self, asset: String) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::DeregisterCollateral { asset };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_fee_routing(&self, fee_routing: FeeRouting) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateFeeRouting { fee_routing };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_fee_tiers(&self, fee_tiers: Vec<FeeTier>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateFeeTiers { fee_tiers };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     /// get margin engine configuration
     pub fn config(&self, querier: &QuerierWrapper) -> StdResult<ConfigResponse> {
         let msg = QueryMsg::Config {};
@@ -271,6 +528,31 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// get the recipients of the trading and liquidation fees
+    pub fn fee_routing(&self, querier: &QuerierWrapper) -> StdResult<FeeRouting> {
+        let msg = QueryMsg::FeeRouting {};
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the fee schedule
+    pub fn fee_tiers(&self, querier: &QuerierWrapper) -> StdResult<Vec<FeeTier>> {
+        let msg = QueryMsg::FeeTiers {};
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the rolling volume and fee tier of a trader
+    pub fn trader_fee_tier(
+        &self,
+        querier: &QuerierWrapper,
+        trader: String,
+    ) -> StdResult<TraderFeeTierResponse> {
+        let msg = QueryMsg::TraderFeeTier { trader };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get margin engine state
     pub fn state(&self, querier: &QuerierWrapper) -> StdResult<StateResponse> {
         let msg = QueryMsg::State {};
@@ -332,6 +614,109 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// get a page of liquidatable positions
+    pub fn get_liquidatable_positions(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        calc_option: Option<PnlCalcOption>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<LiquidatablePositionsResponse> {
+        let msg = QueryMsg::LiquidatablePositions {
+            vamm,
+            calc_option,
+            start_after,
+            limit,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the positions of a side whose take profit or stop loss can be triggered
+    pub fn get_triggerable_positions(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        side: Side,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<Position>> {
+        let msg = QueryMsg::TriggerablePositions { vamm, side, limit };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get a resting limit order
+    pub fn limit_order(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        order_id: u64,
+    ) -> StdResult<LimitOrder> {
+        let msg = QueryMsg::LimitOrder { vamm, order_id };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get resting limit orders from vamm
+    pub fn get_limit_orders(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        trader: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order_by: Option<i32>,
+    ) -> StdResult<Vec<LimitOrder>> {
+        let msg = QueryMsg::LimitOrders {
+            vamm,
+            trader,
+            start_after,
+            limit,
+            order_by,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get limit order price tick from vamm
+    pub fn get_limit_order_tick(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        side: Side,
+        limit_price: Uint128,
+    ) -> StdResult<LimitOrderTickResponse> {
+        let msg = QueryMsg::LimitOrderTick {
+            vamm,
+            side,
+            limit_price,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get limit order price ticks from vamm
+    pub fn get_limit_order_ticks(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        side: Side,
+        start_after: Option<Uint128>,
+        limit: Option<u32>,
+        order_by: Option<i32>,
+    ) -> StdResult<LimitOrderTicksResponse> {
+        let msg = QueryMsg::LimitOrderTicks {
+            vamm,
+            side,
+            start_after,
+            limit,
+            order_by,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get price tick from vamm
     pub fn get_tick(
         &self,
@@ -386,6 +771,13 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// checks if the vamm only accepts risk reducing actions
+    pub fn is_reduce_only(&self, querier: &QuerierWrapper, vamm: String) -> StdResult<bool> {
+        let msg = QueryMsg::IsReduceOnly { vamm };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get unrealized profit and loss for a position
     pub fn get_unrealized_pnl(
         &self,
@@ -415,6 +807,32 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// get eligible collateral
+    pub fn collateral(&self, querier: &QuerierWrapper, asset: String) -> StdResult<Collateral> {
+        let msg = QueryMsg::Collateral { asset };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get all eligible collateral
+    pub fn all_collateral(&self, querier: &QuerierWrapper) -> StdResult<Vec<Collateral>> {
+        let msg = QueryMsg::AllCollateral {};
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get haircut value of the collateral backing a position
+    pub fn get_collateral_value(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        position_id: u64,
+    ) -> StdResult<Uint128> {
+        let msg = QueryMsg::CollateralValue { vamm, position_id };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get margin ratio
     pub fn get_margin_ratio(
         &self,
@@ -427,6 +845,28 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// get margin ratio of a trader's cross margin account
+    pub fn get_account_margin_ratio(
+        &self,
+        querier: &QuerierWrapper,
+        trader: String,
+    ) -> StdResult<Integer> {
+        let msg = QueryMsg::AccountMarginRatio { trader };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get free collateral of a trader's cross margin account
+    pub fn get_account_free_collateral(
+        &self,
+        querier: &QuerierWrapper,
+        trader: String,
+    ) -> StdResult<Integer> {
+        let msg = QueryMsg::AccountFreeCollateral { trader };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get traders margin balance
     pub fn get_balance_with_funding_payment(
         &self,
@@ -450,6 +890,25 @@ impl EngineController {
         querier.query_wasm_smart(&self.0, &msg)
     }
 
+    /// get the funding payments of a position since it was last updated
+    pub fn get_position_funding_payments(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+        position_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<FundingPayment>> {
+        let msg = QueryMsg::PositionFundingPayments {
+            vamm,
+            position_id,
+            start_after,
+            limit,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
     /// get the latest cumulative premium fraction
     pub fn get_latest_cumulative_premium_fraction(
         &self,
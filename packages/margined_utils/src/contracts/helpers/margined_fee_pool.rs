@@ -1,9 +1,10 @@
 use cosmwasm_schema::cw_serde;
 use margined_perp::margined_fee_pool::{
-    AllTokenResponse, ConfigResponse, ExecuteMsg, QueryMsg, TokenLengthResponse, TokenResponse,
+    AllTokenResponse, ConfigResponse, DistributionResponse, ExecuteMsg, QueryMsg, StakerResponse,
+    TokenLengthResponse, TokenResponse,
 };
 
-use cosmwasm_std::{Addr, CosmosMsg, QuerierWrapper, StdResult, Uint128};
+use cosmwasm_std::{Addr, Coin, CosmosMsg, QuerierWrapper, StdResult, Uint128};
 
 use margined_common::messages::wasm_execute;
 
@@ -51,6 +52,45 @@ impl FeePoolController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn update_staking_config(
+        &self,
+        staking_token: Option<String>,
+        epoch_duration: Option<u64>,
+        unbonding_period: Option<u64>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateStakingConfig {
+            staking_token,
+            epoch_duration,
+            unbonding_period,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn bond(&self, amount: Uint128, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Bond { amount };
+        wasm_execute(&self.0, &msg, funds)
+    }
+
+    pub fn unbond(&self, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Unbond { amount };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn claim_unbonded(&self) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::ClaimUnbonded {};
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn distribute_rewards(&self) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::DistributeRewards {};
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn claim_rewards(&self, token: Option<String>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::ClaimRewards { token };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     //////////////////////
     /// Query Messages ///
     //////////////////////
@@ -86,4 +126,14 @@ impl FeePoolController {
 
         querier.query_wasm_smart(&self.0, &msg)
     }
+
+    /// get the bonded total and the reward indexes
+    pub fn distribution(&self, querier: &QuerierWrapper) -> StdResult<DistributionResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::Distribution {})
+    }
+
+    /// get the bonded tokens of a staker and their pending rewards
+    pub fn staker(&self, querier: &QuerierWrapper, address: String) -> StdResult<StakerResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::Staker { address })
+    }
 }
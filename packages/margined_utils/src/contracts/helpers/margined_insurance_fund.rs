@@ -1,10 +1,11 @@
 use cosmwasm_schema::cw_serde;
 use margined_perp::margined_insurance_fund::{
-    AllVammResponse, AllVammStatusResponse, ConfigResponse, ExecuteMsg, QueryMsg, VammResponse,
-    VammStatusResponse,
+    AllVammCoverageResponse, AllVammResponse, AllVammStatusResponse, ConfigResponse, ExecuteMsg,
+    QueryMsg, SpilloverPolicy, StakerResponse, VammCoverageResponse, VammResponse,
+    VammStatusResponse, VaultResponse,
 };
 
-use cosmwasm_std::{Addr, CosmosMsg, QuerierWrapper, StdResult};
+use cosmwasm_std::{Addr, Coin, CosmosMsg, QuerierWrapper, StdResult, Uint128};
 
 use margined_common::messages::wasm_execute;
 
@@ -43,6 +44,39 @@ impl InsuranceFundController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn fund_vamm(&self, vamm: String, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::FundVamm { vamm, amount };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_spillover_policy(
+        &self,
+        spillover_policy: SpilloverPolicy,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateSpilloverPolicy { spillover_policy };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_vault_config(&self, unstake_cooldown: Option<u64>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateVaultConfig { unstake_cooldown };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn stake(&self, amount: Uint128, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Stake { amount };
+        wasm_execute(&self.0, &msg, funds)
+    }
+
+    pub fn unstake(&self, shares: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Unstake { shares };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn claim_unstake(&self) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::ClaimUnstake {};
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     //////////////////////
     /// Query Messages ///
     //////////////////////
@@ -85,6 +119,34 @@ impl InsuranceFundController {
         querier.query_wasm_smart(&self.0, &QueryMsg::GetAllVamm { limit })
     }
 
+    /// get the shares and assets of the staking vault
+    pub fn vault(&self, querier: &QuerierWrapper) -> StdResult<VaultResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::Vault {})
+    }
+
+    /// get the shares of a staker and their current value
+    pub fn staker(&self, querier: &QuerierWrapper, address: String) -> StdResult<StakerResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::Staker { address })
+    }
+
+    /// get the sub-fund of a vamm and its insurance coverage
+    pub fn vamm_coverage(
+        &self,
+        querier: &QuerierWrapper,
+        vamm: String,
+    ) -> StdResult<VammCoverageResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::VammCoverage { vamm })
+    }
+
+    /// get the insurance coverage of all the vamms
+    pub fn all_vamm_coverage(
+        &self,
+        querier: &QuerierWrapper,
+        limit: Option<u32>,
+    ) -> StdResult<AllVammCoverageResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::AllVammCoverage { limit })
+    }
+
     /// query if the given vamm is actually stored
     pub fn is_vamm(&self, querier: &QuerierWrapper, vamm: String) -> StdResult<bool> {
         let res: VammResponse = querier.query_wasm_smart(&self.0, &QueryMsg::IsVamm { vamm })?;
@@ -1,5 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use margined_perp::margined_pricefeed::{ConfigResponse, ExecuteMsg, QueryMsg};
+use margined_perp::margined_pricefeed::{
+    ConfigResponse, ExecuteMsg, PendingRoundResponse, PriceData, PriceGuardResponse, QueryMsg,
+    ReportersResponse,
+};
 
 use cosmwasm_std::{Addr, CosmosMsg, QuerierWrapper, StdResult, Uint128};
 
@@ -43,6 +46,75 @@ impl PricefeedController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn update_config(
+        &self,
+        oracle_hub_contract: Option<String>,
+        retention_rounds: Option<u64>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateConfig {
+            oracle_hub_contract,
+            retention_rounds,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn pull_price(&self, key: String) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::PullPrice { key };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn submit_price(
+        &self,
+        key: String,
+        price: Uint128,
+        timestamp: u64,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::SubmitPrice {
+            key,
+            price,
+            timestamp,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn add_reporter(&self, key: String, reporter: String) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::AddReporter { key, reporter };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn remove_reporter(&self, key: String, reporter: String) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::RemoveReporter { key, reporter };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_reporter_config(
+        &self,
+        key: String,
+        quorum: Option<u64>,
+        max_deviation: Option<Uint128>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdateReporterConfig {
+            key,
+            quorum,
+            max_deviation,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn update_price_guard(
+        &self,
+        key: String,
+        max_age: Option<u64>,
+        max_deviation: Option<Uint128>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::UpdatePriceGuard {
+            key,
+            max_age,
+            max_deviation,
+        };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     /// get margined pricefeed configuration
     pub fn config(&self, querier: &QuerierWrapper) -> StdResult<ConfigResponse> {
         let msg = QueryMsg::Config {};
@@ -83,4 +155,78 @@ impl PricefeedController {
 
         querier.query_wasm_smart(&self.0, &msg)
     }
+
+    /// get the reporters of a key
+    pub fn get_reporters(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+    ) -> StdResult<ReportersResponse> {
+        let msg = QueryMsg::GetReporters { key };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the submissions collected for the next round of a key
+    pub fn get_pending_round(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+    ) -> StdResult<PendingRoundResponse> {
+        let msg = QueryMsg::GetPendingRound { key };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the staleness and deviation guard of a key
+    pub fn get_price_guard(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+    ) -> StdResult<PriceGuardResponse> {
+        let msg = QueryMsg::GetPriceGuard { key };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get a round of a key
+    pub fn get_round_data(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+        round_id: u64,
+    ) -> StdResult<PriceData> {
+        let msg = QueryMsg::GetRoundData { key, round_id };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the round of a key in effect at the timestamp
+    pub fn get_price_at(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+        timestamp: u64,
+    ) -> StdResult<PriceData> {
+        let msg = QueryMsg::GetPriceAt { key, timestamp };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
+
+    /// get the rounds of a key from oldest to latest
+    pub fn get_price_history(
+        &self,
+        querier: &QuerierWrapper,
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<PriceData>> {
+        let msg = QueryMsg::GetPriceHistory {
+            key,
+            start_after,
+            limit,
+        };
+
+        querier.query_wasm_smart(&self.0, &msg)
+    }
 }
@@ -1,6 +1,7 @@
 use cosmwasm_schema::cw_serde;
 use margined_perp::margined_vamm::{
-    CalcFeeResponse, ConfigResponse, Direction, ExecuteMsg, QueryMsg, StateResponse,
+    CalcFeeResponse, ConfigResponse, Direction, ExecuteMsg, FundingIndexResponse,
+    FundingRateResponse, FundingRecord, QueryMsg, StateResponse,
 };
 
 use cosmwasm_std::{Addr, CosmosMsg, QuerierWrapper, StdResult, Uint128};
@@ -135,11 +136,65 @@ impl VammController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn update_funding_config(
+        &self,
+        max_funding_rate: Option<Uint128>,
+        funding_interest_rate: Option<Uint128>,
+        funding_dampening_ratio: Option<Uint128>,
+        continuous_funding: Option<bool>,
+    ) -> StdResult<CosmosMsg> {
+        wasm_execute(
+            &self.0,
+            &ExecuteMsg::UpdateFundingConfig {
+                max_funding_rate,
+                funding_interest_rate,
+                funding_dampening_ratio,
+                continuous_funding,
+            },
+            vec![],
+        )
+    }
+
+    pub fn update_spread_config(
+        &self,
+        dynamic_spread: Option<bool>,
+        max_spread_ratio: Option<Uint128>,
+    ) -> StdResult<CosmosMsg> {
+        wasm_execute(
+            &self.0,
+            &ExecuteMsg::UpdateSpreadConfig {
+                dynamic_spread,
+                max_spread_ratio,
+            },
+            vec![],
+        )
+    }
+
+    pub fn update_rolling_fluctuation_limit(
+        &self,
+        rolling_fluctuation_limit_ratio: Option<Uint128>,
+        rolling_fluctuation_window: Option<u64>,
+    ) -> StdResult<CosmosMsg> {
+        wasm_execute(
+            &self.0,
+            &ExecuteMsg::UpdateRollingFluctuationLimit {
+                rolling_fluctuation_limit_ratio,
+                rolling_fluctuation_window,
+            },
+            vec![],
+        )
+    }
+
     pub fn set_open(&self, open: bool) -> StdResult<CosmosMsg> {
         let msg = ExecuteMsg::SetOpen { open };
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn shutdown(&self) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Shutdown {};
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     pub fn swap_input(
         &self,
         direction: Direction,
@@ -147,6 +202,7 @@ impl VammController {
         quote_asset_amount: Uint128,
         base_asset_limit: Uint128,
         can_go_over_fluctuation: bool,
+        reduces_position: bool,
     ) -> StdResult<CosmosMsg> {
         let msg = ExecuteMsg::SwapInput {
             direction,
@@ -154,6 +210,7 @@ impl VammController {
             quote_asset_amount,
             base_asset_limit,
             can_go_over_fluctuation,
+            reduces_position,
         };
         wasm_execute(&self.0, &msg, vec![])
     }
@@ -179,6 +236,16 @@ impl VammController {
         wasm_execute(&self.0, &msg, vec![])
     }
 
+    pub fn repeg(&self, target_price: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::Repeg { target_price };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
+    pub fn adjust_k(&self, scale: Uint128) -> StdResult<CosmosMsg> {
+        let msg = ExecuteMsg::AdjustK { scale };
+        wasm_execute(&self.0, &msg, vec![])
+    }
+
     /// get margin vamm configuration
     pub fn config(&self, querier: &QuerierWrapper) -> StdResult<ConfigResponse> {
         querier.query_wasm_smart(&self.0, &QueryMsg::Config {})
@@ -216,8 +283,25 @@ impl VammController {
         &self,
         querier: &QuerierWrapper,
         quote_asset_amount: Uint128,
+        direction: Direction,
     ) -> StdResult<CalcFeeResponse> {
-        querier.query_wasm_smart(&self.0, &QueryMsg::CalcFee { quote_asset_amount })
+        querier.query_wasm_smart(
+            &self.0,
+            &QueryMsg::CalcFee {
+                quote_asset_amount,
+                direction,
+            },
+        )
+    }
+
+    /// returns bool if vamm is reduce only after tripping the rolling fluctuation limit
+    pub fn is_reduce_only(&self, querier: &QuerierWrapper) -> StdResult<bool> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::IsReduceOnly {})
+    }
+
+    /// get the price positions are settled at once the vamm is shut down
+    pub fn settlement_price(&self, querier: &QuerierWrapper) -> StdResult<Option<Uint128>> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::SettlementPrice {})
     }
 
     /// returns bool if vamm is over spread limit
@@ -267,4 +351,24 @@ impl VammController {
             },
         )
     }
+
+    /// get the funding rate of the last settlement before and after adjustments
+    pub fn funding_rate(&self, querier: &QuerierWrapper) -> StdResult<FundingRateResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::FundingRate {})
+    }
+
+    /// get the continuous funding index accrued up to the current block
+    pub fn funding_index(&self, querier: &QuerierWrapper) -> StdResult<FundingIndexResponse> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::FundingIndex {})
+    }
+
+    /// get the funding settlements in ascending order
+    pub fn funding_history(
+        &self,
+        querier: &QuerierWrapper,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<Vec<FundingRecord>> {
+        querier.query_wasm_smart(&self.0, &QueryMsg::FundingHistory { start_after, limit })
+    }
 }
@@ -1,3 +1,4 @@
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::contracts::helpers::{
@@ -11,6 +12,7 @@ use cosmwasm_std::{
 };
 use cw20::{Cw20Coin, Cw20Contract, Cw20ExecuteMsg, MinterResponse};
 use margined_common::asset::NATIVE_DENOM;
+use margined_common::integer::Integer;
 use margined_perp::margined_engine::{ExecuteMsg, InstantiateMsg, Side};
 use margined_perp::margined_fee_pool::InstantiateMsg as FeePoolInstantiateMsg;
 use margined_perp::margined_insurance_fund::InstantiateMsg as InsuranceFundInstantiateMsg;
@@ -21,7 +23,7 @@ use margined_perp::margined_vamm::{
     ExecuteMsg as VammExecuteMsg, InstantiateMsg as VammInstantiateMsg,
 };
 // use terra_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
-use cw_multi_test::{App, AppBuilder, Contract, Executor};
+use cw_multi_test::{App, AppBuilder, AppResponse, Contract, Executor};
 
 pub type ContractCode = Box<dyn Contract<Empty>>;
 
@@ -175,6 +177,8 @@ impl NativeTokenScenario {
                     partial_liquidation_ratio: None,
                     tp_sl_spread: None,
                     liquidation_fee: None,
+                    pricefeed: None,
+                    execution_fee: None,
                 },
                 &[],
             )
@@ -454,6 +458,8 @@ impl SimpleScenario {
                     partial_liquidation_ratio: None,
                     tp_sl_spread: None,
                     liquidation_fee: None,
+                    pricefeed: None,
+                    execution_fee: None,
                 },
                 &[],
             )
@@ -1018,3 +1024,18 @@ pub fn parse_event(res: &Response, key: &str) -> String {
 
     res.to_string()
 }
+
+// returns the cost of a repeg or k adjustment, when the engine settles it the last cost is its own
+pub fn adjustment_cost(res: &AppResponse) -> Integer {
+    let value = res
+        .events
+        .iter()
+        .flat_map(|event| event.attributes.iter())
+        .filter(|attr| attr.key == "adjustment_cost")
+        .last()
+        .unwrap()
+        .value
+        .clone();
+
+    Integer::from_str(&value).unwrap()
+}
@@ -1,6 +1,7 @@
 use cosmwasm_std::{Addr, Coin, QuerierWrapper, StdResult, Uint128};
 use margined_common::asset::NATIVE_DENOM;
 use margined_common::integer::Integer;
+use margined_perp::margined_vamm::Direction;
 
 use crate::contracts::helpers::VammController;
 
@@ -28,7 +29,10 @@ pub fn calculate_funds_needed(
     let vamm_controller = VammController(vamm.clone());
 
     // pull the fees for the vamm that the position will be taken on; note that this will be shifted however many digits
-    let fee_amount = vamm_controller.calc_fee(querier, new_notional)?.toll_fee;
+    // the toll fee does not depend on the direction of the trade
+    let fee_amount = vamm_controller
+        .calc_fee(querier, new_notional, Direction::AddToAmm)?
+        .toll_fee;
     let margin_owed = Integer::new_positive(quote_asset_amount);
 
     let funds_owed = if margin_owed.is_positive() {